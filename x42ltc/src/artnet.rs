@@ -0,0 +1,408 @@
+// x42ltc: src/artnet.rs
+//
+// Copyright 2019-2020 Johannes Maibaum <jmaibaum@gmail.com>
+//
+// This file is free software; you can redistribute it and/or modify it
+// under the terms of the GNU Lesser General Public License as
+// published by the Free Software Foundation; either version 3 of the
+// License, or (at your option) any later version.
+//
+// This file is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public
+// License along with this program.  If not, see <http://www.gnu.org/licenses/>.
+//
+// SPDX-License-Identifier: LGPL-3.0-or-later
+use std::io;
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr, ToSocketAddrs, UdpSocket};
+
+use crate::mtc::{frame_rate_from_code, rate_code};
+use crate::{DecodedFrame, Decoder, Error, FrameRate, Timecode};
+
+/// The UDP port of Art-Net.
+pub const ARTNET_PORT: u16 = 6454;
+
+const ARTNET_ID: &[u8; 8] = b"Art-Net\0";
+const OP_TIME_CODE: u16 = 0x9700;
+const PROTOCOL_VERSION: u16 = 14;
+
+/// The length of an `ArtTimeCode` packet in bytes.
+pub const ART_TIME_CODE_LEN: usize = 19;
+
+/// An Art-Net `ArtTimeCode` packet.
+///
+/// Its type code tells film (24 fps), EBU (25 fps), drop-frame (29.97 fps) and SMPTE (30 fps)
+/// timecode apart, the same way as MIDI Time Code: 23.976 fps is sent as film and 29.97 fps
+/// non-drop-frame as SMPTE timecode.
+///
+/// # Example
+///
+/// ```
+/// use x42ltc::{ArtTimeCode, FrameRate, Timecode};
+///
+/// let tc: Timecode = "10:20:30:12".parse().unwrap();
+/// let packet = ArtTimeCode::new(&tc, FrameRate::Fps25).unwrap().to_bytes();
+/// assert_eq!(&packet[..8], b"Art-Net\0");
+/// assert_eq!(packet[14..], [12, 30, 20, 10, 1]);
+///
+/// let parsed = ArtTimeCode::parse(&packet).unwrap();
+/// assert_eq!(parsed.timecode().to_string(), "10:20:30:12");
+/// assert_eq!(parsed.frame_rate(), FrameRate::Fps25);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ArtTimeCode {
+    timecode: Timecode,
+    frame_rate: FrameRate,
+    stream_id: u8,
+}
+
+impl ArtTimeCode {
+    /// A packet carrying `timecode` at `frame_rate`, for the master stream.
+    ///
+    /// Returns `Error::ValueOutOfRange` if `timecode` is not valid at `frame_rate`, or Art-Net
+    /// cannot carry `frame_rate`, i.e. above 30 fps.
+    pub fn new(timecode: &Timecode, frame_rate: FrameRate) -> Result<ArtTimeCode, Error> {
+        rate_code(frame_rate).ok_or(Error::ValueOutOfRange)?;
+        let timecode = timecode.with_frame_rate(frame_rate);
+        timecode.to_frame_number(frame_rate)?;
+        Ok(ArtTimeCode {
+            timecode,
+            frame_rate,
+            stream_id: 0,
+        })
+    }
+
+    /// Returns this packet for the timecode stream `stream_id`, 0 being the master stream.
+    pub fn with_stream_id(mut self, stream_id: u8) -> ArtTimeCode {
+        self.stream_id = stream_id;
+        self
+    }
+
+    /// The timecode, counting frames at [`.frame_rate()`](#method.frame_rate).
+    pub fn timecode(&self) -> Timecode {
+        self.timecode
+    }
+
+    /// The frame rate, [`FrameRate::Fps24`] for film and [`FrameRate::Fps30`] for SMPTE
+    /// timecode in parsed packets.
+    pub fn frame_rate(&self) -> FrameRate {
+        self.frame_rate
+    }
+
+    /// The timecode stream, 0 being the master stream.
+    pub fn stream_id(&self) -> u8 {
+        self.stream_id
+    }
+
+    /// The bytes of the packet.
+    pub fn to_bytes(&self) -> [u8; ART_TIME_CODE_LEN] {
+        let mut packet = [0; ART_TIME_CODE_LEN];
+        packet[..8].copy_from_slice(ARTNET_ID);
+        packet[8..10].copy_from_slice(&OP_TIME_CODE.to_le_bytes());
+        packet[10..12].copy_from_slice(&PROTOCOL_VERSION.to_be_bytes());
+        packet[13] = self.stream_id;
+        packet[14] = self.timecode.frame();
+        packet[15] = self.timecode.secs();
+        packet[16] = self.timecode.mins();
+        packet[17] = self.timecode.hours();
+        packet[18] = rate_code(self.frame_rate).unwrap();
+        packet
+    }
+
+    /// Parse an `ArtTimeCode` packet.
+    ///
+    /// Returns `Error::ParseFailed` if `packet` is not an `ArtTimeCode` packet, and
+    /// `Error::ValueOutOfRange` if the timecode is not valid at the rate of its type code.
+    pub fn parse(packet: &[u8]) -> Result<ArtTimeCode, Error> {
+        if packet.len() < ART_TIME_CODE_LEN
+            || &packet[..8] != ARTNET_ID
+            || packet[8..10] != OP_TIME_CODE.to_le_bytes()
+        {
+            return Err(Error::ParseFailed);
+        }
+        if packet[18] > 3 {
+            return Err(Error::ValueOutOfRange);
+        }
+        let frame_rate = frame_rate_from_code(packet[18]);
+        let timecode = Timecode::new(packet[17], packet[16], packet[15], packet[14])?;
+        Ok(ArtTimeCode::new(&timecode, frame_rate)?.with_stream_id(packet[13]))
+    }
+}
+
+fn invalid_input(error: Error) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, error)
+}
+
+/// Sends timecode as `ArtTimeCode` packets over UDP.
+///
+/// # Example
+///
+/// Forward the frames from a [`Decoder`]:
+///
+/// ```
+/// use x42ltc::{ArtNetReceiver, ArtNetSender, Decoder, Encoder, FrameRate};
+///
+/// let receiver = ArtNetReceiver::bind("127.0.0.1:0").unwrap();
+/// let sender = ArtNetSender::new(receiver.local_addr().unwrap(), FrameRate::Fps25).unwrap();
+///
+/// let mut encoder = Encoder::new(48_000, FrameRate::Fps25).unwrap();
+/// let mut decoder = Decoder::new(1920, 32).unwrap();
+/// for _ in 0..3 {
+///     encoder.encode_frame();
+///     decoder.write(encoder.get_buffer());
+///     encoder.increase_timecode();
+/// }
+/// assert_eq!(sender.forward(&mut decoder).unwrap(), 2);
+///
+/// let (packet, _) = receiver.recv().unwrap();
+/// assert_eq!(packet.timecode().to_string(), "00:00:00:00");
+/// ```
+#[derive(Debug)]
+pub struct ArtNetSender {
+    socket: UdpSocket,
+    destination: SocketAddr,
+    frame_rate: FrameRate,
+    stream_id: u8,
+}
+
+impl ArtNetSender {
+    /// Create a sender of timecode at `frame_rate` to `destination`, usually the broadcast
+    /// address of the Art-Net network and [`ARTNET_PORT`].  The socket is bound to an arbitrary
+    /// local port.
+    ///
+    /// Returns an error of kind [`InvalidInput`](io::ErrorKind::InvalidInput) if Art-Net cannot
+    /// carry `frame_rate`.
+    pub fn new(destination: impl ToSocketAddrs, frame_rate: FrameRate) -> io::Result<ArtNetSender> {
+        rate_code(frame_rate).ok_or_else(|| invalid_input(Error::ValueOutOfRange))?;
+        let destination = destination
+            .to_socket_addrs()?
+            .next()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "no destination address"))?;
+        let socket = match destination {
+            SocketAddr::V4(_) => UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0))?,
+            SocketAddr::V6(_) => UdpSocket::bind((Ipv6Addr::UNSPECIFIED, 0))?,
+        };
+        if destination.is_ipv4() {
+            socket.set_broadcast(true)?;
+        }
+        Ok(ArtNetSender {
+            socket,
+            destination,
+            frame_rate,
+            stream_id: 0,
+        })
+    }
+
+    /// Send to the timecode stream `stream_id` instead of the master stream.
+    pub fn set_stream_id(&mut self, stream_id: u8) {
+        self.stream_id = stream_id;
+    }
+
+    /// The socket the packets are sent from, e.g. to set its time to live.
+    pub fn socket(&self) -> &UdpSocket {
+        &self.socket
+    }
+
+    /// The address the packets are sent to.
+    pub fn destination(&self) -> SocketAddr {
+        self.destination
+    }
+
+    /// The frame rate of the timecode.
+    pub fn frame_rate(&self) -> FrameRate {
+        self.frame_rate
+    }
+
+    /// Send `timecode`.
+    ///
+    /// Returns an error of kind [`InvalidInput`](io::ErrorKind::InvalidInput) if `timecode` is
+    /// not valid at the frame rate of the sender.
+    pub fn send(&self, timecode: &Timecode) -> io::Result<()> {
+        let packet = ArtTimeCode::new(timecode, self.frame_rate)
+            .map_err(invalid_input)?
+            .with_stream_id(self.stream_id);
+        self.socket.send_to(&packet.to_bytes(), self.destination)?;
+        Ok(())
+    }
+
+    /// Send the timecode of `frame`, like [`.send()`](#method.send).
+    pub fn send_frame(&self, frame: &DecodedFrame) -> io::Result<()> {
+        self.send(&frame.timecode())
+    }
+
+    /// Send the timecode of all frames queued in `decoder`, returns the number of frames sent.
+    /// Frames which are not valid timecode at the frame rate of the sender are skipped.
+    pub fn forward(&self, decoder: &mut Decoder) -> io::Result<usize> {
+        let mut sent = 0;
+        for frame in decoder.frames() {
+            match self.send_frame(&frame) {
+                Ok(()) => sent += 1,
+                Err(e) if e.kind() == io::ErrorKind::InvalidInput => {}
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(sent)
+    }
+}
+
+/// Receives `ArtTimeCode` packets over UDP.
+#[derive(Debug)]
+pub struct ArtNetReceiver {
+    socket: UdpSocket,
+}
+
+impl ArtNetReceiver {
+    /// Create a receiver listening on `address`, usually `0.0.0.0` and [`ARTNET_PORT`].
+    pub fn bind(address: impl ToSocketAddrs) -> io::Result<ArtNetReceiver> {
+        Ok(ArtNetReceiver {
+            socket: UdpSocket::bind(address)?,
+        })
+    }
+
+    /// The socket the packets are received on, e.g. to set a read timeout.
+    pub fn socket(&self) -> &UdpSocket {
+        &self.socket
+    }
+
+    /// The local address of the socket.
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.socket.local_addr()
+    }
+
+    /// Wait for the next `ArtTimeCode` packet, returns it along with the address of its sender.
+    /// Other packets are skipped.
+    pub fn recv(&self) -> io::Result<(ArtTimeCode, SocketAddr)> {
+        // Larger than any Art-Net packet, so that long packets are not mistaken for short ones.
+        let mut buffer = [0; 1024];
+        loop {
+            let (len, source) = self.socket.recv_from(&mut buffer)?;
+            if let Ok(packet) = ArtTimeCode::parse(&buffer[..len]) {
+                return Ok((packet, source));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::Encoder;
+
+    #[test]
+    fn art_time_code_round_trips_all_types() {
+        for (frame_rate, type_code, parsed_rate) in [
+            (FrameRate::Fps23_976, 0, FrameRate::Fps24),
+            (FrameRate::Fps24, 0, FrameRate::Fps24),
+            (FrameRate::Fps25, 1, FrameRate::Fps25),
+            (FrameRate::Fps29_97Df, 2, FrameRate::Fps29_97Df),
+            (FrameRate::Fps29_97, 3, FrameRate::Fps30),
+            (FrameRate::Fps30, 3, FrameRate::Fps30),
+        ] {
+            let tc = Timecode::new(23, 59, 59, 20).unwrap();
+            let packet = ArtTimeCode::new(&tc, frame_rate)
+                .unwrap()
+                .with_stream_id(7)
+                .to_bytes();
+            assert_eq!(
+                packet,
+                [
+                    b'A', b'r', b't', b'-', b'N', b'e', b't', 0, 0x00, 0x97, 0, 14, 0, 7, 20, 59,
+                    59, 23, type_code
+                ]
+            );
+            let parsed = ArtTimeCode::parse(&packet).unwrap();
+            assert_eq!(parsed.frame_rate(), parsed_rate);
+            assert_eq!(parsed.stream_id(), 7);
+            assert_eq!(parsed.timecode(), tc.with_frame_rate(parsed_rate));
+        }
+
+        assert!(ArtTimeCode::new(&Timecode::default(), FrameRate::Fps50).is_err());
+        let tc = Timecode::new(0, 0, 0, 25).unwrap();
+        assert!(ArtTimeCode::new(&tc, FrameRate::Fps25).is_err());
+    }
+
+    #[test]
+    fn art_time_code_rejects_other_packets() {
+        let packet = ArtTimeCode::new(&Timecode::default(), FrameRate::Fps25)
+            .unwrap()
+            .to_bytes();
+        assert!(matches!(
+            ArtTimeCode::parse(&packet[..18]),
+            Err(Error::ParseFailed)
+        ));
+        let mut art_dmx = packet;
+        art_dmx[9] = 0x50;
+        assert!(matches!(
+            ArtTimeCode::parse(&art_dmx),
+            Err(Error::ParseFailed)
+        ));
+        let mut invalid_type = packet;
+        invalid_type[18] = 4;
+        assert!(matches!(
+            ArtTimeCode::parse(&invalid_type),
+            Err(Error::ValueOutOfRange)
+        ));
+        let mut invalid_frame = packet;
+        invalid_frame[14] = 25;
+        assert!(matches!(
+            ArtTimeCode::parse(&invalid_frame),
+            Err(Error::ValueOutOfRange)
+        ));
+    }
+
+    #[test]
+    fn artnet_sender_forwards_decoded_frames_over_loopback() {
+        let receiver = ArtNetReceiver::bind("127.0.0.1:0").unwrap();
+        receiver
+            .socket()
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+        let mut sender =
+            ArtNetSender::new(receiver.local_addr().unwrap(), FrameRate::Fps29_97Df).unwrap();
+        sender.set_stream_id(3);
+        assert!(ArtNetSender::new(receiver.local_addr().unwrap(), FrameRate::Fps60).is_err());
+
+        // Other packets are skipped.
+        let other = UdpSocket::bind("127.0.0.1:0").unwrap();
+        other
+            .send_to(b"Art-Net\0\x00\x50", receiver.local_addr().unwrap())
+            .unwrap();
+
+        let mut encoder = Encoder::new(48_000, FrameRate::Fps29_97Df).unwrap();
+        encoder.set_timecode("00:00:59;28".parse().unwrap());
+        let mut decoder = Decoder::with_sample_rate(48_000).unwrap();
+        let mut sent = 0;
+        for _ in 0..6 {
+            encoder.encode_frame();
+            decoder.write(encoder.get_buffer());
+            sent += sender.forward(&mut decoder).unwrap();
+            encoder.increase_timecode();
+        }
+        assert_eq!(sent, 5);
+
+        let received: Vec<String> = (0..sent)
+            .map(|_| {
+                let (packet, source) = receiver.recv().unwrap();
+                assert_eq!(source.port(), sender.socket().local_addr().unwrap().port());
+                assert_eq!(packet.stream_id(), 3);
+                assert_eq!(packet.frame_rate(), FrameRate::Fps29_97Df);
+                packet.timecode().to_string()
+            })
+            .collect();
+        assert_eq!(
+            received,
+            [
+                "00:00:59;28",
+                "00:00:59;29",
+                "00:01:00;02",
+                "00:01:00;03",
+                "00:01:00;04"
+            ]
+        );
+    }
+}
@@ -0,0 +1,197 @@
+// libltc: src/bin/common/mod.rs
+//
+// Copyright 2019-2020 Johannes Maibaum <jmaibaum@gmail.com>
+//
+// This file is free software; you can redistribute it and/or modify it
+// under the terms of the GNU Lesser General Public License as
+// published by the Free Software Foundation; either version 3 of the
+// License, or (at your option) any later version.
+//
+// This file is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public
+// License along with this program.  If not, see <http://www.gnu.org/licenses/>.
+//
+// SPDX-License-Identifier: LGPL-3.0-or-later
+
+// Option parsing and file handling shared by the command line tools, each of which uses only some
+// of it.
+#![allow(dead_code)]
+
+use std::fs::File;
+use std::io::{self, BufWriter, Cursor, Read, Seek, Write};
+use std::str::FromStr;
+
+use x42ltc::*;
+
+/// The number of samples of raw input read at once.
+const CHUNK_SAMPLES: usize = 4096;
+
+pub fn parse_value<T: FromStr>(option: &str, value: &str) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("invalid value for {}: {}", option, value))
+}
+
+pub fn parse_sample_format(value: &str) -> Result<SampleFormat, String> {
+    match value {
+        "u8" => Ok(SampleFormat::U8),
+        "s16" => Ok(SampleFormat::I16),
+        "s24" => Ok(SampleFormat::I24),
+        "f32" => Ok(SampleFormat::F32),
+        _ => Err(format!("invalid sample format: {}", value)),
+    }
+}
+
+pub fn read_error(e: io::Error) -> String {
+    format!("read error: {}", e)
+}
+
+pub fn write_error(e: io::Error) -> String {
+    format!("write error: {}", e)
+}
+
+/// Open `input`, or standard input if it is `-`, returns whether it is a WAV file along with a
+/// reader for all of it.
+pub fn open(input: &str) -> Result<(Box<dyn Read>, bool), String> {
+    let mut reader: Box<dyn Read> = if input == "-" {
+        Box::new(io::stdin().lock())
+    } else {
+        let file = File::open(input).map_err(|e| format!("cannot open {}: {}", input, e))?;
+        Box::new(io::BufReader::new(file))
+    };
+
+    let mut header = [0; 12];
+    let len = read_up_to(&mut reader, &mut header).map_err(read_error)?;
+    let is_wav = len == 12 && &header[..4] == b"RIFF" && &header[8..] == b"WAVE";
+    // Put back the bytes already read.
+    let prefix = Cursor::new(header[..len].to_vec());
+    Ok((Box::new(prefix.chain(reader)), is_wav))
+}
+
+/// Like `read_exact()`, but returns the number of bytes read at the end of the input.
+fn read_up_to(reader: &mut impl Read, buf: &mut [u8]) -> io::Result<usize> {
+    let mut len = 0;
+    while len < buf.len() {
+        match reader.read(&mut buf[len..])? {
+            0 => break,
+            n => len += n,
+        }
+    }
+    Ok(len)
+}
+
+/// Decode `input`, a WAV file with the LTC on `channel` (counting from 1), or raw mono samples
+/// in `format` at `sample_rate`, and pass the frames to `each`.  Returns the sample rate.
+#[cfg(feature = "wav")]
+pub fn decode(
+    input: &str,
+    format: SampleFormat,
+    sample_rate: u32,
+    frame_rate: FrameRate,
+    channel: u16,
+    mut each: impl FnMut(DecodedFrame) -> Result<(), String>,
+) -> Result<u32, String> {
+    let (mut reader, is_wav) = open(input)?;
+    if is_wav {
+        let mut frames =
+            decode_wav_with_frame_rate(reader, channel - 1, frame_rate).map_err(read_error)?;
+        for frame in frames.by_ref() {
+            each(frame)?;
+        }
+        if let Some(e) = frames.error() {
+            return Err(format!("read error: {}", e));
+        }
+        return Ok(frames.sample_rate());
+    }
+
+    let mut decoder = Decoder::with_frame_rate(sample_rate, frame_rate, 32)
+        .map_err(|_| format!("cannot decode at {} Hz", sample_rate))?;
+    let sample_len = match format {
+        SampleFormat::U8 => 1,
+        SampleFormat::I16 => 2,
+        SampleFormat::I24 => 3,
+        SampleFormat::F32 => 4,
+    };
+    let mut buffer = vec![0; CHUNK_SAMPLES * sample_len];
+    let mut filled = 0;
+    let mut i16_samples = Vec::with_capacity(CHUNK_SAMPLES);
+    let mut f32_samples = Vec::with_capacity(CHUNK_SAMPLES);
+    loop {
+        let len = reader.read(&mut buffer[filled..]).map_err(read_error)?;
+        if len == 0 {
+            return Ok(sample_rate);
+        }
+        filled += len;
+        let usable = filled - filled % sample_len;
+        let bytes = buffer[..usable].chunks_exact(sample_len);
+        let frames =
+            match format {
+                SampleFormat::U8 => decoder.decode(&buffer[..usable]),
+                SampleFormat::I16 => {
+                    i16_samples.clear();
+                    i16_samples.extend(bytes.map(|s| i16::from_le_bytes([s[0], s[1]])));
+                    decoder.decode(&i16_samples)
+                }
+                SampleFormat::I24 => {
+                    f32_samples.clear();
+                    f32_samples.extend(bytes.map(|s| {
+                        i32::from_le_bytes([0, s[0], s[1], s[2]]) as f32 / 2_147_483_648.0
+                    }));
+                    decoder.decode(&f32_samples)
+                }
+                SampleFormat::F32 => {
+                    f32_samples.clear();
+                    f32_samples.extend(bytes.map(|s| f32::from_le_bytes([s[0], s[1], s[2], s[3]])));
+                    decoder.decode(&f32_samples)
+                }
+            };
+        for frame in frames {
+            each(frame)?;
+        }
+        buffer.copy_within(usable..filled, 0);
+        filled -= usable;
+    }
+}
+
+/// Create `output`, or write to standard output if it is `-`.
+pub fn create(output: &str) -> Result<BufWriter<Box<dyn Write>>, String> {
+    let writer: Box<dyn Write> = if output == "-" {
+        Box::new(io::stdout().lock())
+    } else {
+        Box::new(File::create(output).map_err(|e| format!("cannot create {}: {}", output, e))?)
+    };
+    Ok(BufWriter::new(writer))
+}
+
+/// A seekable output for [`WavWriter`].
+pub trait WriteSeek: Write + Seek {}
+
+impl<W: Write + Seek> WriteSeek for W {}
+
+/// Write a WAV file laid out by `config` to `output`, or to standard output if it is `-`, with
+/// the samples written by `write`.
+pub fn write_wav(
+    output: &str,
+    config: WavConfig,
+    write: impl FnOnce(&mut WavWriter<&mut dyn WriteSeek>) -> io::Result<()>,
+) -> Result<(), String> {
+    let write = |writer: &mut dyn WriteSeek| {
+        let mut wav = WavWriter::new(writer, config)?;
+        write(&mut wav)?;
+        wav.finish().map(|_| ())
+    };
+    if output == "-" {
+        // Standard output is not seekable, so the file is assembled in memory.
+        let mut file = Cursor::new(Vec::new());
+        write(&mut file).map_err(write_error)?;
+        io::stdout().write_all(file.get_ref()).map_err(write_error)
+    } else {
+        let file = File::create(output).map_err(|e| format!("cannot create {}: {}", output, e))?;
+        let mut file = BufWriter::new(file);
+        write(&mut file).map_err(write_error)
+    }
+}
@@ -0,0 +1,253 @@
+// libltc: src/bin/ltc2mtc.rs
+//
+// Copyright 2019-2020 Johannes Maibaum <jmaibaum@gmail.com>
+//
+// This file is free software; you can redistribute it and/or modify it
+// under the terms of the GNU Lesser General Public License as
+// published by the Free Software Foundation; either version 3 of the
+// License, or (at your option) any later version.
+//
+// This file is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public
+// License along with this program.  If not, see <http://www.gnu.org/licenses/>.
+//
+// SPDX-License-Identifier: LGPL-3.0-or-later
+use std::env;
+use std::io::Write;
+use std::process;
+
+use x42ltc::*;
+
+mod common;
+use common::*;
+
+const USAGE: &str = "\
+Usage: ltc2mtc [OPTIONS] [INPUT] [OUTPUT]
+
+Decode LTC from INPUT and write it as MIDI Time Code to OUTPUT, a Standard MIDI
+File with one track and millisecond ticks.  INPUT and OUTPUT default to
+standard input and output, or are '-'.  WAV files are detected automatically,
+anything else is read as raw mono samples.
+
+Options:
+  -f, --format FMT         Sample format of raw input: u8, s16, s24 or f32,
+                           the latter three little endian (default: u8)
+  -r, --sample-rate HZ     Sample rate of raw input (default: 48000)
+      --fps RATE           Frame rate of the LTC: 23.976, 24, 25, 29.97df,
+                           29.97 or 30 (default: 25)
+  -c, --channel N          Channel of WAV input carrying the LTC (default: 1)
+      --raw                Write the plain MIDI bytes without timestamps
+                           instead of a MIDI file
+  -h, --help               Show this help
+";
+
+/// The time division of the MIDI file: 25 fps SMPTE time with 40 ticks per frame, i.e. one
+/// tick per millisecond.
+const DIVISION: [u8; 2] = [-25i8 as u8, 40];
+const TICKS_PER_SECOND: f64 = 1000.0;
+
+struct Options {
+    input: String,
+    output: String,
+    format: SampleFormat,
+    sample_rate: u32,
+    frame_rate: FrameRate,
+    channel: u16,
+    raw: bool,
+}
+
+impl Options {
+    fn parse(mut args: impl Iterator<Item = String>) -> Result<Options, String> {
+        let mut options = Options {
+            input: String::from("-"),
+            output: String::from("-"),
+            format: SampleFormat::U8,
+            sample_rate: 48_000,
+            frame_rate: FrameRate::Fps25,
+            channel: 1,
+            raw: false,
+        };
+        let mut files = Vec::new();
+
+        while let Some(arg) = args.next() {
+            let mut value = || args.next().ok_or(format!("missing value for {}", arg));
+            match arg.as_str() {
+                "-f" | "--format" => options.format = parse_sample_format(&value()?)?,
+                "-r" | "--sample-rate" => options.sample_rate = parse_value(&arg, &value()?)?,
+                "--fps" => options.frame_rate = parse_value(&arg, &value()?)?,
+                "-c" | "--channel" => options.channel = parse_value(&arg, &value()?)?,
+                "--raw" => options.raw = true,
+                "-h" | "--help" => {
+                    print!("{}", USAGE);
+                    process::exit(0);
+                }
+                _ if arg.starts_with('-') && arg != "-" => {
+                    return Err(format!("unknown option {}", arg));
+                }
+                _ if files.len() < 2 => files.push(arg),
+                _ => return Err(format!("unexpected argument {}", arg)),
+            }
+        }
+
+        if options.channel == 0 {
+            return Err(String::from("channels are counted from 1"));
+        }
+        let mut files = files.into_iter();
+        if let Some(input) = files.next() {
+            options.input = input;
+        }
+        if let Some(output) = files.next() {
+            options.output = output;
+        }
+        Ok(options)
+    }
+}
+
+/// Decode the input, returns its sample rate and the MTC messages of the LTC.
+fn decode_mtc(options: &Options) -> Result<(u32, Vec<TimedMtcMessage>), String> {
+    let mut bridge = MtcBridge::new(options.frame_rate)
+        .map_err(|_| format!("MTC cannot carry {} fps", options.frame_rate))?;
+    let mut messages = Vec::new();
+    let sample_rate = decode(
+        &options.input,
+        options.format,
+        options.sample_rate,
+        options.frame_rate,
+        options.channel,
+        |frame| {
+            messages.extend(bridge.process(&frame));
+            Ok(())
+        },
+    )?;
+    Ok((sample_rate, messages))
+}
+
+/// Append `value` as variable-length quantity.
+fn push_vlq(bytes: &mut Vec<u8>, value: u32) {
+    let mut shift = 28;
+    while shift > 0 && value >> shift == 0 {
+        shift -= 7;
+    }
+    while shift > 0 {
+        bytes.push((value >> shift) as u8 & 0x7F | 0x80);
+        shift -= 7;
+    }
+    bytes.push(value as u8 & 0x7F);
+}
+
+/// A Standard MIDI File of format 0 with the messages at their sample offsets.
+fn midi_file(sample_rate: u32, messages: &[TimedMtcMessage]) -> Vec<u8> {
+    let mut track = Vec::new();
+    let mut tick = 0;
+    for timed in messages {
+        // The first frame may start a few samples before the audio does.
+        let seconds = timed.offset.max(0) as f64 / f64::from(sample_rate);
+        let time = (seconds * TICKS_PER_SECOND).round() as u32;
+        push_vlq(&mut track, time.max(tick) - tick);
+        tick = time.max(tick);
+
+        let bytes = timed.message.to_bytes();
+        match timed.message {
+            MtcMessage::QuarterFrame(_) => {
+                // System common messages need an escape event.
+                track.push(0xF7);
+                push_vlq(&mut track, bytes.len() as u32);
+                track.extend_from_slice(&bytes);
+            }
+            MtcMessage::FullFrame(_) => {
+                track.push(0xF0);
+                push_vlq(&mut track, bytes.len() as u32 - 1);
+                track.extend_from_slice(&bytes[1..]);
+            }
+        }
+    }
+    // End of track.
+    track.extend_from_slice(&[0, 0xFF, 0x2F, 0]);
+
+    let mut file = Vec::new();
+    file.extend_from_slice(b"MThd");
+    file.extend_from_slice(&6u32.to_be_bytes());
+    // Format 0, one track.
+    file.extend_from_slice(&[0, 0, 0, 1]);
+    file.extend_from_slice(&DIVISION);
+    file.extend_from_slice(b"MTrk");
+    file.extend_from_slice(&(track.len() as u32).to_be_bytes());
+    file.extend_from_slice(&track);
+    file
+}
+
+fn convert(options: &Options) -> Result<(), String> {
+    let (sample_rate, messages) = decode_mtc(options)?;
+    let bytes = if options.raw {
+        messages
+            .iter()
+            .flat_map(|timed| timed.message.to_bytes())
+            .collect()
+    } else {
+        midi_file(sample_rate, &messages)
+    };
+
+    let mut output = create(&options.output)?;
+    output.write_all(&bytes).map_err(write_error)?;
+    output.flush().map_err(write_error)
+}
+
+fn main() {
+    let result = Options::parse(env::args().skip(1)).and_then(|options| convert(&options));
+    if let Err(message) = result {
+        eprintln!("ltc2mtc: {}", message);
+        eprintln!("Try 'ltc2mtc --help' for more information.");
+        process::exit(1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn push_vlq_writes_variable_length_quantities() {
+        let vlq = |value| {
+            let mut bytes = Vec::new();
+            push_vlq(&mut bytes, value);
+            bytes
+        };
+        assert_eq!(vlq(0), [0x00]);
+        assert_eq!(vlq(0x7F), [0x7F]);
+        assert_eq!(vlq(0x80), [0x81, 0x00]);
+        assert_eq!(vlq(0x2000), [0xC0, 0x00]);
+        assert_eq!(vlq(0x1F_FFFF), [0xFF, 0xFF, 0x7F]);
+        assert_eq!(vlq(0x0FFF_FFFF), [0xFF, 0xFF, 0xFF, 0x7F]);
+    }
+
+    #[test]
+    fn midi_file_escapes_quarter_frames_at_millisecond_ticks() {
+        let messages = [
+            TimedMtcMessage {
+                offset: 0,
+                message: MtcMessage::FullFrame([0x21, 2, 3, 4]),
+            },
+            TimedMtcMessage {
+                offset: 9_600,
+                message: MtcMessage::QuarterFrame(0x15),
+            },
+        ];
+        let file = midi_file(48_000, &messages);
+        assert_eq!(&file[..8], b"MThd\0\0\0\x06");
+        assert_eq!(&file[8..14], [0, 0, 0, 1, 0xE7, 40]);
+        assert_eq!(&file[14..22], b"MTrk\0\0\0\x16");
+        assert_eq!(
+            file[22..],
+            [
+                0x00, 0xF0, 0x09, 0x7F, 0x7F, 0x01, 0x01, 0x21, 0x02, 0x03, 0x04,
+                0xF7, // full frame
+                0x81, 0x48, 0xF7, 0x02, 0xF1, 0x15, // quarter frame 200 ms later
+                0x00, 0xFF, 0x2F, 0x00, // end of track
+            ]
+        );
+    }
+}
@@ -0,0 +1,229 @@
+// libltc: src/bin/ltcdump.rs
+//
+// Copyright 2019-2020 Johannes Maibaum <jmaibaum@gmail.com>
+//
+// This file is free software; you can redistribute it and/or modify it
+// under the terms of the GNU Lesser General Public License as
+// published by the Free Software Foundation; either version 3 of the
+// License, or (at your option) any later version.
+//
+// This file is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public
+// License along with this program.  If not, see <http://www.gnu.org/licenses/>.
+//
+// SPDX-License-Identifier: LGPL-3.0-or-later
+use std::env;
+use std::io::{self, BufWriter, Write};
+use std::process;
+
+use x42ltc::*;
+
+mod common;
+use common::*;
+
+const USAGE: &str = "\
+Usage: ltcdump [OPTIONS] [INPUT]
+
+Decode LTC from INPUT, or from standard input if INPUT is '-' or missing, and
+print one line per frame.  WAV files are detected automatically, anything else
+is read as raw mono samples.
+
+Options:
+  -f, --format FMT         Sample format of raw input: u8, s16, s24 or f32,
+                           the latter three little endian (default: u8)
+  -r, --sample-rate HZ     Sample rate of raw input (default: 48000)
+      --fps RATE           Expected frame rate, which helps decoding the first
+                           frame (default: 25)
+  -c, --channel N          Channel of WAV input carrying the LTC (default: 1)
+  -d, --date               Print the user bits as date and timezone (SMPTE 309)
+  -o, --output MODE        Output format: text, json (one object per line) or
+                           csv (default: text)
+  -h, --help               Show this help
+";
+
+#[derive(Clone, Copy, PartialEq)]
+enum Output {
+    Text,
+    Json,
+    Csv,
+}
+
+struct Options {
+    input: String,
+    format: SampleFormat,
+    sample_rate: u32,
+    frame_rate: FrameRate,
+    channel: u16,
+    date: bool,
+    output: Output,
+}
+
+impl Options {
+    fn parse(mut args: impl Iterator<Item = String>) -> Result<Options, String> {
+        let mut options = Options {
+            input: String::from("-"),
+            format: SampleFormat::U8,
+            sample_rate: 48_000,
+            frame_rate: FrameRate::Fps25,
+            channel: 1,
+            date: false,
+            output: Output::Text,
+        };
+        let mut input = None;
+
+        while let Some(arg) = args.next() {
+            let mut value = || args.next().ok_or(format!("missing value for {}", arg));
+            match arg.as_str() {
+                "-f" | "--format" => options.format = parse_sample_format(&value()?)?,
+                "-r" | "--sample-rate" => options.sample_rate = parse_value(&arg, &value()?)?,
+                "--fps" => options.frame_rate = parse_value(&arg, &value()?)?,
+                "-c" | "--channel" => options.channel = parse_value(&arg, &value()?)?,
+                "-d" | "--date" => options.date = true,
+                "-o" | "--output" => {
+                    options.output = match value()?.as_str() {
+                        "text" => Output::Text,
+                        "json" => Output::Json,
+                        "csv" => Output::Csv,
+                        output => return Err(format!("invalid output format: {}", output)),
+                    }
+                }
+                "-h" | "--help" => {
+                    print!("{}", USAGE);
+                    process::exit(0);
+                }
+                _ if arg.starts_with('-') && arg != "-" => {
+                    return Err(format!("unknown option {}", arg));
+                }
+                _ if input.is_none() => input = Some(arg),
+                _ => return Err(format!("unexpected argument {}", arg)),
+            }
+        }
+
+        if options.channel == 0 {
+            return Err(String::from("channels are counted from 1"));
+        }
+        if let Some(input) = input {
+            options.input = input;
+        }
+        Ok(options)
+    }
+}
+
+fn dump(options: &Options) -> Result<(), String> {
+    let mut output = BufWriter::new(io::stdout().lock());
+    print_header(&mut output, options).map_err(write_error)?;
+    decode(
+        &options.input,
+        options.format,
+        options.sample_rate,
+        options.frame_rate,
+        options.channel,
+        |frame| print_frame(&mut output, options, &frame).map_err(write_error),
+    )?;
+    output.flush().map_err(write_error)
+}
+
+fn print_header(output: &mut impl Write, options: &Options) -> io::Result<()> {
+    match options.output {
+        Output::Text if options.date => {
+            writeln!(output, "#Date      Zone  Timecode    |    Pos. (samples)")
+        }
+        Output::Text => writeln!(output, "#User bits Timecode    |    Pos. (samples)"),
+        Output::Json => Ok(()),
+        Output::Csv => writeln!(
+            output,
+            "date,timezone,user_bits,timecode,drop_frame,off_start,off_end,reverse,volume"
+        ),
+    }
+}
+
+fn print_frame(output: &mut impl Write, options: &Options, frame: &DecodedFrame) -> io::Result<()> {
+    let tc = frame.timecode();
+    // The time of day with the drop-frame separator used by libltc's ltcdump.
+    let time = format!(
+        "{:02}:{:02}:{:02}{}{:02}",
+        tc.hours(),
+        tc.mins(),
+        tc.secs(),
+        if tc.is_drop_frame() { '.' } else { ':' },
+        tc.frame()
+    );
+    let user_bits = format!("{:08x}", frame.frame().user_bits());
+    let date = tc.date().filter(|_| options.date);
+    let timezone = tc.timezone().filter(|_| options.date);
+
+    match options.output {
+        Output::Text => {
+            if options.date {
+                write!(
+                    output,
+                    "{} {} ",
+                    date.map_or_else(|| String::from("2000-00-00"), |date| date.to_string()),
+                    timezone.unwrap_or(TimezoneOffset::UTC)
+                )?;
+            } else {
+                write!(output, "{}  ", user_bits)?;
+            }
+            writeln!(
+                output,
+                "{} | {:8} {:8}{}",
+                time,
+                frame.off_start(),
+                frame.off_end(),
+                if frame.is_reverse() { "  R" } else { "" }
+            )
+        }
+        Output::Json => {
+            let string_or_null = |value: Option<String>| {
+                value.map_or_else(|| String::from("null"), |value| format!("\"{}\"", value))
+            };
+            writeln!(
+                output,
+                "{{\"date\":{},\"timezone\":{},\"user_bits\":\"{}\",\"timecode\":\"{}\",\
+                 \"drop_frame\":{},\"off_start\":{},\"off_end\":{},\"reverse\":{},\"volume\":{}}}",
+                string_or_null(date.map(|date| date.to_string())),
+                string_or_null(timezone.map(|timezone| timezone.to_string())),
+                user_bits,
+                tc,
+                tc.is_drop_frame(),
+                frame.off_start(),
+                frame.off_end(),
+                frame.is_reverse(),
+                // JSON has no infinity, silence is reported as null.
+                if frame.volume().is_finite() {
+                    format!("{:.1}", frame.volume())
+                } else {
+                    String::from("null")
+                },
+            )
+        }
+        Output::Csv => writeln!(
+            output,
+            "{},{},{},{},{},{},{},{},{:.1}",
+            date.map(|date| date.to_string()).unwrap_or_default(),
+            timezone
+                .map(|timezone| timezone.to_string())
+                .unwrap_or_default(),
+            user_bits,
+            tc,
+            tc.is_drop_frame(),
+            frame.off_start(),
+            frame.off_end(),
+            frame.is_reverse(),
+            frame.volume()
+        ),
+    }
+}
+
+fn main() {
+    let result = Options::parse(env::args().skip(1)).and_then(|options| dump(&options));
+    if let Err(message) = result {
+        eprintln!("ltcdump: {}", message);
+        eprintln!("Try 'ltcdump --help' for more information.");
+        process::exit(1);
+    }
+}
@@ -16,36 +16,287 @@
 // License along with this program.  If not, see <http://www.gnu.org/licenses/>.
 //
 // SPDX-License-Identifier: LGPL-3.0-or-later
-use std::fs::File;
+use std::env;
 use std::io::Write;
+use std::process;
+
 use x42ltc::*;
 
-fn to_bcd(n: u32) -> u32 {
-    let mut remainder = n;
-    let mut bcd = 0;
-    for i in 0..8 {
-        bcd |= (remainder % 10) << (i * 4);
-        remainder /= 10;
-        if remainder == 0 {
-            break;
+mod common;
+use common::*;
+
+const USAGE: &str = "\
+Usage: ltcgenerator [OPTIONS] [OUTPUT]
+
+Generate LTC audio as raw unsigned 8 bit mono samples or as a Broadcast Wave
+file, written to OUTPUT (default: output.raw), or to standard output if OUTPUT
+is '-'.
+
+Options:
+  -s, --start TIMECODE     First timecode, HH:MM:SS:FF (default: 00:00:00:00)
+  -d, --duration SECONDS   Length of the generated audio (default: 10)
+  -e, --end TIMECODE       Stop before this timecode, instead of --duration
+  -f, --fps RATE           Frame rate: 23.976, 24, 25, 29.97, 29.97df, 30, 48,
+                           50, 59.94, 59.94df or 60 (default: 25)
+  -r, --sample-rate HZ     Sample rate (default: 48000)
+  -v, --volume DBFS        Signal level in dBFS, up to 0, and down to -42 for
+                           8 bit samples (default: -3)
+  -t, --rise-time US       Rise time of the signal in microseconds, 0 for a
+                           square wave (default: 40)
+  -u, --user-bits BITS     User bits, as up to 8 decimal digits stored as BCD,
+                           0x followed by up to 8 hexadecimal digits, or
+                           ascii: followed by up to 4 ASCII characters
+      --date YYYY-MM-DD    Encode this date in the user bits (SMPTE 309)
+      --timezone +HHMM     Encode this timezone in the user bits (SMPTE 309)
+      --reverse            Generate reverse LTC, counting down from --start
+  -w, --wav                Write a Broadcast Wave file, whose time reference
+                           places it at the start timecode (default if OUTPUT
+                           ends in .wav)
+  -b, --sample-format FMT  Sample format of the WAV file: u8, s16, s24 or f32
+                           (default: s16)
+  -c, --channels N         Number of channels of the WAV file (default: 1)
+  -l, --ltc-channel N      Channel of the WAV file carrying the LTC, the others
+                           are silent (default: 1)
+  -h, --help               Show this help
+";
+
+enum Length {
+    Seconds(f64),
+    End(Timecode),
+}
+
+struct Options {
+    start: Timecode,
+    length: Length,
+    frame_rate: FrameRate,
+    sample_rate: u32,
+    volume: f64,
+    rise_time: f64,
+    user_bits: Option<u32>,
+    date: Option<Date>,
+    timezone: Option<TimezoneOffset>,
+    direction: Direction,
+    output: String,
+    wav: bool,
+    sample_format: SampleFormat,
+    channels: u16,
+    ltc_channel: u16,
+}
+
+impl Options {
+    fn parse(mut args: impl Iterator<Item = String>) -> Result<Options, String> {
+        let mut options = Options {
+            start: Timecode::default(),
+            length: Length::Seconds(10.0),
+            frame_rate: FrameRate::Fps25,
+            sample_rate: 48_000,
+            volume: -3.0,
+            rise_time: 40.0,
+            user_bits: None,
+            date: None,
+            timezone: None,
+            direction: Direction::Forward,
+            output: String::from("output.raw"),
+            wav: false,
+            sample_format: SampleFormat::I16,
+            channels: 1,
+            ltc_channel: 1,
+        };
+        let mut output = None;
+
+        while let Some(arg) = args.next() {
+            let mut value = || args.next().ok_or(format!("missing value for {}", arg));
+            match arg.as_str() {
+                "-s" | "--start" => options.start = parse_value(&arg, &value()?)?,
+                "-d" | "--duration" => {
+                    options.length = Length::Seconds(parse_value(&arg, &value()?)?)
+                }
+                "-e" | "--end" => options.length = Length::End(parse_value(&arg, &value()?)?),
+                "-f" | "--fps" => options.frame_rate = parse_value(&arg, &value()?)?,
+                "-r" | "--sample-rate" => options.sample_rate = parse_value(&arg, &value()?)?,
+                "-v" | "--volume" => options.volume = parse_value(&arg, &value()?)?,
+                "-t" | "--rise-time" => options.rise_time = parse_value(&arg, &value()?)?,
+                "-u" | "--user-bits" => options.user_bits = Some(parse_user_bits(&value()?)?),
+                "--date" => options.date = Some(parse_value(&arg, &value()?)?),
+                "--timezone" => options.timezone = Some(parse_value(&arg, &value()?)?),
+                "--reverse" => options.direction = Direction::Reverse,
+                "-w" | "--wav" => options.wav = true,
+                "-b" | "--sample-format" => options.sample_format = parse_sample_format(&value()?)?,
+                "-c" | "--channels" => options.channels = parse_value(&arg, &value()?)?,
+                "-l" | "--ltc-channel" => options.ltc_channel = parse_value(&arg, &value()?)?,
+                "-h" | "--help" => {
+                    print!("{}", USAGE);
+                    process::exit(0);
+                }
+                _ if arg.starts_with('-') && arg != "-" => {
+                    return Err(format!("unknown option {}", arg));
+                }
+                _ if output.is_none() => output = Some(arg),
+                _ => return Err(format!("unexpected argument {}", arg)),
+            }
+        }
+
+        if let Length::Seconds(seconds) = options.length {
+            if !seconds.is_finite() || seconds < 0.0 {
+                return Err(format!("invalid duration: {}", seconds));
+            }
+        }
+        if options.user_bits.is_some() && (options.date.is_some() || options.timezone.is_some()) {
+            return Err(String::from(
+                "--user-bits cannot be combined with --date or --timezone",
+            ));
+        }
+        if options.channels == 0 || !(1..=options.channels).contains(&options.ltc_channel) {
+            return Err(format!(
+                "the LTC channel must be one of the {} channels",
+                options.channels
+            ));
+        }
+        if let Some(output) = output {
+            options.wav |= output.to_ascii_lowercase().ends_with(".wav");
+            options.output = output;
+        }
+        // 8 bit samples cannot resolve lower levels, only the other WAV formats can.
+        let min_volume = if !options.wav || options.sample_format == SampleFormat::U8 {
+            -42.0
+        } else {
+            f64::NEG_INFINITY
+        };
+        if !(min_volume..=0.0).contains(&options.volume) {
+            return Err(format!("volume out of range: {} dBFS", options.volume));
+        }
+        Ok(options)
+    }
+
+    /// The number of LTC frames to generate, i.e. frame pairs above 30 fps.
+    fn frame_count(&self) -> Result<u64, String> {
+        let ltc_frame_rate = self.frame_rate.ltc_frame_rate();
+        match self.length {
+            Length::Seconds(seconds) => Ok((seconds * ltc_frame_rate.as_f64()).round() as u64),
+            Length::End(end) => {
+                let start = self.start.with_frame_rate(self.frame_rate);
+                let end = end.with_frame_rate(self.frame_rate);
+                for tc in [start, end] {
+                    if tc.to_frame_number(self.frame_rate).is_err() {
+                        return Err(format!(
+                            "{} is not a valid timecode at {} fps",
+                            tc, self.frame_rate
+                        ));
+                    }
+                }
+                let frames_per_day = i64::from(self.frame_rate.frames_per_day());
+                let frames = match self.direction {
+                    Direction::Forward => end.frames_since(&start),
+                    Direction::Reverse => start.frames_since(&end),
+                }
+                .map_err(|e| e.to_string())?;
+                // Wrap around midnight if the end lies before the start.
+                let frames = frames.0.rem_euclid(frames_per_day) as u64;
+                Ok(frames.div_ceil(u64::from(self.frame_rate.frames_per_ltc_frame())))
+            }
+        }
+    }
+}
+
+fn parse_user_bits(value: &str) -> Result<u32, String> {
+    let invalid = || format!("invalid user bits: {}", value);
+    if let Some(hex) = value.strip_prefix("0x") {
+        if hex.is_empty() || hex.len() > 8 {
+            return Err(invalid());
+        }
+        u32::from_str_radix(hex, 16).map_err(|_| invalid())
+    } else if let Some(text) = value.strip_prefix("ascii:") {
+        if text.len() > 4 || !text.is_ascii() {
+            return Err(invalid());
+        }
+        // The first character goes to the first two user bits nibbles.
+        Ok(text
+            .bytes()
+            .enumerate()
+            .fold(0, |bits, (i, c)| bits | u32::from(c) << (8 * i)))
+    } else {
+        if value.is_empty() || value.len() > 8 || !value.bytes().all(|c| c.is_ascii_digit()) {
+            return Err(invalid());
+        }
+        Ok(value
+            .bytes()
+            .rev()
+            .enumerate()
+            .fold(0, |bcd, (i, c)| bcd | u32::from(c - b'0') << (4 * i)))
+    }
+}
+
+fn generate(options: &Options) -> Result<(), String> {
+    let mut flags = BgFlags::empty();
+    if options.date.is_some() || options.timezone.is_some() {
+        flags |= BgFlags::USE_DATE;
+    }
+    let config = EncoderConfig::new(options.sample_rate, options.frame_rate).flags(flags);
+    let mut encoder = Encoder::with_config(config)
+        .map_err(|_| format!("cannot encode at {} Hz", options.sample_rate))?;
+    encoder
+        .set_volume(options.volume)
+        .map_err(|_| format!("volume out of range: {} dBFS", options.volume))?;
+    encoder.set_filter(options.rise_time);
+
+    let mut start = options.start;
+    if let Some(date) = options.date {
+        start = start.with_date(date);
+    }
+    if let Some(timezone) = options.timezone {
+        start = start.with_timezone(timezone);
+    }
+    encoder.set_timecode(start);
+    if let Some(user_bits) = options.user_bits {
+        encoder.set_user_bits(user_bits);
+    }
+
+    let frame_count = options.frame_count()?;
+    if options.wav {
+        let time_reference = options
+            .start
+            .to_sample_position(options.frame_rate, options.sample_rate)
+            .map_err(|_| {
+                format!(
+                    "{} is not a valid timecode at {} fps",
+                    options.start, options.frame_rate
+                )
+            })?;
+        let config = WavConfig::new(options.sample_rate, options.sample_format)
+            .channels(options.channels, options.ltc_channel - 1)
+            .time_reference(time_reference);
+        write_wav(&options.output, config, |wav| {
+            for _ in 0..frame_count {
+                match options.direction {
+                    Direction::Forward => {
+                        wav.write_frame(&mut encoder)?;
+                        encoder.increase_timecode();
+                    }
+                    Direction::Reverse => {
+                        wav.write_reversed_frame(&mut encoder)?;
+                        encoder.decrease_timecode();
+                    }
+                }
+            }
+            Ok(())
+        })
+    } else {
+        let mut output = create(&options.output)?;
+        for audio in encoder
+            .generate(options.direction)
+            .take(frame_count as usize)
+        {
+            output.write_all(&audio).map_err(write_error)?;
         }
+        output.flush().map_err(write_error)
     }
-    bcd
 }
 
 fn main() {
-    let length = 10; // in seconds
-    let sample_rate = 48_000;
-    let frames_per_second = 25;
-    let mut encoder = Encoder::new(sample_rate, frames_per_second as f64).unwrap();
-    let bcd = to_bcd(123);
-    encoder.set_user_bits(bcd);
-
-    let mut output_file = File::create("output.raw").unwrap();
-
-    for _frame in 0..(length * frames_per_second) {
-        encoder.encode_frame();
-        output_file.write_all(encoder.get_buffer()).unwrap();
-        encoder.increase_timecode();
+    let result = Options::parse(env::args().skip(1)).and_then(|options| generate(&options));
+    if let Err(message) = result {
+        eprintln!("ltcgenerator: {}", message);
+        eprintln!("Try 'ltcgenerator --help' for more information.");
+        process::exit(1);
     }
 }
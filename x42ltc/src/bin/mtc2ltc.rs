@@ -0,0 +1,667 @@
+// libltc: src/bin/mtc2ltc.rs
+//
+// Copyright 2019-2020 Johannes Maibaum <jmaibaum@gmail.com>
+//
+// This file is free software; you can redistribute it and/or modify it
+// under the terms of the GNU Lesser General Public License as
+// published by the Free Software Foundation; either version 3 of the
+// License, or (at your option) any later version.
+//
+// This file is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public
+// License along with this program.  If not, see <http://www.gnu.org/licenses/>.
+//
+// SPDX-License-Identifier: LGPL-3.0-or-later
+use std::env;
+use std::fs::File;
+use std::io::{self, Read, Seek, Write};
+use std::process;
+
+use x42ltc::*;
+
+mod common;
+use common::*;
+
+const USAGE: &str = "\
+Usage: mtc2ltc [OPTIONS] [INPUT] [OUTPUT]
+
+Render the MIDI Time Code in INPUT as LTC audio, written to OUTPUT as raw
+unsigned 8 bit mono samples or as a WAV file.  INPUT and OUTPUT default to
+standard input and output, or are '-'.
+
+INPUT is a Standard MIDI File, or plain MIDI bytes.  The latter carry no
+timestamps, so the quarter frame messages are taken to be evenly spaced at a
+quarter of a frame.  LTC is rendered while quarter frames are received, and
+continues for at most two frames after the last of them.
+
+Options:
+  -f, --fps RATE           Frame rate of the LTC: 23.976, 24, 25, 29.97df,
+                           29.97 or 30 (default: the rate of the MTC, where
+                           24 and 30 fps also stand for 23.976 and 29.97 fps)
+  -r, --sample-rate HZ     Sample rate (default: 48000)
+  -v, --volume DBFS        Signal level in dBFS, up to 0, and down to -42 for
+                           8 bit samples (default: -3)
+  -w, --wav                Write a WAV file (default if OUTPUT ends in .wav)
+  -b, --sample-format FMT  Sample format of the WAV file: u8, s16, s24 or f32
+                           (default: s16)
+  -h, --help               Show this help
+";
+
+/// The tempo of a MIDI file until it sets one, in microseconds per quarter note.
+const DEFAULT_TEMPO: u32 = 500_000;
+
+/// The number of frames LTC is rendered past a timecode received, unless the next one is
+/// received earlier.  A full sequence of quarter frames takes two frames.
+const FRAMES_PER_SEQUENCE: f64 = 2.0;
+
+/// The number of samples of silence written at once.
+const SILENCE_CHUNK: usize = 4096;
+
+struct Options {
+    input: String,
+    output: String,
+    frame_rate: Option<FrameRate>,
+    sample_rate: u32,
+    volume: f64,
+    wav: bool,
+    sample_format: SampleFormat,
+}
+
+impl Options {
+    fn parse(mut args: impl Iterator<Item = String>) -> Result<Options, String> {
+        let mut options = Options {
+            input: String::from("-"),
+            output: String::from("-"),
+            frame_rate: None,
+            sample_rate: 48_000,
+            volume: -3.0,
+            wav: false,
+            sample_format: SampleFormat::I16,
+        };
+        let mut files = Vec::new();
+
+        while let Some(arg) = args.next() {
+            let mut value = || args.next().ok_or(format!("missing value for {}", arg));
+            match arg.as_str() {
+                "-f" | "--fps" => options.frame_rate = Some(parse_value(&arg, &value()?)?),
+                "-r" | "--sample-rate" => options.sample_rate = parse_value(&arg, &value()?)?,
+                "-v" | "--volume" => options.volume = parse_value(&arg, &value()?)?,
+                "-w" | "--wav" => options.wav = true,
+                "-b" | "--sample-format" => options.sample_format = parse_sample_format(&value()?)?,
+                "-h" | "--help" => {
+                    print!("{}", USAGE);
+                    process::exit(0);
+                }
+                _ if arg.starts_with('-') && arg != "-" => {
+                    return Err(format!("unknown option {}", arg));
+                }
+                _ if files.len() < 2 => files.push(arg),
+                _ => return Err(format!("unexpected argument {}", arg)),
+            }
+        }
+
+        if let Some(frame_rate) = options.frame_rate {
+            if frame_rate.nominal_fps() > 30 {
+                return Err(format!("MTC cannot carry {} fps", frame_rate));
+            }
+        }
+        let mut files = files.into_iter();
+        if let Some(input) = files.next() {
+            options.input = input;
+        }
+        if let Some(output) = files.next() {
+            options.wav |= output.to_ascii_lowercase().ends_with(".wav");
+            options.output = output;
+        }
+        // 8 bit samples cannot resolve lower levels, only the other WAV formats can.
+        let min_volume = if !options.wav || options.sample_format == SampleFormat::U8 {
+            -42.0
+        } else {
+            f64::NEG_INFINITY
+        };
+        if !(min_volume..=0.0).contains(&options.volume) {
+            return Err(format!("volume out of range: {} dBFS", options.volume));
+        }
+        Ok(options)
+    }
+}
+
+/// When an MTC event was received.
+#[derive(Clone, Copy)]
+enum Time {
+    Seconds(f64),
+    /// The number of quarter frames before, in a stream without timestamps.
+    QuarterFrames(u64),
+}
+
+/// An event of a MIDI file track.
+enum TrackEvent {
+    /// The bytes of a SysEx or escaped event.
+    Bytes(Vec<u8>),
+    /// A tempo change, in microseconds per quarter note.
+    Tempo(u32),
+}
+
+/// The MIDI bytes of a Standard MIDI File with their time in seconds, in order.
+fn parse_midi_file(data: &[u8]) -> Result<Vec<(f64, Vec<u8>)>, String> {
+    let mut file = MidiReader { data, pos: 0 };
+    file.expect(b"MThd")?;
+    let header = file.chunk()?;
+    if header.len() < 6 {
+        return Err(String::from("MIDI file header too short"));
+    }
+    let division = u16::from_be_bytes([header[4], header[5]]);
+
+    // The events of all tracks, by tick.
+    let mut events = Vec::new();
+    while file.pos < data.len() {
+        let id = file.take(4)?;
+        let chunk = file.chunk()?;
+        if id == b"MTrk" {
+            parse_track(chunk, &mut events)?;
+        }
+    }
+    // A stable sort keeps the order of events at the same tick.
+    events.sort_by_key(|&(tick, _)| tick);
+
+    let mut seconds_per_tick = if division & 0x8000 != 0 {
+        // SMPTE time: frames per second as negative number, and ticks per frame.
+        let fps = match division >> 8 {
+            0xE8 => 24.0,
+            0xE7 => 25.0,
+            0xE3 => 30_000.0 / 1001.0,
+            0xE2 => 30.0,
+            _ => return Err(String::from("invalid time division of MIDI file")),
+        };
+        1.0 / (fps * f64::from(division & 0xFF))
+    } else {
+        f64::from(DEFAULT_TEMPO) / 1e6 / f64::from(division)
+    };
+    if !seconds_per_tick.is_finite() {
+        return Err(String::from("invalid time division of MIDI file"));
+    }
+
+    let mut messages = Vec::new();
+    let (mut last_tick, mut seconds) = (0, 0.0);
+    for (tick, event) in events {
+        seconds += (tick - last_tick) as f64 * seconds_per_tick;
+        last_tick = tick;
+        match event {
+            TrackEvent::Bytes(bytes) => messages.push((seconds, bytes)),
+            TrackEvent::Tempo(tempo) if division & 0x8000 == 0 => {
+                seconds_per_tick = f64::from(tempo) / 1e6 / f64::from(division)
+            }
+            TrackEvent::Tempo(_) => {}
+        }
+    }
+    Ok(messages)
+}
+
+/// Collect the SysEx and escaped events of a track, as well as its tempo changes.
+fn parse_track(data: &[u8], events: &mut Vec<(u64, TrackEvent)>) -> Result<(), String> {
+    let mut track = MidiReader { data, pos: 0 };
+    let mut tick = 0;
+    let mut running_status = None;
+    while track.pos < data.len() {
+        tick += u64::from(track.vlq()?);
+        let status = match data.get(track.pos) {
+            Some(&status) if status >= 0x80 => {
+                track.pos += 1;
+                status
+            }
+            _ => running_status.ok_or("MIDI event without status")?,
+        };
+        match status {
+            0xFF => {
+                let kind = track.byte()?;
+                let len = track.vlq()?;
+                let data = track.take(len as usize)?;
+                match kind {
+                    0x2F => break,
+                    0x51 if len == 3 => {
+                        let tempo = u32::from_be_bytes([0, data[0], data[1], data[2]]);
+                        events.push((tick, TrackEvent::Tempo(tempo)));
+                    }
+                    _ => {}
+                }
+            }
+            0xF0 | 0xF7 => {
+                let len = track.vlq()?;
+                let data = track.take(len as usize)?;
+                // Escaped events are sent as they are, SysEx after its status byte.
+                let mut bytes = Vec::with_capacity(data.len() + 1);
+                if status == 0xF0 {
+                    bytes.push(status);
+                }
+                bytes.extend_from_slice(data);
+                events.push((tick, TrackEvent::Bytes(bytes)));
+                running_status = None;
+            }
+            0x80..=0xEF => {
+                // Channel messages are of no interest.
+                let len = if matches!(status & 0xF0, 0xC0 | 0xD0) {
+                    1
+                } else {
+                    2
+                };
+                track.take(len)?;
+                running_status = Some(status);
+            }
+            _ => return Err(format!("invalid MIDI event {:02x}", status)),
+        }
+    }
+    Ok(())
+}
+
+struct MidiReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> MidiReader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], String> {
+        let bytes = self
+            .data
+            .get(self.pos..self.pos + len)
+            .ok_or("truncated MIDI file")?;
+        self.pos += len;
+        Ok(bytes)
+    }
+
+    fn byte(&mut self) -> Result<u8, String> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn expect(&mut self, id: &[u8]) -> Result<(), String> {
+        match self.take(id.len()) {
+            Ok(bytes) if bytes == id => Ok(()),
+            _ => Err(String::from("not a MIDI file")),
+        }
+    }
+
+    /// The data of a chunk whose ID was read already.
+    fn chunk(&mut self) -> Result<&'a [u8], String> {
+        let len = u32::from_be_bytes(self.take(4)?.try_into().unwrap());
+        self.take(len as usize)
+    }
+
+    /// A variable-length quantity.
+    fn vlq(&mut self) -> Result<u32, String> {
+        let mut value = 0;
+        for _ in 0..4 {
+            let byte = self.byte()?;
+            value = value << 7 | u32::from(byte & 0x7F);
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err(String::from(
+            "invalid variable-length quantity in MIDI file",
+        ))
+    }
+}
+
+/// The MTC events of the input, with the time they take effect.
+fn parse_input(data: &[u8]) -> Result<Vec<(Time, MtcEvent)>, String> {
+    let mut parser = MtcParser::new();
+    let mut events = Vec::new();
+    if data.starts_with(b"MThd") {
+        for (seconds, bytes) in parse_midi_file(data)? {
+            for &byte in &bytes {
+                if let Some(event) = parser.push(byte) {
+                    events.push((Time::Seconds(seconds), event));
+                }
+            }
+        }
+    } else {
+        let mut quarter_frames = 0;
+        for &byte in data {
+            if let Some(event) = parser.push(byte) {
+                events.push((Time::QuarterFrames(quarter_frames), event));
+            }
+            // Data bytes never have the top bit set.
+            if byte == 0xF1 {
+                quarter_frames += 1;
+            }
+        }
+    }
+    Ok(events)
+}
+
+/// A timecode to render LTC from.
+struct Anchor {
+    /// The sample position of the start of the frame.
+    position: i64,
+    timecode: Timecode,
+    frame_number: u32,
+    /// The direction of quarter frames, or `None` for a full frame message.
+    direction: Option<Direction>,
+}
+
+/// Convert the events to anchors, at `frame_rate` and `sample_rate`.
+fn anchors(events: &[(Time, MtcEvent)], frame_rate: FrameRate, sample_rate: u32) -> Vec<Anchor> {
+    let quarter_frame = 0.25 / frame_rate.as_f64();
+    let mut anchors = Vec::new();
+    for &(time, event) in events {
+        let (timecode, direction) = match event {
+            MtcEvent::QuarterFrames {
+                timecode,
+                direction,
+            } => (timecode, Some(direction)),
+            MtcEvent::FullFrame(timecode) => (timecode, None),
+        };
+        let seconds = match time {
+            // The timecode is the one of the frame starting with the next quarter frame.
+            Time::Seconds(seconds) if direction.is_some() => seconds + quarter_frame,
+            Time::Seconds(seconds) => seconds,
+            Time::QuarterFrames(count) => count as f64 * quarter_frame,
+        };
+        let timecode = timecode.with_frame_rate(frame_rate);
+        if let Ok(frame_number) = timecode.to_frame_number(frame_rate) {
+            anchors.push(Anchor {
+                position: (seconds * f64::from(sample_rate)).round() as i64,
+                timecode,
+                frame_number,
+                direction,
+            });
+        }
+    }
+    anchors
+}
+
+/// Where rendered LTC goes.
+trait LtcOutput {
+    /// Write unsigned 8 bit samples.
+    fn write_samples(&mut self, samples: &[u8]) -> io::Result<()>;
+
+    /// Write the current frame of `encoder` in `direction`, returns the number of samples.
+    fn write_frame(&mut self, encoder: &mut Encoder, direction: Direction) -> io::Result<usize>;
+
+    /// Write `len` samples of silence.
+    fn write_silence(&mut self, mut len: usize) -> io::Result<()> {
+        let silence = [128; SILENCE_CHUNK];
+        while len > 0 {
+            let chunk = len.min(SILENCE_CHUNK);
+            self.write_samples(&silence[..chunk])?;
+            len -= chunk;
+        }
+        Ok(())
+    }
+}
+
+/// Raw unsigned 8 bit samples.
+struct RawOutput<W>(W);
+
+impl<W: Write> LtcOutput for RawOutput<W> {
+    fn write_samples(&mut self, samples: &[u8]) -> io::Result<()> {
+        self.0.write_all(samples)
+    }
+
+    fn write_frame(&mut self, encoder: &mut Encoder, direction: Direction) -> io::Result<usize> {
+        match direction {
+            Direction::Forward => encoder.encode_frame(),
+            Direction::Reverse => encoder.encode_reversed_frame(),
+        }
+        let samples = encoder.get_buffer();
+        self.0.write_all(samples)?;
+        Ok(samples.len())
+    }
+}
+
+/// A WAV file, with the LTC at the full precision of its sample format.
+impl<W: Write + Seek> LtcOutput for WavWriter<W> {
+    fn write_samples(&mut self, samples: &[u8]) -> io::Result<()> {
+        WavWriter::write_samples(self, samples)
+    }
+
+    fn write_frame(&mut self, encoder: &mut Encoder, direction: Direction) -> io::Result<usize> {
+        match direction {
+            Direction::Forward => WavWriter::write_frame(self, encoder),
+            Direction::Reverse => self.write_reversed_frame(encoder),
+        }
+    }
+}
+
+/// Render LTC for the anchors to `output`.
+fn render(
+    anchors: &[Anchor],
+    encoder: &mut Encoder,
+    frame_rate: FrameRate,
+    sample_rate: u32,
+    output: &mut impl LtcOutput,
+) -> io::Result<()> {
+    let frame_len = f64::from(sample_rate) / frame_rate.as_f64();
+    let max_len = (FRAMES_PER_SEQUENCE * frame_len).round() as i64;
+    let frames_per_day = frame_rate.frames_per_day();
+
+    let mut position = 0;
+    // The frame number and direction the encoder continues with.
+    let mut next = None;
+    for (i, anchor) in anchors.iter().enumerate() {
+        let following = anchors.get(i + 1);
+        let direction = match (anchor.direction, following) {
+            (Some(direction), _) => direction,
+            // A full frame message only locates, LTC runs if quarter frames follow right away.
+            (None, Some(following)) if following.position - anchor.position <= max_len => {
+                match following.direction {
+                    Some(direction) => direction,
+                    None => continue,
+                }
+            }
+            (None, _) => {
+                next = None;
+                continue;
+            }
+        };
+
+        let mut frame_number = anchor.frame_number;
+        if next != Some((frame_number, direction)) {
+            // Silence until the anchor, which may be hours away.
+            if position < anchor.position {
+                output.write_silence((anchor.position - position) as usize)?;
+                position = anchor.position;
+            }
+            encoder.set_timecode(anchor.timecode);
+        }
+        let end = following.map_or(i64::MAX, |following| following.position);
+        let end = end.min(anchor.position + max_len);
+        // Render the frames which mostly fit before the next anchor.
+        while (position as f64) + frame_len / 2.0 < end as f64 {
+            position += output.write_frame(encoder, direction)? as i64;
+            match direction {
+                Direction::Forward => {
+                    encoder.increase_timecode();
+                    frame_number = (frame_number + 1) % frames_per_day;
+                }
+                Direction::Reverse => {
+                    encoder.decrease_timecode();
+                    frame_number = (frame_number + frames_per_day - 1) % frames_per_day;
+                }
+            }
+        }
+        next = Some((frame_number, direction));
+    }
+    Ok(())
+}
+
+fn convert(options: &Options) -> Result<(), String> {
+    let mut data = Vec::new();
+    if options.input == "-" {
+        io::stdin().read_to_end(&mut data).map_err(read_error)?;
+    } else {
+        File::open(&options.input)
+            .map_err(|e| format!("cannot open {}: {}", options.input, e))?
+            .read_to_end(&mut data)
+            .map_err(read_error)?;
+    }
+
+    let events = parse_input(&data)?;
+    let frame_rate = options
+        .frame_rate
+        .or_else(|| events.iter().find_map(|(_, event)| event_frame_rate(event)))
+        .ok_or("no MIDI Time Code found")?;
+    let anchors = anchors(&events, frame_rate, options.sample_rate);
+
+    let mut encoder = Encoder::new(options.sample_rate, frame_rate)
+        .map_err(|_| format!("cannot encode at {} Hz", options.sample_rate))?;
+    encoder
+        .set_volume(options.volume)
+        .map_err(|_| format!("volume out of range: {} dBFS", options.volume))?;
+
+    if options.wav {
+        let config = WavConfig::new(options.sample_rate, options.sample_format);
+        write_wav(&options.output, config, |wav| {
+            render(&anchors, &mut encoder, frame_rate, options.sample_rate, wav)
+        })
+    } else {
+        let mut output = RawOutput(create(&options.output)?);
+        render(
+            &anchors,
+            &mut encoder,
+            frame_rate,
+            options.sample_rate,
+            &mut output,
+        )
+        .map_err(write_error)?;
+        output.0.flush().map_err(write_error)
+    }
+}
+
+fn event_frame_rate(event: &MtcEvent) -> Option<FrameRate> {
+    match event {
+        MtcEvent::QuarterFrames { timecode, .. } | MtcEvent::FullFrame(timecode) => {
+            timecode.frame_rate()
+        }
+    }
+}
+
+fn main() {
+    let result = Options::parse(env::args().skip(1)).and_then(|options| convert(&options));
+    if let Err(message) = result {
+        eprintln!("mtc2ltc: {}", message);
+        eprintln!("Try 'mtc2ltc --help' for more information.");
+        process::exit(1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A Standard MIDI File of format 0 with `division` and a single track.
+    fn midi_file(division: [u8; 2], track: &[u8]) -> Vec<u8> {
+        let mut file = Vec::new();
+        file.extend_from_slice(b"MThd\0\0\0\x06\0\0\0\x01");
+        file.extend_from_slice(&division);
+        file.extend_from_slice(b"MTrk");
+        file.extend_from_slice(&(track.len() as u32).to_be_bytes());
+        file.extend_from_slice(track);
+        file
+    }
+
+    #[test]
+    fn midi_reader_reads_variable_length_quantities() {
+        let vlq = |data: &[u8]| MidiReader { data, pos: 0 }.vlq();
+        assert_eq!(vlq(&[0x00]), Ok(0));
+        assert_eq!(vlq(&[0x7F]), Ok(0x7F));
+        assert_eq!(vlq(&[0x81, 0x00]), Ok(0x80));
+        assert_eq!(vlq(&[0xC0, 0x00]), Ok(0x2000));
+        assert_eq!(vlq(&[0xFF, 0xFF, 0xFF, 0x7F]), Ok(0x0FFF_FFFF));
+        assert!(vlq(&[0x80, 0x80, 0x80, 0x80, 0x00]).is_err());
+        assert!(vlq(&[0x81]).is_err());
+    }
+
+    #[test]
+    fn parse_midi_file_follows_running_status_and_tempo_changes() {
+        let track = [
+            0x00, 0x90, 0x3C, 0x40, // note on
+            0x81, 0x00, 0x3C, 0x00, // note off by running status, at tick 128
+            0x00, 0xF7, 0x02, 0xF1, 0x10, // quarter frame
+            0x40, 0xFF, 0x51, 0x03, 0x03, 0xD0,
+            0x90, // 250000 us per quarter note at tick 192
+            0x60, 0xF0, 0x03, 0x01, 0x02, 0xF7, // SysEx at tick 288
+            0x00, 0xFF, 0x2F, 0x00, // end of track
+        ];
+        let messages = parse_midi_file(&midi_file([0, 96], &track)).unwrap();
+        assert_eq!(messages.len(), 2);
+        assert!((messages[0].0 - 128.0 / 192.0).abs() < 1e-9);
+        assert_eq!(messages[0].1, [0xF1, 0x10]);
+        assert!((messages[1].0 - 1.25).abs() < 1e-9);
+        assert_eq!(messages[1].1, [0xF0, 0x01, 0x02, 0xF7]);
+
+        // Running status without a previous channel message.
+        let track = [0x00, 0x3C, 0x00];
+        assert!(parse_midi_file(&midi_file([0, 96], &track)).is_err());
+    }
+
+    #[test]
+    fn parse_midi_file_reads_smpte_time_division() {
+        // A tick at 500 and another at 1000, with a tempo change in between which SMPTE time
+        // ignores.
+        let track = [
+            0x83, 0x74, 0xF7, 0x01, 0xFE, // active sensing
+            0x00, 0xFF, 0x51, 0x03, 0x03, 0xD0, 0x90, // tempo change
+            0x83, 0x74, 0xF7, 0x01, 0xFE, // active sensing
+        ];
+        for (fps, seconds) in [
+            (0xE8, 500.0 / 24.0 / 40.0),
+            (0xE7, 500.0 / 25.0 / 40.0),
+            (0xE3, 500.0 * 1.001 / 30.0 / 40.0),
+            (0xE2, 500.0 / 30.0 / 40.0),
+        ] {
+            let messages = parse_midi_file(&midi_file([fps, 40], &track)).unwrap();
+            assert!((messages[0].0 - seconds).abs() < 1e-9, "{:x}", fps);
+            assert!((messages[1].0 - 2.0 * seconds).abs() < 1e-9, "{:x}", fps);
+        }
+
+        for division in [[0x80, 40], [0xE9, 40], [0xE7, 0], [0, 0]] {
+            assert_eq!(
+                parse_midi_file(&midi_file(division, &track)),
+                Err(String::from("invalid time division of MIDI file"))
+            );
+        }
+    }
+
+    #[test]
+    fn ltc_round_trips_through_mtc() {
+        let frame_rate = FrameRate::Fps25;
+        let start: Timecode = "10:00:00:00".parse().unwrap();
+        let mut encoder = Encoder::new(48_000, frame_rate).unwrap();
+        encoder.set_timecode(start);
+        let mut decoder = Decoder::with_frame_rate(48_000, frame_rate, 32).unwrap();
+        let mut bridge = MtcBridge::new(frame_rate).unwrap();
+        let mut midi = Vec::new();
+        for audio in encoder.generate(Direction::Forward).take(50) {
+            for frame in decoder.decode(&audio) {
+                for timed in bridge.process(&frame) {
+                    midi.extend(timed.message.to_bytes());
+                }
+            }
+        }
+
+        let events = parse_input(&midi).unwrap();
+        let anchors = anchors(&events, frame_rate, 48_000);
+        let mut encoder = Encoder::new(48_000, frame_rate).unwrap();
+        let mut output = RawOutput(Vec::new());
+        render(&anchors, &mut encoder, frame_rate, 48_000, &mut output).unwrap();
+
+        let mut decoder = Decoder::with_frame_rate(48_000, frame_rate, 32).unwrap();
+        let timecodes: Vec<_> = output
+            .0
+            .chunks(1920)
+            .flat_map(|chunk| decoder.decode(chunk).collect::<Vec<_>>())
+            .map(|frame| frame.timecode().with_frame_rate(frame_rate))
+            .collect();
+        assert!(timecodes.len() > 40, "{} frames", timecodes.len());
+        let first = timecodes[0];
+        assert!(first.frames_since(&start).unwrap().0 < 10, "{}", first);
+        for (i, timecode) in timecodes.iter().enumerate() {
+            let expected = first.checked_add(FrameCount(i as i64)).unwrap();
+            assert_eq!(timecode.to_string(), expected.to_string());
+        }
+    }
+}
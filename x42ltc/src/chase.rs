@@ -0,0 +1,449 @@
+// x42ltc: src/chase.rs
+//
+// Copyright 2019-2020 Johannes Maibaum <jmaibaum@gmail.com>
+//
+// This file is free software; you can redistribute it and/or modify it
+// under the terms of the GNU Lesser General Public License as
+// published by the Free Software Foundation; either version 3 of the
+// License, or (at your option) any later version.
+//
+// This file is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public
+// License along with this program.  If not, see <http://www.gnu.org/licenses/>.
+//
+// SPDX-License-Identifier: LGPL-3.0-or-later
+use core::f64::consts::{PI, SQRT_2};
+
+use crate::{DecodedFrame, Direction, FrameRate, Timecode};
+
+/// The bandwidth of the delay-locked loop in cycles per frame, 1 Hz at 25 fps.
+const BANDWIDTH: f64 = 0.04;
+
+/// The largest deviation from the predicted position in frames that is still followed by the
+/// loop.  Larger ones are jumps, which the clock relocks to.
+const MAX_ERROR: f64 = 0.5;
+
+/// The deviation from the predicted position in frames below which a frame counts towards the
+/// lock, and the number of such frames in a row needed.
+const LOCK_TOLERANCE: f64 = 0.05;
+const LOCK_FRAMES: u32 = 5;
+
+/// The number of frames without a decoded frame after which the signal is considered lost.
+/// Frames are only decoded once they end, and audio often arrives in chunks of several frames.
+const DROPOUT_FRAMES: f64 = 2.0;
+
+/// The default of [`ChaseClock::with_freewheel()`].
+const DEFAULT_FREEWHEEL_FRAMES: u32 = 10;
+
+/// The state of a [`ChaseClock`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LockState {
+    /// No frames were observed, or the signal was lost for longer than the clock freewheels.
+    Unlocked,
+    /// Frames are observed, but the clock has not settled yet, e.g. after a jump.
+    Locking,
+    /// The clock follows the frames closely.
+    Locked,
+    /// The signal was lost while locked, and the clock continues at the last speed.
+    Freewheel,
+}
+
+/// Derives a continuous clock from decoded frames.
+///
+/// Frames are decoded in bursts, and their offsets jitter by a few samples.  The clock smoothes
+/// them with a delay-locked loop, which estimates the timecode position for any sample index, as
+/// well as the playback speed and direction.  When the signal drops out, the clock freewheels at
+/// the last speed for a number of frames before it unlocks.
+///
+/// Positions are counted in frames since midnight at the frame rate of the clock, including the
+/// fraction of the current frame, and wrap around at midnight.
+///
+/// # Example
+///
+/// ```
+/// use x42ltc::{ChaseClock, Decoder, Direction, Encoder, FrameRate, LockState};
+///
+/// let mut encoder = Encoder::new(48_000, FrameRate::Fps25).unwrap();
+/// encoder.set_timecode("10:00:00:00".parse().unwrap());
+/// let mut decoder = Decoder::new(1920, 32).unwrap();
+/// let mut clock = ChaseClock::new(48_000, FrameRate::Fps25);
+/// for _ in 0..25 {
+///     encoder.encode_frame();
+///     for frame in decoder.decode(encoder.get_buffer()) {
+///         clock.observe(&frame);
+///     }
+///     encoder.increase_timecode();
+/// }
+///
+/// assert_eq!(clock.state(48_000), LockState::Locked);
+/// assert_eq!(clock.direction(), Some(Direction::Forward));
+/// assert!((clock.speed().unwrap() - 1.0).abs() < 0.001);
+/// // Half-way through the 25th frame of the second.
+/// let tc = clock.timecode(24 * 1920 + 960).unwrap();
+/// assert_eq!(tc.to_string(), "10:00:00:24");
+/// ```
+#[derive(Debug, Clone)]
+pub struct ChaseClock {
+    frame_rate: FrameRate,
+    frames_per_day: f64,
+    /// The nominal speed in frames per sample.
+    nominal_speed: f64,
+    freewheel_frames: u32,
+    /// The sample index and position the loop predicts from.
+    anchor: Option<(i64, f64)>,
+    /// The speed in frames per sample, negative in reverse.
+    speed: f64,
+    /// The end of the last frame observed.
+    last_end: i64,
+    /// The number of frames observed since the clock (re)started.
+    observed: u32,
+    /// The number of frames in a row within the lock tolerance.
+    locked_frames: u32,
+}
+
+impl ChaseClock {
+    /// Create a clock for LTC at `frame_rate` decoded from audio sampled at `sample_rate`.
+    pub fn new(sample_rate: u32, frame_rate: FrameRate) -> ChaseClock {
+        ChaseClock {
+            frame_rate,
+            frames_per_day: f64::from(frame_rate.frames_per_day()),
+            nominal_speed: frame_rate.as_f64() / f64::from(sample_rate),
+            freewheel_frames: DEFAULT_FREEWHEEL_FRAMES,
+            anchor: None,
+            speed: 0.0,
+            last_end: 0,
+            observed: 0,
+            locked_frames: 0,
+        }
+    }
+
+    /// Returns this clock freewheeling for up to `frames` frames after the end of the last
+    /// frame observed, 10 by default.
+    pub fn with_freewheel(mut self, frames: u32) -> ChaseClock {
+        self.freewheel_frames = frames;
+        self
+    }
+
+    /// The frame rate of the clock.
+    pub fn frame_rate(&self) -> FrameRate {
+        self.frame_rate
+    }
+
+    /// Forget all frames observed so far.
+    pub fn reset(&mut self) {
+        self.anchor = None;
+        self.observed = 0;
+        self.locked_frames = 0;
+    }
+
+    /// Take `frame` into account.  Frames must be observed in the order they were decoded, and
+    /// frames which are not valid timecode at the frame rate of the clock are ignored.
+    pub fn observe(&mut self, frame: &DecodedFrame) {
+        let Ok(frame_number) = frame
+            .frame()
+            .to_timecode_at(self.frame_rate)
+            .and_then(|tc| tc.to_frame_number(self.frame_rate))
+        else {
+            return;
+        };
+        let reverse = frame.is_reverse();
+        let sample = frame.off_start();
+        // Reverse frames are played from their end, so their first sample is at the start of
+        // the next frame (pair).
+        let position = if reverse {
+            let frames = self.frame_rate.frames_per_ltc_frame();
+            self.wrap(f64::from(frame_number) + f64::from(frames))
+        } else {
+            f64::from(frame_number)
+        };
+
+        match self.anchor {
+            Some((anchor_sample, anchor_position))
+                if self.state(sample) != LockState::Unlocked
+                    && (self.speed < 0.0) == reverse
+                    && sample > anchor_sample =>
+            {
+                let elapsed = (sample - anchor_sample) as f64;
+                let predicted = anchor_position + self.speed * elapsed;
+                let error = self.wrap_error(position - predicted);
+                if error.abs() > MAX_ERROR {
+                    self.restart(sample, position, reverse);
+                } else if self.observed == 1 {
+                    // Measure the speed from the first two frames, the loop starts from there.
+                    self.speed = self.wrap_error(position - anchor_position) / elapsed;
+                    self.anchor = Some((sample, position));
+                    self.observed += 1;
+                } else {
+                    let omega = 2.0 * PI * BANDWIDTH;
+                    let position = self.wrap(predicted + SQRT_2 * omega * error);
+                    self.anchor = Some((sample, position));
+                    self.speed += omega * omega * error / elapsed;
+                    self.observed += 1;
+                    if error.abs() < LOCK_TOLERANCE {
+                        self.locked_frames += 1;
+                    } else {
+                        self.locked_frames = 0;
+                    }
+                }
+            }
+            _ => self.restart(sample, position, reverse),
+        }
+        self.last_end = frame.off_end();
+    }
+
+    /// Start over from `position` at `sample`, keeping the speed if there is one.
+    fn restart(&mut self, sample: i64, position: f64, reverse: bool) {
+        let speed = if self.anchor.is_some() && self.speed != 0.0 {
+            self.speed.abs()
+        } else {
+            self.nominal_speed
+        };
+        self.speed = if reverse { -speed } else { speed };
+        self.anchor = Some((sample, position));
+        self.observed = 1;
+        self.locked_frames = 0;
+    }
+
+    /// `position` wrapped into one day.
+    fn wrap(&self, position: f64) -> f64 {
+        if position < 0.0 {
+            position + self.frames_per_day
+        } else if position >= self.frames_per_day {
+            position - self.frames_per_day
+        } else {
+            position
+        }
+    }
+
+    /// The difference of two positions, taking the shorter way around midnight.
+    fn wrap_error(&self, error: f64) -> f64 {
+        if error > self.frames_per_day / 2.0 {
+            error - self.frames_per_day
+        } else if error < -self.frames_per_day / 2.0 {
+            error + self.frames_per_day
+        } else {
+            error
+        }
+    }
+
+    /// The state of the clock at `sample`, which is [`LockState::Freewheel`] or
+    /// [`LockState::Unlocked`] once no frame was observed for a while before.
+    pub fn state(&self, sample: i64) -> LockState {
+        if self.anchor.is_none() {
+            return LockState::Unlocked;
+        }
+        let locked = self.locked_frames >= LOCK_FRAMES;
+        let silence = (sample - self.last_end) as f64 * self.speed.abs();
+        if silence > f64::from(self.freewheel_frames) || (silence > DROPOUT_FRAMES && !locked) {
+            LockState::Unlocked
+        } else if silence > DROPOUT_FRAMES {
+            LockState::Freewheel
+        } else if locked {
+            LockState::Locked
+        } else {
+            LockState::Locking
+        }
+    }
+
+    /// The position at `sample` in frames since midnight, or `None` if the clock is unlocked.
+    pub fn position(&self, sample: i64) -> Option<f64> {
+        let (anchor_sample, anchor_position) = self.anchor?;
+        if self.state(sample) == LockState::Unlocked {
+            return None;
+        }
+        let position = anchor_position + self.speed * (sample - anchor_sample) as f64;
+        // Far from the anchor, the position may wrap more than once.
+        let days = (position / self.frames_per_day) as i64 - i64::from(position < 0.0);
+        Some(self.wrap(position - days as f64 * self.frames_per_day))
+    }
+
+    /// The timecode of the frame at `sample`, or `None` if the clock is unlocked.
+    pub fn timecode(&self, sample: i64) -> Option<Timecode> {
+        let position = self.position(sample)?;
+        let frame_number = (position as u32).min(self.frame_rate.frames_per_day() - 1);
+        Some(Timecode::from_frame_number(frame_number, self.frame_rate))
+    }
+
+    /// The playback speed estimated from the frames observed so far, 1.0 being the nominal
+    /// speed, or `None` if no frame was observed.
+    pub fn speed(&self) -> Option<f64> {
+        self.anchor?;
+        Some(self.speed.abs() / self.nominal_speed)
+    }
+
+    /// The playback direction, or `None` if no frame was observed.
+    pub fn direction(&self) -> Option<Direction> {
+        self.anchor?;
+        Some(if self.speed < 0.0 {
+            Direction::Reverse
+        } else {
+            Direction::Forward
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::encode_ltc;
+    use crate::{Decoder, Encoder};
+
+    /// Feed `ltc` to `clock` through a decoder at 48 kHz, returns the number of samples written.
+    fn play(clock: &mut ChaseClock, ltc: Vec<Vec<u8>>) -> i64 {
+        let mut decoder = Decoder::with_sample_rate(48_000).unwrap();
+        for audio in ltc {
+            decoder
+                .decode(&audio)
+                .for_each(|frame| clock.observe(&frame));
+        }
+        decoder.position()
+    }
+
+    #[test]
+    fn chase_clock_locks_to_forward_ltc() {
+        for frame_rate in [FrameRate::Fps25, FrameRate::Fps29_97Df, FrameRate::Fps30] {
+            let mut clock = ChaseClock::new(48_000, frame_rate);
+            assert_eq!(clock.state(0), LockState::Unlocked);
+            assert_eq!(clock.position(0), None);
+            assert_eq!(clock.speed(), None);
+
+            let end = play(
+                &mut clock,
+                encode_ltc(48_000, frame_rate, "01:00:00:00", 3, Direction::Forward),
+            );
+            assert_eq!(clock.state(end), LockState::Locking);
+            clock.reset();
+            let end = play(
+                &mut clock,
+                encode_ltc(48_000, frame_rate, "01:00:00:00", 23, Direction::Forward),
+            );
+            assert_eq!(clock.state(end), LockState::Locked, "{}", frame_rate);
+
+            // The position continues from the last frame, which ended at `end`.
+            let start = "01:00:00:00"
+                .parse::<Timecode>()
+                .unwrap()
+                .to_frame_number(frame_rate)
+                .unwrap();
+            let expected = f64::from(start) + 23.0 + frame_rate.as_f64() / 48_000.0 * 1000.0;
+            let position = clock.position(end + 1000).unwrap();
+            assert!((position - expected).abs() < 0.01, "{}", position);
+            assert!((clock.speed().unwrap() - 1.0).abs() < 0.001);
+            assert_eq!(clock.direction(), Some(Direction::Forward));
+        }
+    }
+
+    #[test]
+    fn chase_clock_follows_varispeed_and_reverse_ltc() {
+        // 48 kHz / 45.714 kHz is about 5 % fast.
+        let mut clock = ChaseClock::new(48_000, FrameRate::Fps25);
+        let end = play(
+            &mut clock,
+            encode_ltc(
+                45_714,
+                FrameRate::Fps25,
+                "23:59:59:00",
+                50,
+                Direction::Forward,
+            ),
+        );
+        assert_eq!(clock.state(end), LockState::Locked);
+        let speed = clock.speed().unwrap();
+        assert!((speed - 48_000.0 / 45_714.0).abs() < 0.001, "{}", speed);
+        // Across midnight, in the middle of the last frame.
+        assert_eq!(
+            clock.timecode(end - 900).unwrap().to_string(),
+            "00:00:00:24"
+        );
+
+        let mut clock = ChaseClock::new(48_000, FrameRate::Fps25);
+        let end = play(
+            &mut clock,
+            encode_ltc(
+                48_000,
+                FrameRate::Fps25,
+                "00:00:00:20",
+                30,
+                Direction::Reverse,
+            ),
+        );
+        assert_eq!(clock.state(end), LockState::Locked);
+        assert_eq!(clock.direction(), Some(Direction::Reverse));
+        assert!((clock.speed().unwrap() - 1.0).abs() < 0.001);
+        // The last frame played was 23:59:59:16, which is followed by the 15th frame at `end`.
+        let position = clock.position(end - 960).unwrap();
+        assert!(
+            (position - (clock.frames_per_day - 8.5)).abs() < 0.01,
+            "{}",
+            position
+        );
+        assert_eq!(
+            clock.timecode(end + 960).unwrap().to_string(),
+            "23:59:59:15"
+        );
+    }
+
+    #[test]
+    fn chase_clock_freewheels_through_dropouts_and_relocks_after_jumps() {
+        let mut clock = ChaseClock::new(48_000, FrameRate::Fps25).with_freewheel(5);
+        let end = play(
+            &mut clock,
+            encode_ltc(
+                48_000,
+                FrameRate::Fps25,
+                "10:00:00:00",
+                20,
+                Direction::Forward,
+            ),
+        );
+        assert_eq!(clock.state(end), LockState::Locked);
+
+        // The last frame decoded ended a frame before `end`, two frames later the signal is
+        // considered lost.
+        let frame = 1920;
+        assert_eq!(clock.state(end + frame / 2), LockState::Locked);
+        assert_eq!(clock.state(end + 2 * frame), LockState::Freewheel);
+        let tc = clock.timecode(end + 3 * frame + 100).unwrap();
+        assert_eq!(tc.to_string(), "10:00:00:23");
+        assert_eq!(clock.state(end + 5 * frame), LockState::Unlocked);
+        assert_eq!(clock.position(end + 5 * frame), None);
+        // The speed is kept for relocking.
+        assert!(clock.speed().is_some());
+
+        // A jump restarts the lock.
+        let mut clock = ChaseClock::new(48_000, FrameRate::Fps25);
+        let end = play(
+            &mut clock,
+            encode_ltc(
+                48_000,
+                FrameRate::Fps25,
+                "10:00:00:00",
+                20,
+                Direction::Forward,
+            ),
+        );
+        let mut encoder = Encoder::new(48_000, FrameRate::Fps25).unwrap();
+        let mut decoder = Decoder::with_sample_rate(48_000).unwrap();
+        encoder.set_timecode("11:00:00:00".parse::<Timecode>().unwrap());
+        // Pick up where the first decoder stopped.
+        decoder.write(&vec![128u8; end as usize]);
+        for _ in 0..3 {
+            encoder.encode_frame();
+            for frame in decoder.decode(encoder.get_buffer()) {
+                clock.observe(&frame);
+            }
+            encoder.increase_timecode();
+        }
+        let end = decoder.position();
+        assert_eq!(clock.state(end), LockState::Locking);
+        assert_eq!(clock.timecode(end).unwrap().to_string(), "11:00:00:03");
+
+        clock.reset();
+        assert_eq!(clock.state(end), LockState::Unlocked);
+        assert_eq!(clock.direction(), None);
+    }
+}
@@ -0,0 +1,290 @@
+// x42ltc: src/detector.rs
+//
+// Copyright 2019-2020 Johannes Maibaum <jmaibaum@gmail.com>
+//
+// This file is free software; you can redistribute it and/or modify it
+// under the terms of the GNU Lesser General Public License as
+// published by the Free Software Foundation; either version 3 of the
+// License, or (at your option) any later version.
+//
+// This file is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public
+// License along with this program.  If not, see <http://www.gnu.org/licenses/>.
+//
+// SPDX-License-Identifier: LGPL-3.0-or-later
+use crate::{DecodedFrame, FrameRate};
+
+/// The largest deviation of the measured frame rate from a fractional or integer rate to still
+/// tell them apart, a quarter of their relative difference of 1/1000.
+const PULL_DOWN_TOLERANCE: f64 = 0.25 / 1000.0;
+
+/// How sure a [`FrameRateDetector`] is about the detected rate.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Confidence {
+    /// The frame numbers have not wrapped yet, so the rate is guessed from the frame spacing.
+    Low,
+    /// The number of frames per timecode second is known, but not yet whether the rate is
+    /// fractional, e.g. 29.97 or 30 fps.
+    Medium,
+    /// The rate is unambiguous.
+    High,
+}
+
+/// Detects the frame rate of a timecode signal from the frames decoded from it.
+///
+/// The detector looks at the largest frame number before the frame number wraps to 0, the
+/// drop-frame bit, and the spacing of contiguous frames in the audio, which tells 25 fps from
+/// 24 fps before the first wrap and 30 fps from 29.97 fps after about a second.
+///
+/// Timecode above 30 fps is sent as frame pairs, which cannot be told from LTC at half the rate,
+/// so the detected rate is always one of the rates LTC frames are sent at, see
+/// [`FrameRate::ltc_frame_rate()`].
+///
+/// # Example
+///
+/// ```
+/// use x42ltc::{Confidence, Decoder, Encoder, FrameRate, FrameRateDetector};
+///
+/// let mut encoder = Encoder::new(48_000, FrameRate::Fps29_97).unwrap();
+/// let mut decoder = Decoder::with_sample_rate(48_000).unwrap();
+/// let mut detector = FrameRateDetector::new(48_000);
+/// for _ in 0..60 {
+///     encoder.encode_frame();
+///     for frame in decoder.decode(encoder.get_buffer()) {
+///         detector.observe(&frame);
+///     }
+///     encoder.increase_timecode();
+/// }
+/// assert_eq!(
+///     detector.detect(),
+///     Some((FrameRate::Fps29_97, Confidence::High))
+/// );
+/// ```
+#[derive(Debug, Clone)]
+pub struct FrameRateDetector {
+    sample_rate: u32,
+    previous: Option<DecodedFrame>,
+    max_frame: u8,
+    frames_per_second: Option<u8>,
+    drop_frame: bool,
+    /// Samples and frames of the completed runs of contiguous frames.
+    samples: i64,
+    frames: i64,
+    /// Start and length in frames of the current run of contiguous frames.  The run starts with
+    /// the second frame, the boundaries of the first one are less precise after a dropout.
+    run_start: Option<i64>,
+    run_frames: i64,
+}
+
+impl FrameRateDetector {
+    /// Create a detector for frames decoded from audio sampled at `sample_rate`.
+    pub fn new(sample_rate: u32) -> FrameRateDetector {
+        FrameRateDetector {
+            sample_rate,
+            previous: None,
+            max_frame: 0,
+            frames_per_second: None,
+            drop_frame: false,
+            samples: 0,
+            frames: 0,
+            run_start: None,
+            run_frames: 0,
+        }
+    }
+
+    /// Forget all frames observed so far, e.g. when the signal source changed.
+    pub fn reset(&mut self) {
+        *self = FrameRateDetector::new(self.sample_rate);
+    }
+
+    /// Take `frame` into account.  Frames must be observed in the order they were decoded.
+    pub fn observe(&mut self, frame: &DecodedFrame) {
+        let tc = frame.timecode();
+        let start = frame.off_start();
+        self.max_frame = self.max_frame.max(tc.frame());
+        self.drop_frame = tc.is_drop_frame();
+
+        let contiguous = self.previous.as_ref().filter(|previous| {
+            let length = previous.off_end() - previous.off_start();
+            // Allow for some jitter of the detected frame boundaries.
+            previous.is_reverse() == frame.is_reverse()
+                && (start - previous.off_end() - 1).abs() < length / 4
+        });
+        match contiguous {
+            Some(previous) => {
+                // The frame number wraps to 0 at the end of each second, or comes from 0 when
+                // played backwards.
+                let (before, after) = if frame.is_reverse() {
+                    (tc.frame(), previous.timecode().frame())
+                } else {
+                    (previous.timecode().frame(), tc.frame())
+                };
+                if after == 0 && before > 0 {
+                    let fps = self.frames_per_second.unwrap_or(0).max(before + 1);
+                    self.frames_per_second = Some(fps);
+                }
+                match self.run_start {
+                    Some(_) => self.run_frames += 1,
+                    None => self.run_start = Some(start),
+                }
+            }
+            None => {
+                self.samples += self.run_samples();
+                self.frames += self.run_frames;
+                self.run_start = None;
+                self.run_frames = 0;
+            }
+        }
+        self.previous = Some(*frame);
+    }
+
+    /// The frame rate measured from the spacing of contiguous frames, if any were observed.
+    ///
+    /// This includes any deviation from the nominal speed of the signal.
+    pub fn measured_fps(&self) -> Option<f64> {
+        let samples = self.samples + self.run_samples();
+        let frames = self.frames + self.run_frames;
+        if frames == 0 || samples <= 0 {
+            return None;
+        }
+        Some(f64::from(self.sample_rate) * frames as f64 / samples as f64)
+    }
+
+    /// The detected frame rate and the confidence in it, or `None` if no frame was observed.
+    pub fn detect(&self) -> Option<(FrameRate, Confidence)> {
+        self.previous?;
+        let measured = self.measured_fps();
+        let candidates = FrameRate::ALL.into_iter().filter(|rate| {
+            rate.ltc_frame_rate() == *rate
+                && rate.is_drop_frame() == self.drop_frame
+                && match self.frames_per_second {
+                    Some(fps) => rate.nominal_fps() == u32::from(fps),
+                    None => rate.nominal_fps() > u32::from(self.max_frame),
+                }
+        });
+        let deviation = |rate: &FrameRate| match measured {
+            Some(measured) => (measured / rate.as_f64() - 1.0).abs(),
+            None => 0.0,
+        };
+        let best = candidates
+            .clone()
+            .min_by(|a, b| deviation(a).total_cmp(&deviation(b)))?;
+
+        let confidence = if self.frames_per_second.is_none() {
+            Confidence::Low
+        } else if candidates.count() == 1 {
+            Confidence::High
+        } else {
+            // Tell fractional from integer rates only after a second of contiguous frames, the
+            // jitter of the frame boundaries is too large before.
+            let samples = self.samples + self.run_samples();
+            if samples >= i64::from(self.sample_rate) && deviation(&best) < PULL_DOWN_TOLERANCE {
+                Confidence::High
+            } else {
+                Confidence::Medium
+            }
+        };
+        Some((best, confidence))
+    }
+
+    /// The length of the current run of contiguous frames in samples.
+    fn run_samples(&self) -> i64 {
+        match (self.previous, self.run_start) {
+            (Some(previous), Some(run_start)) => previous.off_start() - run_start,
+            _ => 0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::encode_ltc;
+    use crate::{Decoder, Direction};
+
+    const START: &str = "10:00:59:00";
+
+    #[test]
+    fn detector_detects_all_frame_rates() {
+        for frame_rate in FrameRate::ALL {
+            // Frame pairs are detected as LTC at half the rate.
+            let ltc_frame_rate = frame_rate.ltc_frame_rate();
+            for sample_rate in [44_100, 48_000] {
+                let frames = 2 * ltc_frame_rate.nominal_fps() as usize;
+                let mut decoder = Decoder::with_sample_rate(sample_rate).unwrap();
+                let mut detector = FrameRateDetector::new(sample_rate);
+                for audio in encode_ltc(sample_rate, frame_rate, START, frames, Direction::Forward)
+                {
+                    decoder
+                        .decode(&audio)
+                        .for_each(|frame| detector.observe(&frame));
+                }
+                assert_eq!(
+                    detector.detect(),
+                    Some((ltc_frame_rate, Confidence::High)),
+                    "{} fps at {} Hz",
+                    frame_rate,
+                    sample_rate
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn detector_confidence_grows_with_observed_frames() {
+        assert_eq!(FrameRateDetector::new(48_000).detect(), None);
+
+        let mut decoder = Decoder::with_sample_rate(48_000).unwrap();
+        let mut detector = FrameRateDetector::new(48_000);
+        for audio in encode_ltc(48_000, FrameRate::Fps25, START, 10, Direction::Forward) {
+            decoder
+                .decode(&audio)
+                .for_each(|frame| detector.observe(&frame));
+        }
+        assert_eq!(detector.detect(), Some((FrameRate::Fps25, Confidence::Low)));
+        let measured = detector.measured_fps().unwrap();
+        assert!((measured - 25.0).abs() < 0.1, "{}", measured);
+
+        // The frame number wraps after 30 frames, which is not enough to tell 30 from 29.97 fps.
+        let mut decoder = Decoder::with_sample_rate(48_000).unwrap();
+        let mut detector = FrameRateDetector::new(48_000);
+        let ltc = encode_ltc(48_000, FrameRate::Fps30, START, 45, Direction::Forward);
+        for (frames, confidence) in [
+            (0..30, Confidence::Low),
+            (30..32, Confidence::Medium),
+            (32..45, Confidence::High),
+        ] {
+            for audio in &ltc[frames] {
+                decoder
+                    .decode(audio)
+                    .for_each(|frame| detector.observe(&frame));
+            }
+            assert_eq!(detector.detect().unwrap().1, confidence);
+        }
+        assert_eq!(detector.detect().unwrap().0, FrameRate::Fps30);
+    }
+
+    #[test]
+    fn detector_detects_reverse_ltc() {
+        for (frame_rate, frames, confidence) in [
+            (FrameRate::Fps29_97, 60, Confidence::High),
+            (FrameRate::Fps24, 15, Confidence::Medium),
+        ] {
+            let mut decoder = Decoder::with_sample_rate(48_000).unwrap();
+            let mut detector = FrameRateDetector::new(48_000);
+            for audio in encode_ltc(48_000, frame_rate, START, frames, Direction::Reverse) {
+                for frame in decoder.decode(&audio) {
+                    assert!(frame.is_reverse());
+                    detector.observe(&frame);
+                }
+            }
+            assert_eq!(detector.detect(), Some((frame_rate, confidence)));
+            detector.reset();
+            assert_eq!(detector.detect(), None);
+        }
+    }
+}
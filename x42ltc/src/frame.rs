@@ -0,0 +1,740 @@
+// x42ltc: src/frame.rs
+//
+// Copyright 2019-2020 Johannes Maibaum <jmaibaum@gmail.com>
+//
+// This file is free software; you can redistribute it and/or modify it
+// under the terms of the GNU Lesser General Public License as
+// published by the Free Software Foundation; either version 3 of the
+// License, or (at your option) any later version.
+//
+// This file is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public
+// License along with this program.  If not, see <http://www.gnu.org/licenses/>.
+//
+// SPDX-License-Identifier: LGPL-3.0-or-later
+use core::fmt;
+
+#[cfg(feature = "libltc")]
+use x42ltc_sys as ffi;
+#[cfg(all(feature = "libltc", not(feature = "pure-rust")))]
+use x42ltc_sys::SMPTETimecode;
+
+use crate::{BgFlags, Date, Error, FrameRate, Timecode, TimezoneOffset};
+
+/// The sync word terminating every LTC frame (bits 64..=79).
+pub const SYNC_WORD: u16 = 0xBFFC;
+
+/// A bit field of the frame, given by its first bit and its width.  Apart from the sync word,
+/// no field crosses a byte boundary.
+#[derive(Clone, Copy)]
+struct Field(usize, u8);
+
+const FRAME_UNITS: Field = Field(0, 4);
+const FRAME_TENS: Field = Field(8, 2);
+const DFBIT: Field = Field(10, 1);
+const COL_FRAME: Field = Field(11, 1);
+const SECS_UNITS: Field = Field(16, 4);
+const SECS_TENS: Field = Field(24, 3);
+const BIPHASE_MARK_PHASE_CORRECTION: Field = Field(27, 1);
+const MINS_UNITS: Field = Field(32, 4);
+const MINS_TENS: Field = Field(40, 3);
+const BINARY_GROUP_FLAG_BIT0: Field = Field(43, 1);
+const HOURS_UNITS: Field = Field(48, 4);
+const HOURS_TENS: Field = Field(56, 2);
+const BINARY_GROUP_FLAG_BIT1: Field = Field(58, 1);
+const BINARY_GROUP_FLAG_BIT2: Field = Field(59, 1);
+const USER: [Field; 8] = [
+    Field(4, 4),
+    Field(12, 4),
+    Field(20, 4),
+    Field(28, 4),
+    Field(36, 4),
+    Field(44, 4),
+    Field(52, 4),
+    Field(60, 4),
+];
+
+macro_rules! digit_accessors {
+    ($($name:literal, $get:ident, $set:ident, $field:ident, $max:literal;)*) => {$(
+        #[doc = concat!("The ", $name, " BCD digit.")]
+        pub fn $get(&self) -> u8 {
+            self.get($field)
+        }
+
+        #[doc = concat!("Set the ", $name, " BCD digit.")]
+        ///
+        #[doc = concat!("Returns `Error::ValueOutOfRange` if `value` > ", stringify!($max), ".")]
+        pub fn $set(&mut self, value: u8) -> Result<(), Error> {
+            if value > $max {
+                return Err(Error::ValueOutOfRange);
+            }
+            self.put($field, value);
+            Ok(())
+        }
+    )*};
+}
+
+macro_rules! bit_accessors {
+    ($($(#[$doc:meta])* $get:ident, $set:ident, $field:ident;)*) => {$(
+        $(#[$doc])*
+        pub fn $get(&self) -> bool {
+            self.get($field) != 0
+        }
+
+        $(#[$doc])*
+        pub fn $set(&mut self, value: bool) {
+            self.put($field, u8::from(value));
+        }
+    )*};
+}
+
+/// The raw 80 bit LTC frame.
+///
+/// Besides the BCD digits of the timecode, it holds the user bits, flag bits and the sync word.
+/// See the libltc documentation of `LTCFrame` for the bit layout.
+///
+/// # Example
+///
+/// ```
+/// use x42ltc::{BgFlags, FrameRate, LtcFrame};
+///
+/// let mut frame = LtcFrame::new();
+/// frame.set_secs_units(9).unwrap();
+/// frame.set_secs_tens(5).unwrap();
+/// frame.set_frame_units(4).unwrap();
+/// frame.set_frame_tens(2).unwrap();
+///
+/// let wrapped = frame.increment(FrameRate::Fps25, BgFlags::empty());
+/// assert!(!wrapped);
+/// assert_eq!(frame.to_timecode().unwrap().to_string(), "00:01:00:00");
+///
+/// let copy = LtcFrame::from_bytes(frame.to_bytes());
+/// assert_eq!(copy, frame);
+/// ```
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct LtcFrame {
+    bytes: [u8; 10],
+}
+
+impl LtcFrame {
+    /// Create a frame with all bits set to zero, except for the sync word.
+    pub fn new() -> LtcFrame {
+        let mut frame = LtcFrame::from_bytes([0; 10]);
+        frame.reset();
+        frame
+    }
+
+    /// Create a frame from its raw representation, bit 0 being the least significant bit of the
+    /// first byte.
+    pub fn from_bytes(bytes: [u8; 10]) -> LtcFrame {
+        LtcFrame { bytes }
+    }
+
+    /// The raw representation of the frame, bit 0 being the least significant bit of the first
+    /// byte.
+    pub fn to_bytes(&self) -> [u8; 10] {
+        self.bytes
+    }
+
+    #[cfg(feature = "libltc")]
+    #[cfg_attr(feature = "pure-rust", allow(dead_code))]
+    pub(crate) fn from_ffi(frame: &ffi::LTCFrame) -> LtcFrame {
+        // The first ten bytes of the (padded) C struct hold the 80 bits of the frame.
+        let bytes = unsafe { *(frame as *const ffi::LTCFrame as *const [u8; 10]) };
+        LtcFrame { bytes }
+    }
+
+    #[cfg(feature = "libltc")]
+    #[cfg_attr(feature = "pure-rust", allow(dead_code))]
+    pub(crate) fn to_ffi(self) -> ffi::LTCFrame {
+        let mut frame = ffi::LTCFrame::default();
+        unsafe {
+            *(&mut frame as *mut ffi::LTCFrame as *mut [u8; 10]) = self.bytes;
+        }
+        frame
+    }
+
+    fn get(&self, Field(bit, width): Field) -> u8 {
+        (self.bytes[bit / 8] >> (bit % 8)) & ((1 << width) - 1)
+    }
+
+    /// Store `value` truncated to the width of the field, like assigning to a C bit field.
+    fn put(&mut self, Field(bit, width): Field, value: u8) {
+        let mask = ((1 << width) - 1) << (bit % 8);
+        let byte = &mut self.bytes[bit / 8];
+        *byte = (*byte & !mask) | ((value << (bit % 8)) & mask);
+    }
+
+    digit_accessors! {
+        "frame units", frame_units, set_frame_units, FRAME_UNITS, 9;
+        "frame tens", frame_tens, set_frame_tens, FRAME_TENS, 3;
+        "seconds units", secs_units, set_secs_units, SECS_UNITS, 9;
+        "seconds tens", secs_tens, set_secs_tens, SECS_TENS, 5;
+        "minutes units", mins_units, set_mins_units, MINS_UNITS, 9;
+        "minutes tens", mins_tens, set_mins_tens, MINS_TENS, 5;
+        "hours units", hours_units, set_hours_units, HOURS_UNITS, 9;
+        "hours tens", hours_tens, set_hours_tens, HOURS_TENS, 2;
+    }
+
+    bit_accessors! {
+        /// The drop-frame flag (bit 10).
+        dfbit, set_dfbit, DFBIT;
+        /// The color-frame flag (bit 11).
+        col_frame, set_col_frame, COL_FRAME;
+        /// Bit 27: the polarity correction bit, except at 25 fps where it is BGF0.
+        biphase_mark_phase_correction, set_biphase_mark_phase_correction,
+            BIPHASE_MARK_PHASE_CORRECTION;
+        /// Bit 43: BGF0, except at 25 fps where it is BGF2.
+        binary_group_flag_bit0, set_binary_group_flag_bit0, BINARY_GROUP_FLAG_BIT0;
+        /// Bit 58: BGF1.
+        binary_group_flag_bit1, set_binary_group_flag_bit1, BINARY_GROUP_FLAG_BIT1;
+        /// Bit 59: BGF2, except at 25 fps where it is the polarity correction bit.
+        binary_group_flag_bit2, set_binary_group_flag_bit2, BINARY_GROUP_FLAG_BIT2;
+    }
+
+    /// The binary group flags BGF0, BGF1 and BGF2 as bits 0, 1 and 2, taking their different
+    /// positions at 25 and 50 fps into account.
+    pub fn binary_group_flags(&self, frame_rate: FrameRate) -> u8 {
+        let (bgf0, bgf2) = if frame_rate.is_625_50() {
+            (BIPHASE_MARK_PHASE_CORRECTION, BINARY_GROUP_FLAG_BIT0)
+        } else {
+            (BINARY_GROUP_FLAG_BIT0, BINARY_GROUP_FLAG_BIT2)
+        };
+        self.get(bgf0) | self.get(BINARY_GROUP_FLAG_BIT1) << 1 | self.get(bgf2) << 2
+    }
+
+    /// The user bits nibble with the given `index` in `0..8` (`user1` to `user8` in libltc).
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index` >= 8.
+    pub fn user_nibble(&self, index: usize) -> u8 {
+        self.get(USER[index])
+    }
+
+    /// Set the user bits nibble with the given `index` in `0..8` (`user1` to `user8` in libltc).
+    ///
+    /// Returns `Error::ValueOutOfRange` if `value` > 15.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index` >= 8.
+    pub fn set_user_nibble(&mut self, index: usize, value: u8) -> Result<(), Error> {
+        if value > 0xF {
+            return Err(Error::ValueOutOfRange);
+        }
+        self.put(USER[index], value);
+        Ok(())
+    }
+
+    /// All user bits as 32 bit unsigned integer, the first nibble being the least significant.
+    pub fn user_bits(&self) -> u32 {
+        (0..8).fold(0, |bits, index| {
+            bits | u32::from(self.user_nibble(index)) << (4 * index)
+        })
+    }
+
+    /// Set all user bits from a 32 bit unsigned integer, the least significant nibble going into
+    /// the first user bits field.
+    pub fn set_user_bits(&mut self, user_bits: u32) {
+        for (index, field) in USER.into_iter().enumerate() {
+            self.put(field, (user_bits >> (4 * index)) as u8);
+        }
+    }
+
+    /// The sync word, which is [`SYNC_WORD`] for valid frames.
+    pub fn sync_word(&self) -> u16 {
+        u16::from_le_bytes([self.bytes[8], self.bytes[9]])
+    }
+
+    /// Set the sync word.
+    pub fn set_sync_word(&mut self, sync_word: u16) {
+        self.bytes[8..].copy_from_slice(&sync_word.to_le_bytes());
+    }
+
+    /// The timecode held by the frame, interpreting the user bits as date and timezone where
+    /// possible.
+    ///
+    /// Returns `Error::ValueOutOfRange` if the BCD digits do not form a valid time of day.
+    pub fn to_timecode(&self) -> Result<Timecode, Error> {
+        self.timecode(true)
+    }
+
+    /// The timecode held by the frame at `frame_rate`, i.e. with the frame number multiplied by
+    /// two for the frame pairs sent above 30 fps, see [`FrameRate::ltc_frame_rate()`].
+    ///
+    /// Returns `Error::ValueOutOfRange` if the BCD digits do not form a valid time of day.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use x42ltc::{BgFlags, FrameRate, LtcFrame, Timecode};
+    ///
+    /// let mut frame = LtcFrame::new();
+    /// frame.set_timecode(Timecode::new(0, 0, 0, 48).unwrap(), FrameRate::Fps50, BgFlags::empty());
+    /// assert_eq!(frame.to_timecode().unwrap().frame(), 24);
+    /// assert_eq!(frame.to_timecode_at(FrameRate::Fps50).unwrap().frame(), 48);
+    /// ```
+    pub fn to_timecode_at(&self, frame_rate: FrameRate) -> Result<Timecode, Error> {
+        self.timecode_at(frame_rate, true)
+    }
+
+    /// Like `ltc_frame_to_time()`: the user bits are only interpreted as date and timezone if
+    /// `use_date` is set.  Otherwise, and for unknown timezone codes, the timezone is UTC.
+    pub(crate) fn timecode(&self, use_date: bool) -> Result<Timecode, Error> {
+        self.timecode_with_frame_multiplier(1, use_date)
+    }
+
+    /// Like [`.timecode()`](#method.timecode), counting frames at `frame_rate`.
+    pub(crate) fn timecode_at(
+        &self,
+        frame_rate: FrameRate,
+        use_date: bool,
+    ) -> Result<Timecode, Error> {
+        self.timecode_with_frame_multiplier(frame_rate.frames_per_ltc_frame(), use_date)
+    }
+
+    fn timecode_with_frame_multiplier(
+        &self,
+        multiplier: u8,
+        use_date: bool,
+    ) -> Result<Timecode, Error> {
+        let frame = self.frame_units() + self.frame_tens() * 10;
+        let mut tc = Timecode::new(
+            self.hours_units() + self.hours_tens() * 10,
+            self.mins_units() + self.mins_tens() * 10,
+            self.secs_units() + self.secs_tens() * 10,
+            frame * multiplier,
+        )?
+        .with_drop_frame(self.dfbit())
+        .with_timezone(TimezoneOffset::UTC);
+        if use_date {
+            let code = self.user_nibble(6) | self.user_nibble(7) << 4;
+            if let Some(timezone) = TimezoneOffset::from_smpte_code(code) {
+                tc = tc.with_timezone(timezone);
+            }
+            let (years, months, days) = self.date_digits();
+            if let Ok(date) = Date::from_two_digit_year(years, months, days) {
+                tc = tc.with_date(date);
+            }
+        }
+        Ok(tc)
+    }
+
+    /// Set the BCD digits from the given timecode, and the date and timezone if `flags` contains
+    /// [`BgFlags::USE_DATE`].  The parity bit is updated according to `frame_rate`, unless `flags`
+    /// contains [`BgFlags::NO_PARITY`].  All other bits are left untouched.
+    ///
+    /// Above 30 fps, the frame number of the frame pair is stored, i.e. half the frame number of
+    /// `tc`, see [`FrameRate::ltc_frame_rate()`].
+    pub fn set_timecode(&mut self, tc: Timecode, frame_rate: FrameRate, flags: BgFlags) {
+        let frame = tc.frame() / frame_rate.frames_per_ltc_frame();
+        self.set_timecode_raw(tc, frame, frame_rate.ltc_frame_rate(), flags);
+    }
+
+    /// Reset all bits to zero, except for the sync word.
+    pub fn reset(&mut self) {
+        self.reset_raw();
+    }
+
+    /// Move the timecode to the next frame at `frame_rate`, skipping the dropped frame numbers if
+    /// the `dfbit` is set.  If `flags` contains [`BgFlags::USE_DATE`], the date in the user bits
+    /// is incremented when the timecode wraps around.  The parity bit is updated unless `flags`
+    /// contains [`BgFlags::NO_PARITY`].
+    ///
+    /// Above 30 fps, the frame moves to the next frame pair, see [`FrameRate::ltc_frame_rate()`].
+    ///
+    /// Returns `true` if the timecode wrapped around after 23:59:59.
+    pub fn increment(&mut self, frame_rate: FrameRate, flags: BgFlags) -> bool {
+        self.increment_raw(frame_rate.ltc_frame_rate(), flags)
+    }
+
+    /// Move the timecode to the previous frame.  The counterpart of
+    /// [`.increment()`](#method.increment).
+    ///
+    /// Returns `true` if the timecode wrapped around at 00:00:00:00.
+    pub fn decrement(&mut self, frame_rate: FrameRate, flags: BgFlags) -> bool {
+        self.decrement_raw(frame_rate.ltc_frame_rate(), flags)
+    }
+
+    /// Set or clear the polarity correction bit, so that the frame contains an even number of
+    /// zeros.  This bit is bit 59 at 25 fps and bit 27 otherwise.
+    pub fn set_parity(&mut self, frame_rate: FrameRate) {
+        self.set_parity_raw(frame_rate.ltc_frame_rate());
+    }
+
+    /// The two-digit year, month and day stored in the user bits (SMPTE 309).
+    fn date_digits(&self) -> (u8, u8, u8) {
+        let digits = |index: usize| self.user_nibble(index) + self.user_nibble(index + 1) * 10;
+        (digits(4), digits(2), digits(0))
+    }
+}
+
+// The frame arithmetic is done by libltc, unless the native backend is used.
+#[cfg(all(feature = "libltc", not(feature = "pure-rust")))]
+impl LtcFrame {
+    /// Call `f` with the frame converted to libltc's representation, and take over its changes.
+    fn with_ffi<T>(&mut self, f: impl FnOnce(&mut ffi::LTCFrame) -> T) -> T {
+        let mut frame = self.to_ffi();
+        let rv = f(&mut frame);
+        *self = LtcFrame::from_ffi(&frame);
+        rv
+    }
+
+    fn set_timecode_raw(&mut self, tc: Timecode, frame: u8, frame_rate: FrameRate, flags: BgFlags) {
+        let mut time = SMPTETimecode::from(tc);
+        time.frame = frame;
+        self.with_ffi(|ltc_frame| unsafe {
+            ffi::ltc_time_to_frame(
+                ltc_frame,
+                &mut time,
+                frame_rate.tv_standard(),
+                flags.bits() as i32,
+            );
+        });
+    }
+
+    fn reset_raw(&mut self) {
+        self.with_ffi(|frame| unsafe { ffi::ltc_frame_reset(frame) });
+    }
+
+    fn increment_raw(&mut self, frame_rate: FrameRate, flags: BgFlags) -> bool {
+        let rv = self.with_ffi(|frame| unsafe {
+            ffi::ltc_frame_increment(
+                frame,
+                frame_rate.nominal_fps() as i32,
+                frame_rate.tv_standard(),
+                flags.bits() as i32,
+            )
+        });
+        rv == 1
+    }
+
+    fn decrement_raw(&mut self, frame_rate: FrameRate, flags: BgFlags) -> bool {
+        let rv = self.with_ffi(|frame| unsafe {
+            ffi::ltc_frame_decrement(
+                frame,
+                frame_rate.nominal_fps() as i32,
+                frame_rate.tv_standard(),
+                flags.bits() as i32,
+            )
+        });
+        rv == 1
+    }
+
+    fn set_parity_raw(&mut self, frame_rate: FrameRate) {
+        self.with_ffi(|frame| unsafe {
+            ffi::ltc_frame_set_parity(frame, frame_rate.tv_standard())
+        });
+    }
+}
+
+// Ports of libltc's frame arithmetic for the native backend.
+#[cfg(feature = "pure-rust")]
+impl LtcFrame {
+    /// Mirrors ltc_time_to_frame().
+    fn set_timecode_raw(&mut self, tc: Timecode, frame: u8, frame_rate: FrameRate, flags: BgFlags) {
+        if flags.contains(BgFlags::USE_DATE) {
+            // Timezones without SMPTE code are sent as UTC, just like libltc does.
+            let code = tc.timezone().and_then(|tz| tz.smpte_code()).unwrap_or(0);
+            self.put(USER[6], code & 0xF);
+            self.put(USER[7], code >> 4);
+            match tc.date() {
+                Some(date) => self.set_date_digits(date.two_digit_year(), date.month(), date.day()),
+                None => self.set_date_digits(0, 0, 0),
+            }
+        }
+
+        self.put(HOURS_TENS, tc.hours() / 10);
+        self.put(HOURS_UNITS, tc.hours() % 10);
+        self.put(MINS_TENS, tc.mins() / 10);
+        self.put(MINS_UNITS, tc.mins() % 10);
+        self.put(SECS_TENS, tc.secs() / 10);
+        self.put(SECS_UNITS, tc.secs() % 10);
+        self.put(FRAME_TENS, frame / 10);
+        self.put(FRAME_UNITS, frame % 10);
+
+        if self.dfbit() {
+            self.skip_drop_frames();
+        }
+        if !flags.contains(BgFlags::NO_PARITY) {
+            self.set_parity_raw(frame_rate);
+        }
+    }
+
+    fn reset_raw(&mut self) {
+        self.bytes = [0; 10];
+        self.set_sync_word(SYNC_WORD);
+    }
+
+    /// Mirrors ltc_frame_increment(), including the truncation to the width of the BCD fields.
+    fn increment_raw(&mut self, frame_rate: FrameRate, flags: BgFlags) -> bool {
+        let fps = frame_rate.nominal_fps() as u8;
+        let mut wrapped = false;
+
+        self.put(FRAME_UNITS, self.get(FRAME_UNITS) + 1);
+        if self.get(FRAME_UNITS) == 10 {
+            self.put(FRAME_UNITS, 0);
+            self.put(FRAME_TENS, self.get(FRAME_TENS) + 1);
+        }
+        if fps == self.get(FRAME_UNITS) + self.get(FRAME_TENS) * 10 {
+            self.put(FRAME_UNITS, 0);
+            self.put(FRAME_TENS, 0);
+            if self.increment_digits(SECS_UNITS, SECS_TENS)
+                && self.increment_digits(MINS_UNITS, MINS_TENS)
+            {
+                self.put(HOURS_UNITS, self.get(HOURS_UNITS) + 1);
+                if self.get(HOURS_UNITS) == 10 {
+                    self.put(HOURS_UNITS, 0);
+                    self.put(HOURS_TENS, self.get(HOURS_TENS) + 1);
+                }
+                if self.get(HOURS_UNITS) == 4 && self.get(HOURS_TENS) == 2 {
+                    wrapped = true;
+                    self.put(HOURS_UNITS, 0);
+                    self.put(HOURS_TENS, 0);
+                    if flags.contains(BgFlags::USE_DATE) {
+                        self.increment_date();
+                    }
+                }
+            }
+        }
+
+        if self.dfbit() {
+            self.skip_drop_frames();
+        }
+        if !flags.contains(BgFlags::NO_PARITY) {
+            self.set_parity_raw(frame_rate);
+        }
+        wrapped
+    }
+
+    /// Increment the seconds or minutes made of `units` and `tens`, wrapping to zero at 60.
+    /// Returns whether it wrapped.
+    fn increment_digits(&mut self, units: Field, tens: Field) -> bool {
+        self.put(units, self.get(units) + 1);
+        if self.get(units) == 10 {
+            self.put(units, 0);
+            self.put(tens, self.get(tens) + 1);
+            if self.get(tens) == 6 {
+                self.put(tens, 0);
+                return true;
+            }
+        }
+        false
+    }
+
+    fn increment_date(&mut self) {
+        let (mut years, mut months, mut days) = self.date_digits();
+        if !(1..=12).contains(&months) {
+            return;
+        }
+        days += 1;
+        if days > days_in_month(years, months) {
+            days = 1;
+            months += 1;
+            if months > 12 {
+                months = 1;
+                years = (years + 1) % 100;
+            }
+        }
+        self.set_date_digits(years, months, days);
+    }
+
+    /// Mirrors ltc_frame_decrement().
+    fn decrement_raw(&mut self, frame_rate: FrameRate, flags: BgFlags) -> bool {
+        let fps = frame_rate.nominal_fps() as u8;
+        let mut wrapped = false;
+
+        let frames = match self.get(FRAME_UNITS) + self.get(FRAME_TENS) * 10 {
+            0 => fps - 1,
+            frames => frames - 1,
+        };
+        self.put(FRAME_UNITS, frames % 10);
+        self.put(FRAME_TENS, frames / 10);
+
+        if frames == fps - 1
+            && self.decrement_digits(SECS_UNITS, SECS_TENS, 59)
+            && self.decrement_digits(MINS_UNITS, MINS_TENS, 59)
+            && self.decrement_digits(HOURS_UNITS, HOURS_TENS, 23)
+        {
+            wrapped = true;
+            if flags.contains(BgFlags::USE_DATE) {
+                self.decrement_date();
+            }
+        }
+
+        if self.dfbit()
+            && self.get(MINS_UNITS) != 0
+            && self.get(SECS_UNITS) == 0
+            && self.get(SECS_TENS) == 0
+            && self.get(FRAME_UNITS) == 1
+            && self.get(FRAME_TENS) == 0
+        {
+            let flags = flags & BgFlags::USE_DATE;
+            self.decrement_raw(frame_rate, flags);
+            self.decrement_raw(frame_rate, flags);
+        }
+
+        if !flags.contains(BgFlags::NO_PARITY) {
+            self.set_parity_raw(frame_rate);
+        }
+        wrapped
+    }
+
+    /// Decrement the two-digit value made of `units` and `tens`, wrapping to `max` below zero.
+    /// Returns whether it wrapped.
+    fn decrement_digits(&mut self, units: Field, tens: Field, max: u8) -> bool {
+        let value = match self.get(units) + self.get(tens) * 10 {
+            0 => max,
+            value => value - 1,
+        };
+        self.put(units, value % 10);
+        self.put(tens, value / 10);
+        value == max
+    }
+
+    fn decrement_date(&mut self) {
+        let (mut years, mut months, mut days) = self.date_digits();
+        if !(1..=12).contains(&months) {
+            return;
+        }
+        if days > 1 {
+            days -= 1;
+        } else {
+            months = 1 + (months + 10) % 12;
+            days = days_in_month(years, months);
+            if months == 12 {
+                years = (years + 99) % 100;
+            }
+        }
+        self.set_date_digits(years, months, days);
+    }
+
+    fn set_date_digits(&mut self, years: u8, months: u8, days: u8) {
+        for (index, value) in [days, months, years].into_iter().enumerate() {
+            self.put(USER[2 * index], value % 10);
+            self.put(USER[2 * index + 1], value / 10);
+        }
+    }
+
+    /// Replace the frame numbers 0 and 1, which are dropped at the start of every minute except
+    /// for every tenth one, by 2.
+    fn skip_drop_frames(&mut self) {
+        if self.get(MINS_UNITS) != 0
+            && self.get(SECS_UNITS) == 0
+            && self.get(SECS_TENS) == 0
+            && self.get(FRAME_UNITS) == 0
+            && self.get(FRAME_TENS) == 0
+        {
+            self.put(FRAME_UNITS, 2);
+        }
+    }
+
+    fn set_parity_raw(&mut self, frame_rate: FrameRate) {
+        let parity_bit = if frame_rate.is_625_50() {
+            BINARY_GROUP_FLAG_BIT2
+        } else {
+            BIPHASE_MARK_PHASE_CORRECTION
+        };
+        self.put(parity_bit, 0);
+        let parity = self.bytes.iter().fold(0, |parity, byte| parity ^ byte);
+        self.put(parity_bit, (parity.count_ones() & 1) as u8);
+    }
+}
+
+#[cfg(feature = "pure-rust")]
+/// Days per month for a two-digit year, with libltc's simplified leap year rule.
+fn days_in_month(years: u8, month: u8) -> u8 {
+    const DAYS_PER_MONTH: [u8; 12] = [31, 28, 31, 30, 31, 30, 31, 31, 30, 31, 30, 31];
+    if month == 2 && years.is_multiple_of(4) {
+        29
+    } else {
+        DAYS_PER_MONTH[usize::from(month - 1)]
+    }
+}
+
+impl Default for LtcFrame {
+    fn default() -> LtcFrame {
+        LtcFrame::new()
+    }
+}
+
+impl fmt::Debug for LtcFrame {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LtcFrame")
+            .field("bytes", &self.bytes)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn frame_byte_layout_matches_libltc() {
+        let mut frame = LtcFrame::from_bytes([0; 10]);
+        frame.set_frame_units(4).unwrap();
+        frame.set_user_nibble(0, 0xA).unwrap();
+        frame.set_dfbit(true);
+        frame.set_hours_tens(2).unwrap();
+        frame.set_user_nibble(7, 0x5).unwrap();
+        frame.set_sync_word(SYNC_WORD);
+
+        assert_eq!(
+            frame.to_bytes(),
+            [0xA4, 0x04, 0, 0, 0, 0, 0, 0x52, 0xFC, 0xBF]
+        );
+        assert!(frame.set_hours_tens(3).is_err());
+        assert!(frame.set_user_nibble(1, 16).is_err());
+    }
+
+    #[test]
+    fn frame_user_bits_round_trip() {
+        let mut frame = LtcFrame::from_bytes([0; 10]);
+        frame.set_user_bits(0x8765_4321);
+        assert_eq!(frame.user_nibble(0), 1);
+        assert_eq!(frame.user_nibble(7), 8);
+        assert_eq!(frame.user_bits(), 0x8765_4321);
+    }
+
+    #[test]
+    fn frame_arithmetic_wraps_date_and_skips_dropped_frames() {
+        let date = Date::new(2008, 12, 31).unwrap();
+        let tc = Timecode::new(23, 59, 59, 24).unwrap().with_date(date);
+        let mut frame = LtcFrame::new();
+        frame.set_timecode(tc, FrameRate::Fps25, BgFlags::USE_DATE);
+
+        assert!(frame.increment(FrameRate::Fps25, BgFlags::USE_DATE));
+        let tc = frame.to_timecode().unwrap();
+        assert_eq!(tc.to_string(), "00:00:00:00");
+        assert_eq!(tc.date(), Some(Date::new(2009, 1, 1).unwrap()));
+        assert_eq!(tc.timezone(), Some(TimezoneOffset::UTC));
+        assert!(frame.decrement(FrameRate::Fps25, BgFlags::USE_DATE));
+        assert_eq!(frame.to_timecode().unwrap().date(), Some(date));
+
+        let mut frame = LtcFrame::new();
+        frame.set_dfbit(true);
+        let tc = Timecode::new(0, 0, 59, 29).unwrap();
+        frame.set_timecode(tc, FrameRate::Fps29_97Df, BgFlags::empty());
+        assert!(!frame.increment(FrameRate::Fps29_97Df, BgFlags::empty()));
+        assert_eq!(frame.to_timecode().unwrap().to_string(), "00:01:00;02");
+        assert!(!frame.decrement(FrameRate::Fps29_97Df, BgFlags::empty()));
+        assert_eq!(frame.to_timecode().unwrap().to_string(), "00:00:59;29");
+    }
+
+    #[test]
+    fn frame_parity_gives_even_number_of_ones() {
+        let mut frame = LtcFrame::new();
+        frame.set_user_bits(0x1234_5679);
+        for frame_rate in [FrameRate::Fps25, FrameRate::Fps30] {
+            frame.set_parity(frame_rate);
+            let ones: u32 = frame.to_bytes().iter().map(|b| b.count_ones()).sum();
+            assert_eq!(ones % 2, 0);
+        }
+    }
+}
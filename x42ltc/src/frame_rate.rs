@@ -0,0 +1,312 @@
+// x42ltc: src/frame_rate.rs
+//
+// Copyright 2019-2020 Johannes Maibaum <jmaibaum@gmail.com>
+//
+// This file is free software; you can redistribute it and/or modify it
+// under the terms of the GNU Lesser General Public License as
+// published by the Free Software Foundation; either version 3 of the
+// License, or (at your option) any later version.
+//
+// This file is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public
+// License along with this program.  If not, see <http://www.gnu.org/licenses/>.
+//
+// SPDX-License-Identifier: LGPL-3.0-or-later
+use core::fmt;
+use core::str::FromStr;
+
+#[cfg(feature = "libltc")]
+use x42ltc_sys as ffi;
+
+use crate::Error;
+
+/// Video frame rate of a timecode signal.
+///
+/// The fractional NTSC rates are represented exactly as `24000/1001`, `30000/1001` and
+/// `60000/1001` frames per second.  Drop-frame counting is only defined for the latter two, hence
+/// the separate `Df` variants.
+///
+/// # Example
+///
+/// ```
+/// use x42ltc::FrameRate;
+///
+/// let rate: FrameRate = "29.97df".parse().unwrap();
+/// assert_eq!(rate, FrameRate::Fps29_97Df);
+/// assert_eq!(rate.nominal_fps(), 30);
+/// assert!(rate.is_drop_frame());
+/// assert_eq!(rate.as_f64(), 30000.0 / 1001.0);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum FrameRate {
+    /// 24000/1001 fps (23.976)
+    Fps23_976,
+    /// 24 fps
+    Fps24,
+    /// 25 fps
+    Fps25,
+    /// 30000/1001 fps (29.97), non-drop-frame
+    Fps29_97,
+    /// 30000/1001 fps (29.97), drop-frame
+    Fps29_97Df,
+    /// 30 fps
+    Fps30,
+    /// 48 fps
+    Fps48,
+    /// 50 fps
+    Fps50,
+    /// 60000/1001 fps (59.94), non-drop-frame
+    Fps59_94,
+    /// 60000/1001 fps (59.94), drop-frame
+    Fps59_94Df,
+    /// 60 fps
+    Fps60,
+}
+
+impl FrameRate {
+    /// All supported frame rates, in ascending order.
+    pub const ALL: [FrameRate; 11] = [
+        FrameRate::Fps23_976,
+        FrameRate::Fps24,
+        FrameRate::Fps25,
+        FrameRate::Fps29_97,
+        FrameRate::Fps29_97Df,
+        FrameRate::Fps30,
+        FrameRate::Fps48,
+        FrameRate::Fps50,
+        FrameRate::Fps59_94,
+        FrameRate::Fps59_94Df,
+        FrameRate::Fps60,
+    ];
+
+    /// The numerator of the exact frame rate in frames per second.
+    pub fn numerator(&self) -> u32 {
+        match self {
+            FrameRate::Fps23_976 => 24_000,
+            FrameRate::Fps29_97 | FrameRate::Fps29_97Df => 30_000,
+            FrameRate::Fps59_94 | FrameRate::Fps59_94Df => 60_000,
+            _ => self.nominal_fps(),
+        }
+    }
+
+    /// The denominator of the exact frame rate in frames per second.
+    pub fn denominator(&self) -> u32 {
+        if self.is_fractional() {
+            1001
+        } else {
+            1
+        }
+    }
+
+    /// The frame rate in frames per second.
+    pub fn as_f64(&self) -> f64 {
+        f64::from(self.numerator()) / f64::from(self.denominator())
+    }
+
+    /// The number of frames counted per timecode second, e.g. 30 for 29.97 fps.
+    pub fn nominal_fps(&self) -> u32 {
+        match self {
+            FrameRate::Fps23_976 | FrameRate::Fps24 => 24,
+            FrameRate::Fps25 => 25,
+            FrameRate::Fps29_97 | FrameRate::Fps29_97Df | FrameRate::Fps30 => 30,
+            FrameRate::Fps48 => 48,
+            FrameRate::Fps50 => 50,
+            FrameRate::Fps59_94 | FrameRate::Fps59_94Df | FrameRate::Fps60 => 60,
+        }
+    }
+
+    /// The rate at which LTC frames are sent for timecode at this rate.
+    ///
+    /// The frame number field of an LTC frame only counts up to 39, so rates above 30 fps are
+    /// sent as frame pairs at half the rate, e.g. 25 fps for 50 fps, with the frame number of the
+    /// pair's first frame divided by two (SMPTE 12-1).  All other rates are sent as is.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use x42ltc::FrameRate;
+    ///
+    /// assert_eq!(FrameRate::Fps59_94Df.ltc_frame_rate(), FrameRate::Fps29_97Df);
+    /// assert_eq!(FrameRate::Fps25.ltc_frame_rate(), FrameRate::Fps25);
+    /// ```
+    pub fn ltc_frame_rate(&self) -> FrameRate {
+        match self {
+            FrameRate::Fps48 => FrameRate::Fps24,
+            FrameRate::Fps50 => FrameRate::Fps25,
+            FrameRate::Fps59_94 => FrameRate::Fps29_97,
+            FrameRate::Fps59_94Df => FrameRate::Fps29_97Df,
+            FrameRate::Fps60 => FrameRate::Fps30,
+            _ => *self,
+        }
+    }
+
+    /// The number of frames carried by each LTC frame: 2 for the frame pairs sent above 30 fps,
+    /// 1 otherwise.  See [`.ltc_frame_rate()`](#method.ltc_frame_rate).
+    pub fn frames_per_ltc_frame(&self) -> u8 {
+        (self.nominal_fps() / self.ltc_frame_rate().nominal_fps()) as u8
+    }
+
+    /// Whether this is one of the 1000/1001 NTSC rates.
+    pub fn is_fractional(&self) -> bool {
+        matches!(
+            self,
+            FrameRate::Fps23_976
+                | FrameRate::Fps29_97
+                | FrameRate::Fps29_97Df
+                | FrameRate::Fps59_94
+                | FrameRate::Fps59_94Df
+        )
+    }
+
+    /// Whether timecode at this rate uses drop-frame counting.
+    pub fn is_drop_frame(&self) -> bool {
+        matches!(self, FrameRate::Fps29_97Df | FrameRate::Fps59_94Df)
+    }
+
+    /// The number of frame numbers skipped at the start of every minute except for every tenth
+    /// one: 2 at 29.97 fps and 4 at 59.94 fps drop-frame (SMPTE 12M), 0 otherwise.
+    pub(crate) fn dropped_frames(&self) -> u32 {
+        match self {
+            FrameRate::Fps29_97Df => 2,
+            FrameRate::Fps59_94Df => 4,
+            _ => 0,
+        }
+    }
+
+    /// The number of timecode frames from 00:00:00:00 to 23:59:59:xx, i.e. in 24 hours of
+    /// timecode, which is the modulus of timecode arithmetic.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use x42ltc::FrameRate;
+    ///
+    /// assert_eq!(FrameRate::Fps25.frames_per_day(), 2_160_000);
+    /// // Drop-frame counting skips 108 frame numbers per hour.
+    /// assert_eq!(FrameRate::Fps29_97Df.frames_per_day(), 2_589_408);
+    /// ```
+    pub fn frames_per_day(&self) -> u32 {
+        24 * (self.nominal_fps() * 60 * 60 - 54 * self.dropped_frames())
+    }
+
+    /// Whether the binary group flag bits are positioned as for 625/50 (PAL) video, i.e. at 25 and
+    /// 50 fps.
+    pub(crate) fn is_625_50(&self) -> bool {
+        matches!(self.nominal_fps(), 25 | 50)
+    }
+
+    /// The TV standard defining the position of the binary group flag bits in the LTC at this
+    /// rate, i.e. the one of half the rate for frame pairs.
+    #[cfg(feature = "libltc")]
+    #[cfg_attr(feature = "pure-rust", allow(dead_code))]
+    pub(crate) fn tv_standard(&self) -> ffi::LTC_TV_STANDARD {
+        match self.ltc_frame_rate().nominal_fps() {
+            24 => ffi::LTC_TV_STANDARD_LTC_TV_FILM_24,
+            25 => ffi::LTC_TV_STANDARD_LTC_TV_625_50,
+            _ => ffi::LTC_TV_STANDARD_LTC_TV_525_60,
+        }
+    }
+}
+
+impl fmt::Display for FrameRate {
+    /// Formats the rate like `25`, `29.97` or `29.97df`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            FrameRate::Fps23_976 => "23.976",
+            FrameRate::Fps24 => "24",
+            FrameRate::Fps25 => "25",
+            FrameRate::Fps29_97 => "29.97",
+            FrameRate::Fps29_97Df => "29.97df",
+            FrameRate::Fps30 => "30",
+            FrameRate::Fps48 => "48",
+            FrameRate::Fps50 => "50",
+            FrameRate::Fps59_94 => "59.94",
+            FrameRate::Fps59_94Df => "59.94df",
+            FrameRate::Fps60 => "60",
+        })
+    }
+}
+
+impl FromStr for FrameRate {
+    type Err = Error;
+
+    /// Parses the notation produced by `Display`.  Fractional rates may also be given as
+    /// `24000/1001`, `30000/1001` or `60000/1001`, optionally followed by `df`.
+    fn from_str(s: &str) -> Result<FrameRate, Error> {
+        let lower = s.trim().to_ascii_lowercase();
+        let (rate, drop_frame) = match lower.strip_suffix("df") {
+            Some(rate) => (rate.trim_end(), true),
+            None => (lower.as_str(), false),
+        };
+        let rate = match rate {
+            "23.976" | "23.98" | "24000/1001" => FrameRate::Fps23_976,
+            "24" => FrameRate::Fps24,
+            "25" => FrameRate::Fps25,
+            "29.97" | "30000/1001" if drop_frame => FrameRate::Fps29_97Df,
+            "29.97" | "30000/1001" => FrameRate::Fps29_97,
+            "30" => FrameRate::Fps30,
+            "48" => FrameRate::Fps48,
+            "50" => FrameRate::Fps50,
+            "59.94" | "60000/1001" if drop_frame => FrameRate::Fps59_94Df,
+            "59.94" | "60000/1001" => FrameRate::Fps59_94,
+            "60" => FrameRate::Fps60,
+            _ => return Err(Error::ParseFailed),
+        };
+        if drop_frame && !rate.is_drop_frame() {
+            return Err(Error::ParseFailed);
+        }
+        Ok(rate)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn frame_rates_round_trip_through_strings() {
+        for rate in FrameRate::ALL {
+            assert_eq!(rate.to_string().parse::<FrameRate>().unwrap(), rate);
+        }
+        assert_eq!(
+            "30000/1001 DF".parse::<FrameRate>().unwrap(),
+            FrameRate::Fps29_97Df
+        );
+        assert!("25df".parse::<FrameRate>().is_err());
+        assert!("26".parse::<FrameRate>().is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "libltc")]
+    fn frame_rates_select_matching_tv_standard() {
+        assert_eq!(
+            FrameRate::Fps23_976.tv_standard(),
+            ffi::LTC_TV_STANDARD_LTC_TV_FILM_24
+        );
+        assert_eq!(
+            FrameRate::Fps25.tv_standard(),
+            ffi::LTC_TV_STANDARD_LTC_TV_625_50
+        );
+        assert_eq!(
+            FrameRate::Fps29_97Df.tv_standard(),
+            ffi::LTC_TV_STANDARD_LTC_TV_525_60
+        );
+        // Frame pairs are sent as LTC at half the rate.
+        assert_eq!(
+            FrameRate::Fps48.tv_standard(),
+            ffi::LTC_TV_STANDARD_LTC_TV_FILM_24
+        );
+        assert_eq!(
+            FrameRate::Fps50.tv_standard(),
+            ffi::LTC_TV_STANDARD_LTC_TV_625_50
+        );
+        assert_eq!(
+            FrameRate::Fps59_94.tv_standard(),
+            ffi::LTC_TV_STANDARD_LTC_TV_525_60
+        );
+    }
+}
@@ -16,17 +16,99 @@
 // License along with this program.  If not, see <http://www.gnu.org/licenses/>.
 //
 // SPDX-License-Identifier: LGPL-3.0-or-later
-use x42ltc_sys::{self as ffi, LTCFrame, LTCFrameExt, SMPTETimecode};
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
+
+extern crate alloc;
+
+use alloc::vec::Vec;
+use core::fmt;
+
+#[cfg(not(any(feature = "libltc", feature = "pure-rust")))]
+compile_error!("either the `libltc` or the `pure-rust` feature must be enabled");
+
+#[cfg(feature = "artnet")]
+mod artnet;
+mod chase;
+mod detector;
+#[cfg(not(any(feature = "std", test)))]
+mod float;
+mod frame;
+mod frame_rate;
+#[cfg(feature = "libltc")]
+// With both features enabled, libltc is only used to cross-check the native backend in tests.
+#[cfg_attr(feature = "pure-rust", allow(dead_code))]
+mod libltc;
+mod modulator;
+mod mtc;
+mod multi_decoder;
+#[cfg(feature = "pure-rust")]
+mod native;
+#[cfg(feature = "osc")]
+mod osc;
+mod timecode;
+#[cfg(feature = "std")]
+mod wav;
+#[cfg(feature = "wav")]
+mod wav_decoder;
+
+#[cfg(feature = "artnet")]
+pub use artnet::{ArtNetReceiver, ArtNetSender, ArtTimeCode, ARTNET_PORT, ART_TIME_CODE_LEN};
+pub use chase::{ChaseClock, LockState};
+pub use detector::{Confidence, FrameRateDetector};
+pub use frame::{LtcFrame, SYNC_WORD};
+pub use frame_rate::FrameRate;
+pub use mtc::{MtcBridge, MtcEvent, MtcMessage, MtcParser, TimedMtcMessage};
+pub use multi_decoder::MultiDecoder;
+#[cfg(feature = "osc")]
+pub use osc::{OscArguments, OscConfig, OscSender};
+pub use timecode::{Date, FrameCount, Timecode, TimezoneOffset};
+#[cfg(feature = "std")]
+pub use wav::{SampleFormat, WavConfig, WavWriter};
+#[cfg(feature = "wav")]
+pub use wav_decoder::{decode_wav, decode_wav_with_frame_rate, WavFrames};
+
+#[cfg(not(any(feature = "std", test)))]
+use float::Float;
+use modulator::Modulator;
+
+// The native backend takes precedence, so that both can be compared when both are enabled.
+#[cfg(all(feature = "libltc", not(feature = "pure-rust")))]
+use libltc::{RawDecoder, RawEncoder};
+#[cfg(feature = "pure-rust")]
+use native::{RawDecoder, RawEncoder};
 
 #[derive(Debug)]
 pub enum Error {
     AllocationFailed,
     ReinitializationFailed,
     ValueOutOfRange,
+    ParseFailed,
+    BufferTooSmall,
 }
 
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Error::AllocationFailed => "memory allocation failed",
+            Error::ReinitializationFailed => "encoder reinitialization failed",
+            Error::ValueOutOfRange => "value out of range",
+            Error::ParseFailed => "failed to parse value",
+            Error::BufferTooSmall => "buffer too small",
+        })
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for Error {}
+
+/// The number of samples [`Decoder::write_interleaved()`] de-interleaves at once, which matches
+/// the chunks libltc converts samples to 8 bit in, so that the sample offsets are unaffected.
+const INTERLEAVED_CHUNK_SIZE: usize = 1024;
+
 pub struct Decoder {
-    pointer: *mut ffi::LTCDecoder,
+    raw: RawDecoder,
+    position: i64,
+    skipped_frames: u64,
 }
 
 impl Decoder {
@@ -43,13 +125,51 @@ impl Decoder {
     /// let decoder = x42ltc::Decoder::new(1920,32).unwrap();
     /// ```
     pub fn new(audio_frames_per_video_frame: i32, queue_size: i32) -> Result<Decoder, Error> {
-        let pointer = unsafe { ffi::ltc_decoder_create(audio_frames_per_video_frame, queue_size) };
+        let raw = RawDecoder::new(audio_frames_per_video_frame, queue_size)
+            .ok_or(Error::AllocationFailed)?;
+        Ok(Decoder {
+            raw,
+            position: 0,
+            skipped_frames: 0,
+        })
+    }
 
-        if pointer.is_null() {
-            Err(Error::AllocationFailed)
-        } else {
-            Ok(Decoder { pointer })
-        }
+    /// Create a new LTC decoder expecting LTC at `frame_rate` in audio sampled at `sample_rate`.
+    ///
+    /// This is [`Decoder::new()`](#method.new) with `audio_frames_per_video_frame` derived from
+    /// the given rates.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use x42ltc::{Decoder, FrameRate};
+    ///
+    /// let decoder = Decoder::with_frame_rate(48_000, FrameRate::Fps29_97Df, 32).unwrap();
+    /// ```
+    pub fn with_frame_rate(
+        sample_rate: u32,
+        frame_rate: FrameRate,
+        queue_size: i32,
+    ) -> Result<Decoder, Error> {
+        let fps = frame_rate.ltc_frame_rate().as_f64();
+        let audio_frames_per_video_frame = (f64::from(sample_rate) / fps).round();
+        Decoder::new(audio_frames_per_video_frame as i32, queue_size)
+    }
+
+    /// Create a new LTC decoder for audio sampled at `sample_rate`, without knowing the frame rate
+    /// of the signal.
+    ///
+    /// The decoder starts out expecting 25 fps, which is close enough to pick up the first frame
+    /// at any rate from 23.976 to 60 fps.  Its queue holds 32 frames.  Feed the decoded frames to
+    /// a [`FrameRateDetector`] to find out the actual rate.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let decoder = x42ltc::Decoder::with_sample_rate(44_100).unwrap();
+    /// ```
+    pub fn with_sample_rate(sample_rate: u32) -> Result<Decoder, Error> {
+        Decoder::with_frame_rate(sample_rate, FrameRate::Fps25, 32)
     }
 
     /// Resets the decoder queue.
@@ -58,9 +178,7 @@ impl Decoder {
     /// decoder.queue_flush();
     /// ```
     pub fn queue_flush(&mut self) {
-        unsafe {
-            ffi::ltc_decoder_queue_flush(self.pointer);
-        }
+        self.raw.queue_flush();
     }
 
     /// Gets the decoder queue length.
@@ -70,70 +188,450 @@ impl Decoder {
     /// assert_eq!(0, len);
     /// ```
     pub fn queue_length(&mut self) -> i32 {
-        unsafe { ffi::ltc_decoder_queue_length(self.pointer) }
+        self.raw.queue_length()
     }
 
     /// Writes audio data into the decoder.
     /// ```
     /// let mut decoder = x42ltc::Decoder::new(1920,32).unwrap();
-    /// let mut sound = vec![0_u8;1920];
-    /// decoder.write(sound.as_mut_slice());
+    /// let sound = vec![0_u8;1920];
+    /// decoder.write(&sound);
     /// let len = decoder.queue_length();
     /// assert_eq!(0, len);
     /// ```
-    pub fn write(&mut self, data: &mut [u8]) {
-        let len = data.len();
-        unsafe {
-            ffi::ltc_decoder_write(self.pointer, data.as_mut_ptr(), len, 0);
-        }
+    pub fn write(&mut self, data: &[u8]) {
+        self.raw.write(data, self.position);
+        self.position += data.len() as i64;
     }
+
     /// Writes audio data into the decoder as f32.
     /// ```
     /// let mut decoder = x42ltc::Decoder::new(1920,32).unwrap();
-    /// let mut sound = vec![0.0_f32;1920];
-    /// decoder.write_f32(sound.as_mut_slice());
+    /// let sound = vec![0.0_f32;1920];
+    /// decoder.write_f32(&sound);
     /// let len = decoder.queue_length();
     /// assert_eq!(0, len);
     /// ```
-    pub fn write_f32(&mut self, data: &mut [f32]) {
-        let len = data.len();
-        unsafe {
-            ffi::ltc_decoder_write_float(self.pointer, data.as_mut_ptr(), len, 0);
+    pub fn write_f32(&mut self, data: &[f32]) {
+        self.raw.write_f32(data, self.position);
+        self.position += data.len() as i64;
+    }
+
+    /// Writes audio data into the decoder as f64.
+    /// ```
+    /// let mut decoder = x42ltc::Decoder::new(1920,32).unwrap();
+    /// let sound = vec![0.0_f64;1920];
+    /// decoder.write_f64(&sound);
+    /// let len = decoder.queue_length();
+    /// assert_eq!(0, len);
+    /// ```
+    pub fn write_f64(&mut self, data: &[f64]) {
+        self.raw.write_f64(data, self.position);
+        self.position += data.len() as i64;
+    }
+
+    /// Writes audio data into the decoder as signed 16 bit integers.
+    /// ```
+    /// let mut decoder = x42ltc::Decoder::new(1920,32).unwrap();
+    /// let sound = vec![0_i16;1920];
+    /// decoder.write_i16(&sound);
+    /// let len = decoder.queue_length();
+    /// assert_eq!(0, len);
+    /// ```
+    pub fn write_i16(&mut self, data: &[i16]) {
+        self.raw.write_i16(data, self.position);
+        self.position += data.len() as i64;
+    }
+
+    /// Writes audio data into the decoder as unsigned 16 bit integers, centered around `0x8000`.
+    /// ```
+    /// let mut decoder = x42ltc::Decoder::new(1920,32).unwrap();
+    /// let sound = vec![0x8000_u16;1920];
+    /// decoder.write_u16(&sound);
+    /// let len = decoder.queue_length();
+    /// assert_eq!(0, len);
+    /// ```
+    pub fn write_u16(&mut self, data: &[u16]) {
+        self.raw.write_u16(data, self.position);
+        self.position += data.len() as i64;
+    }
+
+    /// Writes channel `channel_index` (counting from 0) of interleaved audio data with `channels`
+    /// channels into the decoder.
+    ///
+    /// The samples are de-interleaved in chunks on the stack, without allocating.  The sample
+    /// offsets of the decoded frames count sample frames, i.e. samples per channel.  A trailing
+    /// incomplete sample frame is ignored.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `channel_index` is not less than `channels`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let mut decoder = x42ltc::Decoder::new(1920, 32).unwrap();
+    /// // Stereo, LTC on the right channel.
+    /// let sound = vec![0_i16; 2 * 1920];
+    /// decoder.write_interleaved(&sound, 2, 1);
+    /// assert_eq!(decoder.position(), 1920);
+    /// ```
+    pub fn write_interleaved<S: Sample>(
+        &mut self,
+        data: &[S],
+        channels: usize,
+        channel_index: usize,
+    ) {
+        assert!(
+            channel_index < channels,
+            "channel index {} out of range for {} channels",
+            channel_index,
+            channels
+        );
+        let Some(&first) = data.get(channel_index) else {
+            return;
+        };
+        let mut buffer = [first; INTERLEAVED_CHUNK_SIZE];
+        for chunk in data.chunks(INTERLEAVED_CHUNK_SIZE * channels) {
+            let mut len = 0;
+            for (sample, frame) in buffer.iter_mut().zip(chunk.chunks_exact(channels)) {
+                *sample = frame[channel_index];
+                len += 1;
+            }
+            S::write_to(self, &buffer[..len]);
         }
     }
 
-    pub fn read(&mut self) -> Option<SMPTETimecode> {
-        let mut frame = LTCFrameExt::default();
+    /// The absolute sample position of the next sample written to the decoder, i.e. the total
+    /// number of samples written so far.
+    ///
+    /// The sample offsets of [`DecodedFrame`]s are relative to this running position.
+    ///
+    /// ```
+    /// let mut decoder = x42ltc::Decoder::new(1920,32).unwrap();
+    /// decoder.write(&[128_u8; 1000]);
+    /// decoder.write_f32(&[0.0_f32; 500]);
+    /// assert_eq!(1500, decoder.position());
+    /// ```
+    pub fn position(&self) -> i64 {
+        self.position
+    }
 
-        unsafe {
-            let read_ret = ffi::ltc_decoder_read(self.pointer, &mut frame as *mut LTCFrameExt);
-            if read_ret == 0 {
-                return None;
+    /// Reads the next decoded frame from the queue.
+    ///
+    /// Returns `None` once the queue is empty.  Frames whose time of day is not a valid timecode
+    /// (e.g. due to a corrupted signal) are skipped, so `None` does not tell whether such frames
+    /// were decoded; they are counted by [`.skipped_frames()`](#method.skipped_frames) instead.
+    /// User bits which do not hold a valid date or timezone are reported as `None` in the
+    /// respective field of the frame's [`Timecode`].
+    pub fn read(&mut self) -> Option<DecodedFrame> {
+        loop {
+            match self.raw.read()? {
+                Ok(frame) => return Some(frame),
+                Err(_) => self.skipped_frames += 1,
             }
+        }
+    }
+
+    /// The number of frames skipped by [`.read()`](#method.read) so far, because they did not
+    /// hold a valid time of day.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let mut encoder = x42ltc::Encoder::new(48_000, x42ltc::FrameRate::Fps25).unwrap();
+    /// let mut decoder = x42ltc::Decoder::new(1920, 32).unwrap();
+    ///
+    /// // Hour 29 does not exist.
+    /// let mut frame = encoder.get_frame();
+    /// frame.set_hours_tens(2).unwrap();
+    /// frame.set_hours_units(9).unwrap();
+    /// encoder.set_frame(&frame);
+    /// for _ in 0..2 {
+    ///     encoder.encode_frame();
+    ///     decoder.write(encoder.get_buffer());
+    /// }
+    ///
+    /// assert!(decoder.read().is_none());
+    /// assert_eq!(decoder.skipped_frames(), 1);
+    /// ```
+    pub fn skipped_frames(&self) -> u64 {
+        self.skipped_frames
+    }
 
-            let mut time = SMPTETimecode::default();
+    /// Returns an iterator draining all frames currently in the queue.
+    ///
+    /// Like [`.read()`](#method.read), it skips frames which do not hold a valid time of day.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let mut encoder = x42ltc::Encoder::new(48_000, x42ltc::FrameRate::Fps25).unwrap();
+    /// let mut decoder = x42ltc::Decoder::new(1920, 32).unwrap();
+    ///
+    /// for _ in 0..10 {
+    ///     encoder.encode_frame();
+    ///     decoder.write(encoder.get_buffer());
+    ///     encoder.increase_timecode();
+    /// }
+    ///
+    /// assert_eq!(decoder.frames().count(), 9);
+    /// assert_eq!(decoder.queue_length(), 0);
+    /// ```
+    pub fn frames(&mut self) -> Frames<'_> {
+        Frames { decoder: self }
+    }
 
-            ffi::ltc_frame_to_time(
-                &mut time as *mut SMPTETimecode,
-                &mut frame.ltc as *mut LTCFrame,
-                1,
-            );
+    /// Writes a chunk of audio data into the decoder and returns an iterator over all frames
+    /// which are completed by it, and any frames which were still queued.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let mut encoder = x42ltc::Encoder::new(48_000, x42ltc::FrameRate::Fps25).unwrap();
+    /// let mut decoder = x42ltc::Decoder::new(1920, 32).unwrap();
+    ///
+    /// let mut audio = Vec::new();
+    /// for _ in 0..10 {
+    ///     encoder.encode_frame();
+    ///     audio.extend(encoder.get_buffer().iter().map(|&s| (f32::from(s) - 128.0) / 127.0));
+    ///     encoder.increase_timecode();
+    /// }
+    ///
+    /// let mut decoded = 0;
+    /// for chunk in audio.chunks(1024) {
+    ///     for frame in decoder.decode(chunk) {
+    ///         assert_eq!(frame.timecode().secs(), 0);
+    ///         decoded += 1;
+    ///     }
+    /// }
+    /// assert_eq!(decoded, 9);
+    /// ```
+    pub fn decode<S: Sample>(&mut self, data: &[S]) -> Frames<'_> {
+        S::write_to(self, data);
+        self.frames()
+    }
+}
+
+/// An LTC frame read from the [`Decoder`], along with its position in the audio stream.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DecodedFrame {
+    timecode: Timecode,
+    frame: LtcFrame,
+    off_start: i64,
+    off_end: i64,
+    reverse: bool,
+    sample_min: u8,
+    sample_max: u8,
+    volume: f64,
+}
+
+impl DecodedFrame {
+    /// Returns `Error::ValueOutOfRange` if the frame does not hold a valid time of day.
+    fn new(
+        frame: LtcFrame,
+        off_start: i64,
+        off_end: i64,
+        reverse: bool,
+        sample_min: u8,
+        sample_max: u8,
+        volume: f64,
+    ) -> Result<DecodedFrame, Error> {
+        Ok(DecodedFrame {
+            timecode: frame.timecode(true)?,
+            frame,
+            off_start,
+            off_end,
+            reverse,
+            sample_min,
+            sample_max,
+            volume,
+        })
+    }
+
+    /// The decoded timecode.
+    ///
+    /// LTC above 30 fps is sent as frame pairs, which look like LTC at half the rate.  Use
+    /// [`LtcFrame::to_timecode_at()`] on [`.frame()`](#method.frame) to count frames at the
+    /// actual rate.
+    pub fn timecode(&self) -> Timecode {
+        self.timecode
+    }
+
+    /// The raw 80 bit LTC frame, bit 0 being the least significant bit of the first byte.
+    pub fn raw(&self) -> [u8; 10] {
+        self.frame.to_bytes()
+    }
+
+    /// The LTC frame, giving access to the user bits and flags.
+    pub fn frame(&self) -> &LtcFrame {
+        &self.frame
+    }
+
+    /// The approximate absolute sample position corresponding to the start of the LTC frame,
+    /// i.e. the first transition of bit 0.
+    pub fn off_start(&self) -> i64 {
+        self.off_start
+    }
+
+    /// The absolute sample position corresponding to the end of the LTC frame.
+    pub fn off_end(&self) -> i64 {
+        self.off_end
+    }
+
+    /// Whether the frame was played backwards.  A reversed frame starts at
+    /// [`.off_end()`](#method.off_end) and finishes at [`.off_start()`](#method.off_start).
+    pub fn is_reverse(&self) -> bool {
+        self.reverse
+    }
+
+    /// The minimum input sample value of this frame, converted to unsigned 8 bit (0..255).
+    pub fn sample_min(&self) -> u8 {
+        self.sample_min
+    }
+
+    /// The maximum input sample value of this frame, converted to unsigned 8 bit (0..255).
+    pub fn sample_max(&self) -> u8 {
+        self.sample_max
+    }
+
+    /// The volume of the input signal in dBFS.
+    pub fn volume(&self) -> f64 {
+        self.volume
+    }
+}
 
-            Some(time)
+/// Draining iterator over the frames queued in a [`Decoder`].
+///
+/// Created by [`Decoder::frames()`] and [`Decoder::decode()`].
+pub struct Frames<'a> {
+    decoder: &'a mut Decoder,
+}
+
+impl Iterator for Frames<'_> {
+    type Item = DecodedFrame;
+
+    fn next(&mut self) -> Option<DecodedFrame> {
+        self.decoder.read()
+    }
+}
+
+// No new frames can be queued while the decoder is borrowed by the iterator.
+impl core::iter::FusedIterator for Frames<'_> {}
+
+mod sealed {
+    pub trait Sealed {}
+}
+
+/// Audio sample formats accepted by [`Decoder::decode()`].
+///
+/// This trait is sealed, it is implemented for `u8`, `i16`, `u16`, `f32` and `f64`, matching
+/// the `write*` methods of the [`Decoder`].
+pub trait Sample: sealed::Sealed + Copy {
+    #[doc(hidden)]
+    fn write_to(decoder: &mut Decoder, data: &[Self]);
+}
+
+macro_rules! impl_sample {
+    ($($ty:ty => $write:ident),*) => {$(
+        impl sealed::Sealed for $ty {}
+
+        impl Sample for $ty {
+            fn write_to(decoder: &mut Decoder, data: &[Self]) {
+                decoder.$write(data);
+            }
         }
+    )*};
+}
+
+impl_sample!(u8 => write, i16 => write_i16, u16 => write_u16, f32 => write_f32, f64 => write_f64);
+
+bitflags::bitflags! {
+    /// Flags controlling the binary group flag (BGF) bits and the interpretation of the user bits
+    /// of encoded LTC frames.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+    pub struct BgFlags: u32 {
+        /// Encode the date and timezone of a [`Timecode`] into the user bits (SMPTE 309), and
+        /// increment the date when the timecode wraps around after 24 hours.  Also sets BGF2 to
+        /// mark the user bits as carrying a date, unless `BGF_DONT_TOUCH` is given.
+        const USE_DATE = 1;
+        /// The timecode is wall-clock time (free-running).  Sets BGF1, unless `BGF_DONT_TOUCH` is
+        /// given.
+        const TC_CLOCK = 2;
+        /// Do not modify the BGF bits when (re-)initializing the encoder.
+        const BGF_DONT_TOUCH = 4;
+        /// Leave the parity bit untouched when setting or stepping the timecode.
+        const NO_PARITY = 8;
     }
 }
 
-impl Drop for Decoder {
-    fn drop(&mut self) {
-        unsafe {
-            ffi::ltc_decoder_free(self.pointer);
+/// The lowest volume in dBFS libltc accepts for its 8 bit output.
+const MIN_VOLUME_U8: f64 = -42.0;
+
+/// The largest signed 24 bit sample value.
+const I24_MAX: i32 = (1 << 23) - 1;
+
+/// Convert a sample in -1.0..=1.0 to signed 16 bit.
+pub(crate) fn sample_to_i16(value: f64) -> i16 {
+    (value * f64::from(i16::MAX)).round() as i16
+}
+
+/// Convert a sample in -1.0..=1.0 to packed signed 24 bit in little endian byte order.
+pub(crate) fn sample_to_i24(value: f64) -> [u8; 3] {
+    let sample = (value * f64::from(I24_MAX)).round() as i32;
+    let bytes = sample.to_le_bytes();
+    [bytes[0], bytes[1], bytes[2]]
+}
+
+/// Settings for creating an [`Encoder`].
+///
+/// # Example
+///
+/// Encode the calendar date into the user bits:
+///
+/// ```
+/// use x42ltc::{BgFlags, Date, Encoder, EncoderConfig, FrameRate, Timecode};
+///
+/// let config = EncoderConfig::new(48_000, FrameRate::Fps25).flags(BgFlags::USE_DATE);
+/// let mut encoder = Encoder::with_config(config).unwrap();
+///
+/// let date = Date::new(2008, 12, 31).unwrap();
+/// encoder.set_timecode(Timecode::new(23, 59, 59, 24).unwrap().with_date(date));
+/// encoder.increase_timecode();
+///
+/// let tc = encoder.get_timecode().unwrap();
+/// assert_eq!(tc.date(), Some(Date::new(2009, 1, 1).unwrap()));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct EncoderConfig {
+    pub sample_rate: u32,
+    pub frame_rate: FrameRate,
+    pub flags: BgFlags,
+}
+
+impl EncoderConfig {
+    /// Create a configuration for the given rates, which leaves the BGF bits untouched.
+    pub fn new(sample_rate: u32, frame_rate: FrameRate) -> EncoderConfig {
+        EncoderConfig {
+            sample_rate,
+            frame_rate,
+            flags: BgFlags::BGF_DONT_TOUCH,
         }
     }
+
+    /// Returns this configuration with the given flags.
+    pub fn flags(mut self, flags: BgFlags) -> EncoderConfig {
+        self.flags = flags;
+        self
+    }
 }
 
 pub struct Encoder {
-    pointer: *mut ffi::LTCEncoder,
+    raw: RawEncoder,
+    sample_rate: u32,
+    frame_rate: FrameRate,
+    flags: BgFlags,
+    modulator: Modulator,
 }
 
 impl Encoder {
@@ -145,35 +643,66 @@ impl Encoder {
     /// # Example
     ///
     /// ```
-    /// let encoder = x42ltc::Encoder::new(48_000, 25.0).unwrap();
-    /// ```
-    pub fn new(sample_rate: u32, fps: f64) -> Result<Encoder, Error> {
-        let pointer = unsafe {
-            ffi::ltc_encoder_create(
-                f64::from(sample_rate),
-                fps,
-                // Position of binary group flags is only different for 25 fps
-                if (fps - 25.0).abs() < f64::EPSILON {
-                    ffi::LTC_TV_STANDARD_LTC_TV_625_50
-                } else {
-                    ffi::LTC_TV_STANDARD_LTC_TV_525_60
-                },
-                ffi::LTC_BG_FLAGS_LTC_BGF_DONT_TOUCH as i32,
-            )
+    /// let encoder = x42ltc::Encoder::new(48_000, x42ltc::FrameRate::Fps25).unwrap();
+    /// ```
+    pub fn new(sample_rate: u32, frame_rate: FrameRate) -> Result<Encoder, Error> {
+        Encoder::with_config(EncoderConfig::new(sample_rate, frame_rate))
+    }
+
+    /// Allocate and initialize LTC audio encoder with the given configuration.
+    ///
+    /// See [`EncoderConfig`] for an example.
+    pub fn with_config(config: EncoderConfig) -> Result<Encoder, Error> {
+        let ltc_frame_rate = config.frame_rate.ltc_frame_rate();
+        let raw = RawEncoder::new(config.sample_rate, ltc_frame_rate, config.flags)
+            .ok_or(Error::AllocationFailed)?;
+        let mut encoder = Encoder {
+            raw,
+            sample_rate: config.sample_rate,
+            frame_rate: config.frame_rate,
+            flags: config.flags,
+            modulator: Modulator::new(config.sample_rate, ltc_frame_rate),
         };
+        encoder.update_drop_frame_bit();
+        Ok(encoder)
+    }
 
-        if pointer.is_null() {
-            Err(Error::AllocationFailed)
-        } else {
-            Ok(Encoder { pointer })
+    /// The current configuration of the encoder.
+    pub fn config(&self) -> EncoderConfig {
+        EncoderConfig {
+            sample_rate: self.sample_rate,
+            frame_rate: self.frame_rate,
+            flags: self.flags,
         }
     }
 
-    /// Move the encoder to the previous timecode frame. This is useful for encoding reverse LTC.
-    pub fn decrease_timecode(&mut self) {
-        unsafe {
-            ffi::ltc_encoder_dec_timecode(self.pointer);
+    /// The sample rate the encoder is currently set up for.
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    /// The frame rate the encoder is currently set up for.
+    pub fn frame_rate(&self) -> FrameRate {
+        self.frame_rate
+    }
+
+    /// libltc sets the `dfbit` for 29.97 fps only, regardless of whether drop-frame counting is
+    /// wanted, so set it from the frame rate instead.
+    fn update_drop_frame_bit(&mut self) {
+        let mut frame = self.get_frame();
+        frame.set_dfbit(self.frame_rate.is_drop_frame());
+        if !self.flags.contains(BgFlags::NO_PARITY) {
+            frame.set_parity(self.frame_rate);
         }
+        self.set_frame(&frame);
+    }
+
+    /// Move the encoder to the previous timecode frame, or the previous frame pair above 30 fps.
+    /// This is useful for encoding reverse LTC.
+    pub fn decrease_timecode(&mut self) {
+        let mut frame = self.get_frame();
+        frame.decrement(self.frame_rate, self.flags);
+        self.set_frame(&frame);
     }
 
     /// Encode a full LTC frame at fixed speed.  This is equivalent to calling
@@ -187,25 +716,267 @@ impl Encoder {
     ///
     /// The default internal buffer size is exactly one full LTC frame at speed 1.0.
     pub fn encode_frame(&mut self) {
-        unsafe {
-            ffi::ltc_encoder_encode_frame(self.pointer);
+        self.raw.encode_frame();
+    }
+
+    /// Encode a full LTC frame at fixed speed -1, i.e. with its bits in reverse order, as if
+    /// played backwards.  This is equivalent to calling [`.encode_byte()`](#method.encode_byte) 10
+    /// times for bytes 9..=0 with speed -1.0.
+    ///
+    /// The same note as for [`.encode_frame()`](#method.encode_frame) regarding the internal
+    /// buffer applies.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let mut encoder = x42ltc::Encoder::new(48_000, x42ltc::FrameRate::Fps25).unwrap();
+    /// encoder.encode_reversed_frame();
+    /// assert_eq!(encoder.get_buffer().len(), 48_000 / 25);
+    /// ```
+    pub fn encode_reversed_frame(&mut self) {
+        self.raw.encode_reversed_frame();
+    }
+
+    /// Encode the current LTC frame at fixed speed into `buffer` as 32 bit floating point samples
+    /// in the range -1.0..=1.0, without touching the internal 8 bit buffer.
+    ///
+    /// The signal is computed at full precision, using the volume and rise time set with
+    /// [`.set_volume()`](#method.set_volume) and [`.set_filter()`](#method.set_filter).  Like
+    /// [`.encode_frame()`](#method.encode_frame), this does not move the encoder to the next
+    /// frame.  The biphase state carries over from one call to the next, but is kept separately
+    /// from the one of the 8 bit output.
+    ///
+    /// # Return value
+    ///
+    /// Returns the number of samples written, or `Error::BufferTooSmall` if `buffer` cannot hold
+    /// the frame.  A buffer of [`.get_buffer_size()`](#method.get_buffer_size) samples is always
+    /// large enough.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let mut encoder = x42ltc::Encoder::new(48_000, x42ltc::FrameRate::Fps25).unwrap();
+    /// encoder.set_volume(-18.0).unwrap();
+    ///
+    /// let mut buffer = vec![0.0f32; encoder.get_buffer_size()];
+    /// let len = encoder.encode_frame_f32(&mut buffer).unwrap();
+    /// assert_eq!(len, 48_000 / 25);
+    /// assert!(buffer.iter().all(|s| s.abs() <= 0.126));
+    /// ```
+    pub fn encode_frame_f32(&mut self, buffer: &mut [f32]) -> Result<usize, Error> {
+        let mut samples = buffer.iter_mut();
+        self.modulate(samples.len(), false, |value| {
+            *samples.next().unwrap() = value as f32;
+        })
+    }
+
+    /// Encode the current LTC frame at fixed speed into `buffer` as signed 16 bit samples.
+    ///
+    /// Otherwise identical to [`.encode_frame_f32()`](#method.encode_frame_f32).
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let mut encoder = x42ltc::Encoder::new(48_000, x42ltc::FrameRate::Fps25).unwrap();
+    /// encoder.set_volume(-60.0).unwrap();
+    ///
+    /// let mut buffer = vec![0i16; encoder.get_buffer_size()];
+    /// let len = encoder.encode_frame_i16(&mut buffer).unwrap();
+    /// assert_eq!(buffer[..len].iter().map(|s| s.abs()).max(), Some(33));
+    /// ```
+    pub fn encode_frame_i16(&mut self, buffer: &mut [i16]) -> Result<usize, Error> {
+        let mut samples = buffer.iter_mut();
+        self.modulate(samples.len(), false, |value| {
+            *samples.next().unwrap() = sample_to_i16(value);
+        })
+    }
+
+    /// Encode the current LTC frame at fixed speed into `buffer` as packed signed 24 bit samples,
+    /// three bytes per sample in little endian byte order, as used in WAV files.
+    ///
+    /// Otherwise identical to [`.encode_frame_f32()`](#method.encode_frame_f32), the returned
+    /// length is in samples, not bytes.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let mut encoder = x42ltc::Encoder::new(48_000, x42ltc::FrameRate::Fps25).unwrap();
+    ///
+    /// let mut buffer = vec![0u8; 3 * encoder.get_buffer_size()];
+    /// let len = encoder.encode_frame_i24(&mut buffer).unwrap();
+    /// assert_eq!(len, 48_000 / 25);
+    /// ```
+    pub fn encode_frame_i24(&mut self, buffer: &mut [u8]) -> Result<usize, Error> {
+        let mut samples = buffer.chunks_exact_mut(3);
+        self.modulate(samples.len(), false, |value| {
+            samples
+                .next()
+                .unwrap()
+                .copy_from_slice(&sample_to_i24(value));
+        })
+    }
+
+    /// Modulate the current frame at full precision, with its bits in reverse order if `reverse`
+    /// is set, passing each sample to `write` unless there are more than `capacity` of them.
+    pub(crate) fn modulate(
+        &mut self,
+        capacity: usize,
+        reverse: bool,
+        write: impl FnMut(f64),
+    ) -> Result<usize, Error> {
+        let bytes = self.get_frame().to_bytes();
+        let len = self.modulator.samples_needed(&bytes, reverse);
+        if len > capacity {
+            return Err(Error::BufferTooSmall);
+        }
+        self.modulator.modulate(&bytes, reverse, write);
+        Ok(len)
+    }
+
+    /// Returns an endless iterator yielding the encoded audio of one LTC frame at a time, moving
+    /// the encoder to the next frame after each one for [`Direction::Forward`], or to the previous
+    /// frame for [`Direction::Reverse`].  Reverse frames are encoded with their bits in reverse
+    /// order, like LTC from a rewinding tape machine.
+    ///
+    /// The internal buffer must be empty when starting, see
+    /// [`.encode_frame()`](#method.encode_frame).
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use x42ltc::{Direction, Encoder, FrameRate};
+    ///
+    /// let mut encoder = Encoder::new(48_000, FrameRate::Fps25).unwrap();
+    /// encoder.set_timecode("00:00:01:00".parse().unwrap());
+    /// let audio: Vec<u8> = encoder.generate(Direction::Reverse).take(25).flatten().collect();
+    /// assert_eq!(audio.len(), 48_000);
+    /// assert_eq!(encoder.get_timecode().unwrap().to_string(), "00:00:00:00");
+    /// ```
+    pub fn generate(&mut self, direction: Direction) -> Generator<'_> {
+        Generator {
+            encoder: self,
+            direction,
         }
     }
 
+    /// Encode a single byte of the current LTC frame into the internal buffer.
+    ///
+    /// An LTC frame has 10 bytes, so `byte` must be in `0..=9`.  `speed` is a factor applied to
+    /// the duration of each bit, i.e. values > 1.0 produce *slower* LTC and values < 1.0 faster
+    /// LTC.  If `speed` is negative, the bits are encoded in reverse order.  See
+    /// [`.encode_with_speed()`](#method.encode_with_speed) for a higher-level interface taking a
+    /// playback speed.
+    ///
+    /// # Return value
+    ///
+    /// Returns `Error::ValueOutOfRange` if `byte` > 9 or `speed` is zero, and
+    /// `Error::BufferTooSmall` if the internal buffer overflowed.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let mut encoder = x42ltc::Encoder::new(48_000, x42ltc::FrameRate::Fps25).unwrap();
+    /// for byte in 0..10 {
+    ///     encoder.encode_byte(byte, 1.0).unwrap();
+    /// }
+    /// assert_eq!(encoder.buffered_len(), 48_000 / 25);
+    /// assert!(encoder.encode_byte(10, 1.0).is_err());
+    /// ```
+    pub fn encode_byte(&mut self, byte: u8, speed: f64) -> Result<(), Error> {
+        if byte > 9 || speed == 0.0 || !speed.is_finite() {
+            return Err(Error::ValueOutOfRange);
+        }
+        if !self.raw.encode_byte(byte, speed) {
+            return Err(Error::BufferTooSmall);
+        }
+        Ok(())
+    }
+
+    /// Terminate encoding by adding the final transition of the last frame to the internal
+    /// buffer.
+    ///
+    /// LTC is usually sent as a continuous stream, where the transition at the start of the next
+    /// frame marks the end of the previous one.  When encoding a finite duration, call this after
+    /// the last frame.  Afterwards the encoder must be [`.reset()`](#method.reset) before encoding
+    /// again.
+    ///
+    /// Returns `Error::BufferTooSmall` if the internal buffer overflowed.
+    pub fn end_encode(&mut self) -> Result<(), Error> {
+        if !self.raw.end_encode() {
+            return Err(Error::BufferTooSmall);
+        }
+        Ok(())
+    }
+
+    /// Encode a full LTC frame at the given playback `speed`.
+    ///
+    /// A `speed` of 1.0 is equivalent to [`.encode_frame()`](#method.encode_frame), 0.5 produces
+    /// a frame lasting twice as long and negative speeds produce LTC playing in reverse.
+    ///
+    /// For speeds below 1.0 a frame does not fit into the default internal buffer, so it is grown
+    /// as needed, as long as it is empty.  This call does not move the encoder to the next or
+    /// previous frame.
+    ///
+    /// # Return value
+    ///
+    /// Returns `Error::ValueOutOfRange` if `speed` is zero, `Error::BufferTooSmall` if the frame
+    /// does not fit into a non-empty internal buffer and `Error::AllocationFailed` if growing the
+    /// internal buffer failed.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let mut encoder = x42ltc::Encoder::new(48_000, x42ltc::FrameRate::Fps25).unwrap();
+    /// encoder.encode_with_speed(0.5).unwrap();
+    /// assert_eq!(encoder.get_buffer().len(), 2 * 48_000 / 25);
+    ///
+    /// encoder.encode_with_speed(-2.0).unwrap();
+    /// assert_eq!(encoder.get_buffer().len(), 48_000 / 25 / 2);
+    /// ```
+    pub fn encode_with_speed(&mut self, speed: f64) -> Result<(), Error> {
+        if speed == 0.0 || !speed.is_finite() {
+            return Err(Error::ValueOutOfRange);
+        }
+
+        let stretch = 1.0 / speed.abs();
+        let fps = self.frame_rate.ltc_frame_rate().as_f64();
+        let frame_len = 1 + (f64::from(self.sample_rate) * stretch / fps).ceil() as usize;
+        let buffered_len = self.buffered_len();
+        if buffered_len + frame_len > self.get_buffer_size() {
+            if buffered_len > 0 {
+                return Err(Error::BufferTooSmall);
+            }
+            let sample_rate = f64::from(self.sample_rate) * stretch;
+            if !self.raw.set_buffer_size(sample_rate, fps) {
+                return Err(Error::AllocationFailed);
+            }
+        }
+
+        if speed > 0.0 {
+            for byte in 0..10 {
+                self.encode_byte(byte, stretch)?;
+            }
+        } else {
+            for byte in (0..10).rev() {
+                self.encode_byte(byte, -stretch)?;
+            }
+        }
+        Ok(())
+    }
+
     /// Resets the write-pointer of the encoded buffer.
     pub fn flush_buffer(&mut self) {
-        unsafe {
-            ffi::ltc_encoder_buffer_flush(self.pointer);
-        }
+        self.raw.flush_buffer();
     }
 
     /// Copy the accumulated encoded audio to the given sample buffer and flush the internal buffer.
     ///
-    /// # Undefined behaviour warning
+    /// Returns the number of samples copied to the start of `buffer`.
+    ///
+    /// # Return value
     ///
-    /// It is the caller's responsibility to make sure that the capacity of the supplied buffer is
-    /// large enough to hold all of the accumulated data, otherwise the program will most likely be
-    /// aborted due to the internal FFI call causing a segmentation fault, which is UB.
+    /// Returns `Error::BufferTooSmall` if `buffer` is shorter than
+    /// [`.buffered_len()`](#method.buffered_len).  Neither buffer is modified in that case.
     ///
     /// # Example
     ///
@@ -214,18 +985,59 @@ impl Encoder {
     /// let frames_per_second = 25;
     ///
     /// // Create a large enough buffer
-    /// let mut audio_buffer = Vec::with_capacity((sample_rate / frames_per_second as u32) as usize);
+    /// let mut audio_buffer = vec![0_u8; (sample_rate / frames_per_second) as usize];
     ///
-    /// let mut encoder = x42ltc::Encoder::new(sample_rate, frames_per_second as f64).unwrap();
+    /// let mut encoder = x42ltc::Encoder::new(sample_rate, x42ltc::FrameRate::Fps25).unwrap();
     /// encoder.encode_frame();
     /// assert_eq!(
-    ///     encoder.copy_audio_to_buffer(&mut audio_buffer),
-    ///     (sample_rate / frames_per_second as u32) as usize,
+    ///     encoder.copy_audio_to_buffer(&mut audio_buffer).unwrap(),
+    ///     (sample_rate / frames_per_second) as usize,
     /// );
+    ///
+    /// encoder.encode_frame();
+    /// assert!(encoder.copy_audio_to_buffer(&mut audio_buffer[..100]).is_err());
+    /// ```
+    pub fn copy_audio_to_buffer(&mut self, buffer: &mut [u8]) -> Result<usize, Error> {
+        if buffer.len() < self.buffered_len() {
+            return Err(Error::BufferTooSmall);
+        }
+        Ok(self.raw.copy_buffer(buffer))
+    }
+
+    /// Append the accumulated encoded audio to `buffer` and flush the internal buffer.
+    ///
+    /// Returns the number of samples appended.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let mut encoder = x42ltc::Encoder::new(48_000, x42ltc::FrameRate::Fps25).unwrap();
+    /// let mut audio = Vec::new();
+    /// for _ in 0..25 {
+    ///     encoder.encode_frame();
+    ///     encoder.extend_into(&mut audio);
+    ///     encoder.increase_timecode();
+    /// }
+    /// assert_eq!(audio.len(), 48_000);
+    /// ```
+    pub fn extend_into(&mut self, buffer: &mut Vec<u8>) -> usize {
+        let samples = self.get_buffer();
+        buffer.extend_from_slice(samples);
+        samples.len()
+    }
+
+    /// The number of encoded samples accumulated in the internal buffer.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let mut encoder = x42ltc::Encoder::new(48_000, x42ltc::FrameRate::Fps25).unwrap();
+    /// assert_eq!(encoder.buffered_len(), 0);
+    /// encoder.encode_frame();
+    /// assert_eq!(encoder.buffered_len(), 48_000 / 25);
     /// ```
-    pub fn copy_audio_to_buffer(&mut self, buffer: &mut [u8]) -> usize {
-        let copied_len = unsafe { ffi::ltc_encoder_get_buffer(self.pointer, buffer.as_mut_ptr()) };
-        copied_len as usize
+    pub fn buffered_len(&self) -> usize {
+        self.raw.buffered_len()
     }
 
     /// Returns a slice to the internal buffer of accumulated audio samples, and flushes buffer
@@ -234,28 +1046,47 @@ impl Encoder {
     /// # Example
     ///
     /// ```
-    /// let mut encoder = x42ltc::Encoder::new(48_000, 25.0).unwrap();
+    /// let mut encoder = x42ltc::Encoder::new(48_000, x42ltc::FrameRate::Fps25).unwrap();
     /// encoder.encode_frame();
     /// let buffer = encoder.get_buffer();
     /// assert_eq!(buffer.len(), 48_000 / 25);
     /// ```
     pub fn get_buffer(&self) -> &[u8] {
-        let mut buf_len = 0;
-        let buf_ptr = unsafe { ffi::ltc_encoder_get_bufptr(self.pointer, &mut buf_len, 1) };
-        unsafe { std::slice::from_raw_parts(buf_ptr, buf_len as usize) }
+        self.raw.take_buffer()
     }
 
-    fn get_frame(&self) -> Frame {
-        let mut frame = ffi::LTCFrame {
-            _bitfield_1: ffi::LTCFrame::new_bitfield_1(
-                0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
-            ),
-            ..Default::default()
-        };
-        unsafe {
-            ffi::ltc_encoder_get_frame(self.pointer, &mut frame);
-        }
-        Frame { frame }
+    /// Get a copy of the current LTC frame of the encoder.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let mut encoder = x42ltc::Encoder::new(48_000, x42ltc::FrameRate::Fps29_97Df).unwrap();
+    /// let frame = encoder.get_frame();
+    /// assert!(frame.dfbit());
+    /// assert_eq!(frame.sync_word(), x42ltc::SYNC_WORD);
+    /// ```
+    pub fn get_frame(&self) -> LtcFrame {
+        self.raw.frame()
+    }
+
+    /// Replace the current LTC frame of the encoder.  The next call to
+    /// [`.encode_frame()`](#method.encode_frame) will encode this frame.
+    ///
+    /// # Note
+    ///
+    /// The frame is used as is; its `dfbit` is not adjusted to the encoder's frame rate.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let mut encoder = x42ltc::Encoder::new(48_000, x42ltc::FrameRate::Fps25).unwrap();
+    /// let mut frame = encoder.get_frame();
+    /// frame.set_user_nibble(0, 7).unwrap();
+    /// encoder.set_frame(&frame);
+    /// assert_eq!(encoder.get_user_bits(), 7);
+    /// ```
+    pub fn set_frame(&mut self, frame: &LtcFrame) {
+        self.raw.set_frame(frame);
     }
 
     /// Query the length of the internal buffer. It is allocated to hold audio frames for exactly
@@ -269,11 +1100,11 @@ impl Encoder {
     /// # Example
     ///
     /// ```
-    /// let encoder = x42ltc::Encoder::new(48_000, 25.0).unwrap();
+    /// let encoder = x42ltc::Encoder::new(48_000, x42ltc::FrameRate::Fps25).unwrap();
     /// assert_eq!(encoder.get_buffer_size(), (1 + 48_000 / 25) as usize);
     /// ```
     pub fn get_buffer_size(&self) -> usize {
-        unsafe { ffi::ltc_encoder_get_buffersize(self.pointer) }
+        self.raw.buffer_size()
     }
 
     /// Get the 32 bit unsigned integer from the user data bits of the current frame. The data
@@ -282,88 +1113,121 @@ impl Encoder {
     /// # Example
     ///
     /// ```
-    /// let mut encoder = x42ltc::Encoder::new(48_000, 25.0).unwrap();
+    /// let mut encoder = x42ltc::Encoder::new(48_000, x42ltc::FrameRate::Fps25).unwrap();
     /// encoder.set_user_bits(12345);
     /// assert_eq!(encoder.get_user_bits(), 12345);
     /// ```
     pub fn get_user_bits(&self) -> u32 {
-        let mut frame = self.get_frame();
-        unsafe {
-            // We can unwrap here, since user bits is actually u32 in libltc_sys
-            // on MacOS / Linux c_ulong is 32 bit and 64bit long depending on CPU, casting here to truncate
-            ffi::ltc_frame_get_user_bits(&mut frame.frame) as u32
-        }
+        self.get_frame().user_bits()
     }
 
-    /// Move the encoder to the next timecode frame.
+    /// Move the encoder to the next timecode frame, or the next frame pair above 30 fps, see
+    /// [`FrameRate::ltc_frame_rate()`].
     pub fn increase_timecode(&mut self) {
-        unsafe {
-            ffi::ltc_encoder_inc_timecode(self.pointer);
-        }
+        let mut frame = self.get_frame();
+        frame.increment(self.frame_rate, self.flags);
+        self.set_frame(&frame);
     }
 
-    // Gets the current timecode from encoder
-    pub fn get_timecode(&mut self) -> x42ltc_sys::SMPTETimecode {
-        let mut tc = x42ltc_sys::SMPTETimecode::default();
-        unsafe {
-            ffi::ltc_encoder_get_timecode(self.pointer, &mut tc as *mut x42ltc_sys::SMPTETimecode);
-        }
-
-        tc
+    /// Get the current timecode of the encoder.
+    ///
+    /// Returns `Error::ValueOutOfRange` if the current frame does not hold a valid time of day.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let mut encoder = x42ltc::Encoder::new(48_000, x42ltc::FrameRate::Fps25).unwrap();
+    /// encoder.set_timecode("10:00:00:00".parse().unwrap());
+    /// encoder.increase_timecode();
+    /// assert_eq!(encoder.get_timecode().unwrap().to_string(), "10:00:00:01");
+    /// ```
+    pub fn get_timecode(&self) -> Result<Timecode, Error> {
+        self.get_frame()
+            .timecode_at(self.frame_rate, self.flags.contains(BgFlags::USE_DATE))
     }
 
-    // Gets the current timecode from encoder
-    pub fn set_timecode(&mut self, mut tc: x42ltc_sys::SMPTETimecode) {
-        unsafe {
-            ffi::ltc_encoder_set_timecode(self.pointer, &mut tc as *mut x42ltc_sys::SMPTETimecode);
-        }
+    /// Set the timecode of the current frame.  The next call to
+    /// [`.encode_frame()`](#method.encode_frame) will encode this timecode.
+    ///
+    /// Above 30 fps, LTC frames are sent as frame pairs, so an odd frame number is rounded down
+    /// to the first frame of its pair.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let mut encoder = x42ltc::Encoder::new(48_000, x42ltc::FrameRate::Fps50).unwrap();
+    /// encoder.set_timecode("10:00:00:45".parse().unwrap());
+    /// assert_eq!(encoder.get_frame().frame_tens(), 2);
+    /// encoder.increase_timecode();
+    /// assert_eq!(encoder.get_timecode().unwrap().to_string(), "10:00:00:46");
+    /// ```
+    pub fn set_timecode(&mut self, tc: Timecode) {
+        let mut frame = self.get_frame();
+        frame.set_timecode(tc, self.frame_rate, self.flags);
+        self.set_frame(&frame);
     }
 
     /// Change the encoder's settings without reallocating any library internal data structure
-    /// (realtime safe). Changing the `fps` and/or `sample_rate` implies a buffer flush, and a
-    /// biphase state reset.
+    /// (realtime safe). Changing the `frame_rate` and/or `sample_rate` implies a buffer flush, and
+    /// a biphase state reset.
     ///
     /// This call will fail if the internal buffer is too small to hold one full LTC frame. Use
     /// [`.set_buffer_size()`](#method.set_buffer_size) to prepare an internal buffer large enough
-    /// to accomodate all `sample_rate` and `fps` combinations that you would like to reinitialize
-    /// to.
+    /// to accomodate all `sample_rate` and `frame_rate` combinations that you would like to
+    /// reinitialize to.
     ///
     /// The LTC frame payload data is not modified by this call, however, the flag bits of the LTC
-    /// frame are updated: If `fps` equals to `29.97` or `30000.0/1001.0` the `Frame`'s `dfbit` bit
-    /// is set to `1` to indicate drop-frame timecode.
+    /// frame are updated: The `dfbit` is set if `frame_rate` is a drop-frame rate, and cleared
+    /// otherwise.
     ///
     /// # Example
     ///
     /// ```
-    /// let mut encoder = x42ltc::Encoder::new(48_000, 25.0).unwrap();
-    /// let result = encoder.reinitialize(44_100, 25.0);
+    /// let mut encoder = x42ltc::Encoder::new(48_000, x42ltc::FrameRate::Fps25).unwrap();
+    /// let result = encoder.reinitialize(44_100, x42ltc::FrameRate::Fps24);
     /// assert!(result.is_ok());
     /// ```
-    pub fn reinitialize(&mut self, sample_rate: u32, fps: f64) -> Result<(), Error> {
-        let rv = unsafe {
-            ffi::ltc_encoder_reinit(
-                self.pointer,
-                f64::from(sample_rate),
-                fps, // Position of binary group flags is only different for 25 fps
-                if (fps - 25.0).abs() < f64::EPSILON {
-                    ffi::LTC_TV_STANDARD_LTC_TV_625_50
-                } else {
-                    ffi::LTC_TV_STANDARD_LTC_TV_525_60
-                },
-                ffi::LTC_BG_FLAGS_LTC_BGF_DONT_TOUCH as i32,
-            )
-        };
-        match rv {
-            0 => Ok(()),
-            _ => Err(Error::ReinitializationFailed),
+    pub fn reinitialize(&mut self, sample_rate: u32, frame_rate: FrameRate) -> Result<(), Error> {
+        self.reconfigure(EncoderConfig {
+            sample_rate,
+            frame_rate,
+            flags: self.flags,
+        })
+    }
+
+    /// Change all of the encoder's settings, including its [`BgFlags`].  Otherwise identical to
+    /// [`.reinitialize()`](#method.reinitialize).
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use x42ltc::{BgFlags, Encoder, FrameRate};
+    ///
+    /// let mut encoder = Encoder::new(48_000, FrameRate::Fps25).unwrap();
+    /// let config = encoder.config().flags(BgFlags::USE_DATE | BgFlags::TC_CLOCK);
+    /// assert!(encoder.reconfigure(config).is_ok());
+    /// ```
+    pub fn reconfigure(&mut self, config: EncoderConfig) -> Result<(), Error> {
+        let ltc_frame_rate = config.frame_rate.ltc_frame_rate();
+        if !self
+            .raw
+            .reinitialize(config.sample_rate, ltc_frame_rate, config.flags)
+        {
+            return Err(Error::ReinitializationFailed);
         }
+        self.sample_rate = config.sample_rate;
+        self.frame_rate = config.frame_rate;
+        self.flags = config.flags;
+        self.modulator
+            .reinitialize(config.sample_rate, ltc_frame_rate);
+        self.update_drop_frame_bit();
+        Ok(())
     }
 
     /// Reset encoder state. Flushes buffer and resets biphase state.
     pub fn reset(&mut self) {
-        unsafe {
-            ffi::ltc_encoder_reset(self.pointer);
-        }
+        self.raw.reset();
+        self.modulator.reset();
     }
 
     /// Configure a custom size for the internal buffer.
@@ -371,7 +1235,7 @@ impl Encoder {
     /// This is needed if you are planning to call [`.reinitialize()`](#method.reinitialize) or if
     /// you want to keep more than one LTC frame's worth of data in the library's internal buffer.
     ///
-    /// The buffer size is `(1 + sample_rate / fps) bytes. Resizing the internal buffer will flush
+    /// The buffer size is `(1 + sample_rate / fps)` bytes. Resizing the internal buffer will flush
     /// all existing data in it, alike [`.flush_buffer()`](#method.flush_buffer).
     ///
     /// # Caution
@@ -382,16 +1246,20 @@ impl Encoder {
     /// # Example
     ///
     /// ```
-    /// let mut encoder = x42ltc::Encoder::new(48_000, 25.0).unwrap();
-    /// let result = encoder.set_buffer_size(192_000, 25.0);
+    /// let mut encoder = x42ltc::Encoder::new(48_000, x42ltc::FrameRate::Fps25).unwrap();
+    /// let result = encoder.set_buffer_size(192_000, x42ltc::FrameRate::Fps25);
     /// assert!(result.is_ok());
     /// ```
-    pub fn set_buffer_size(&mut self, sample_rate: u32, fps: f64) -> Result<(), Error> {
-        let rv = unsafe { ffi::ltc_encoder_set_bufsize(self.pointer, f64::from(sample_rate), fps) };
-        match rv {
-            0 => Ok(()),
-            _ => Err(Error::AllocationFailed),
+    pub fn set_buffer_size(
+        &mut self,
+        sample_rate: u32,
+        frame_rate: FrameRate,
+    ) -> Result<(), Error> {
+        let fps = frame_rate.ltc_frame_rate().as_f64();
+        if !self.raw.set_buffer_size(f64::from(sample_rate), fps) {
+            return Err(Error::AllocationFailed);
         }
+        Ok(())
     }
 
     /// Set encoder signal rise time / signal filtering.
@@ -410,16 +1278,15 @@ impl Encoder {
     /// Generate a perfect square wave LTC signal:
     ///
     /// ```
-    /// let mut encoder = x42ltc::Encoder::new(48_000, 25.0).unwrap();
+    /// let mut encoder = x42ltc::Encoder::new(48_000, x42ltc::FrameRate::Fps25).unwrap();
     /// encoder.set_volume(0.0);  // so that logical one == 255u8
     /// encoder.set_filter(0.0);  // perfect square wave
     /// encoder.encode_frame();
     /// assert_eq!(encoder.get_buffer()[0], 255u8);  // First sample is always logical 1
     /// ```
     pub fn set_filter(&mut self, rise_time: f64) {
-        unsafe {
-            ffi::ltc_encoder_set_filter(self.pointer, rise_time);
-        }
+        self.raw.set_filter(rise_time);
+        self.modulator.set_filter(rise_time);
     }
 
     /// Set the user bits of the current frame to the given data. The data is written LSB first into
@@ -428,16 +1295,14 @@ impl Encoder {
     /// # Example
     ///
     /// ```
-    /// let mut encoder = x42ltc::Encoder::new(48_000, 25.0).unwrap();
+    /// let mut encoder = x42ltc::Encoder::new(48_000, x42ltc::FrameRate::Fps25).unwrap();
     /// encoder.set_user_bits(98765);
     /// assert_eq!(encoder.get_user_bits(), 98765);
     /// ```
     pub fn set_user_bits(&mut self, user_bits: u32) {
-        unsafe {
-            // conversion needed on MacOS Arm where this is a u64 vs a u32
-            #[allow(clippy::useless_conversion)]
-            ffi::ltc_encoder_set_user_bits(self.pointer, user_bits.into());
-        }
+        let mut frame = self.get_frame();
+        frame.set_user_bits(user_bits);
+        self.set_frame(&frame);
     }
 
     /// Set the volume of the generated LTC signal.
@@ -446,34 +1311,94 @@ impl Encoder {
     /// default, libltc creates a -3dBFS LTC signal.
     ///
     /// Since libltc generates 8 bit audio data, the minimum dBFS is about -42dB which corresponds
-    /// to 1 bit.
+    /// to 1 bit.  Lower volumes are clamped to 1 bit in the 8 bit output, but are honored by
+    /// [`.encode_frame_f32()`](#method.encode_frame_f32),
+    /// [`.encode_frame_i16()`](#method.encode_frame_i16) and
+    /// [`.encode_frame_i24()`](#method.encode_frame_i24).
     ///
     /// 0dB corresponds to a signal range of 127 1..255 with 128 at the center.
     ///
     /// # Return value
     ///
-    /// Returns `Error::ValueOutOfRange` if `volume_in_dbfs` is > `0.0`.
+    /// Returns `Error::ValueOutOfRange` if `volume_in_dbfs` is > `0.0` or NaN.
     pub fn set_volume(&mut self, volume_in_dbfs: f64) -> Result<(), Error> {
-        let rv = unsafe { ffi::ltc_encoder_set_volume(self.pointer, volume_in_dbfs) };
-        match rv {
-            0 => Ok(()),
-            _ => Err(Error::ValueOutOfRange),
+        if volume_in_dbfs.is_nan() || volume_in_dbfs > 0.0 {
+            return Err(Error::ValueOutOfRange);
         }
+        if !self.raw.set_volume(volume_in_dbfs) {
+            // Too quiet for 8 bit, use the lowest possible level instead.
+            self.raw.set_volume(MIN_VOLUME_U8);
+        }
+        self.modulator.set_volume(volume_in_dbfs);
+        Ok(())
     }
 }
 
-impl Drop for Encoder {
-    fn drop(&mut self) {
-        unsafe {
-            ffi::ltc_encoder_free(self.pointer);
-        }
+/// Direction of timecode playback.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Direction {
+    Forward,
+    Reverse,
+}
+
+/// Endless iterator over encoded LTC frames, created by [`Encoder::generate()`].
+pub struct Generator<'a> {
+    encoder: &'a mut Encoder,
+    direction: Direction,
+}
+
+impl Generator<'_> {
+    /// The direction in which the timecode is stepped.
+    pub fn direction(&self) -> Direction {
+        self.direction
+    }
+
+    /// Change the direction in which the timecode is stepped, starting with the next frame.
+    pub fn set_direction(&mut self, direction: Direction) {
+        self.direction = direction;
     }
 }
 
-unsafe impl Send for Encoder {}
+impl Iterator for Generator<'_> {
+    type Item = Vec<u8>;
 
-struct Frame {
-    frame: ffi::LTCFrame,
+    fn next(&mut self) -> Option<Vec<u8>> {
+        let audio = match self.direction {
+            Direction::Forward => {
+                self.encoder.encode_frame();
+                let audio = self.encoder.get_buffer().to_vec();
+                self.encoder.increase_timecode();
+                audio
+            }
+            Direction::Reverse => {
+                self.encoder.encode_reversed_frame();
+                let audio = self.encoder.get_buffer().to_vec();
+                self.encoder.decrease_timecode();
+                audio
+            }
+        };
+        Some(audio)
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod test_util {
+    use super::*;
+
+    /// The audio of `frames` LTC frames at `sample_rate` and `frame_rate`, one buffer per frame,
+    /// starting at `start` and counting in `direction`.  Writing a frame at a time keeps the
+    /// queue of a decoder from overflowing.
+    pub(crate) fn encode_ltc(
+        sample_rate: u32,
+        frame_rate: FrameRate,
+        start: &str,
+        frames: usize,
+        direction: Direction,
+    ) -> Vec<Vec<u8>> {
+        let mut encoder = Encoder::new(sample_rate, frame_rate).unwrap();
+        encoder.set_timecode(start.parse().unwrap());
+        encoder.generate(direction).take(frames).collect()
+    }
 }
 
 #[cfg(test)]
@@ -481,45 +1406,214 @@ mod tests {
     use super::*;
     #[test]
     fn encoder_out_of_range_volume_errors() {
-        let mut encoder = Encoder::new(48_000, 25.0).unwrap();
+        let mut encoder = Encoder::new(48_000, FrameRate::Fps25).unwrap();
         assert!(encoder.set_volume(1.0).is_err());
     }
 
     #[test]
     fn encoder_reinitialization_fails_if_internal_buffer_is_too_small() {
-        let mut encoder = Encoder::new(48_000, 25.0).unwrap();
-        assert!(encoder.reinitialize(192_000, 25.0).is_err());
+        let mut encoder = Encoder::new(48_000, FrameRate::Fps25).unwrap();
+        assert!(encoder.reinitialize(192_000, FrameRate::Fps25).is_err());
     }
 
     #[test]
-    fn decoder_test() {
-        let mut encoder = Encoder::new(48_000, 25.0).unwrap();
+    fn encoder_sets_drop_frame_bit_from_frame_rate() {
+        let mut encoder = Encoder::new(48_000, FrameRate::Fps29_97).unwrap();
+        assert!(!encoder.get_timecode().unwrap().is_drop_frame());
+
+        encoder.reinitialize(48_000, FrameRate::Fps29_97Df).unwrap();
+        encoder.set_timecode(Timecode::new(0, 0, 59, 29).unwrap());
+        encoder.increase_timecode();
+        let tc = encoder.get_timecode().unwrap();
+        assert!(tc.is_drop_frame());
+        assert_eq!(tc.to_string(), "00:01:00;02");
+    }
 
-        let mut tc = SMPTETimecode::default();
-        tc.hours = 1;
-        tc.mins = 2;
-        tc.secs = 3;
-        tc.frame = 4;
+    #[test]
+    fn encoder_sends_frame_pairs_above_30_fps() {
+        let mut encoder = Encoder::new(48_000, FrameRate::Fps50).unwrap();
+        encoder.set_timecode(Timecode::new(0, 0, 0, 0).unwrap());
+        let mut decoder = Decoder::with_frame_rate(48_000, FrameRate::Fps25, 32).unwrap();
+
+        // One second at 50 fps is 25 frame pairs, plus one to complete the last frame.
+        let mut audio = Vec::new();
+        for _ in 0..26 {
+            encoder.encode_frame();
+            encoder.extend_into(&mut audio);
+            encoder.increase_timecode();
+        }
+        assert_eq!(audio.len(), 26 * 48_000 / 25);
 
-        encoder.set_timecode(tc);
+        let frames: Vec<_> = decoder.decode(&audio).collect();
+        assert_eq!(frames.len(), 25);
+        for (pair, frame) in frames.iter().enumerate() {
+            let tc = frame.frame().to_timecode_at(FrameRate::Fps50).unwrap();
+            assert_eq!((tc.secs(), tc.frame()), (0, 2 * pair as u8));
+        }
+        assert_eq!(encoder.get_timecode().unwrap().to_string(), "00:00:01:02");
+
+        encoder.set_timecode(Timecode::new(0, 0, 0, 45).unwrap());
+        for _ in 0..5 {
+            encoder.increase_timecode();
+        }
+        assert_eq!(encoder.get_timecode().unwrap().to_string(), "00:00:01:04");
+    }
+
+    #[test]
+    fn encoder_generates_reverse_ltc() {
+        let mut encoder = Encoder::new(48_000, FrameRate::Fps25).unwrap();
+        encoder.set_timecode(Timecode::new(1, 0, 0, 0).unwrap());
+        let mut decoder = Decoder::new(1920, 32).unwrap();
+
+        let mut frames = Vec::new();
+        for audio in encoder.generate(Direction::Reverse).take(20) {
+            frames.extend(decoder.decode(&audio));
+        }
+
+        assert!(frames.len() >= 18);
+        assert!(frames.iter().all(|frame| frame.is_reverse()));
+        assert!(frames.windows(2).all(|w| w[0].timecode() > w[1].timecode()));
+        // The last frame is only completed by the sync word of the next one.
+        assert_eq!(frames.last().unwrap().timecode().to_string(), "00:59:59:07");
+    }
+
+    #[test]
+    fn encoder_reverse_speed_is_decoded_as_reverse() {
+        let mut encoder = Encoder::new(48_000, FrameRate::Fps25).unwrap();
+        encoder.set_timecode(Timecode::new(0, 0, 10, 0).unwrap());
+        let mut decoder = Decoder::new(1920, 8).unwrap();
+
+        for _ in 0..5 {
+            encoder.encode_with_speed(-1.0).unwrap();
+            decoder.write(encoder.get_buffer());
+            encoder.decrease_timecode();
+        }
+
+        let frames: Vec<_> = decoder.frames().collect();
+        assert!(!frames.is_empty());
+        assert!(frames.iter().all(|frame| frame.is_reverse()));
+        assert!(frames.windows(2).all(|w| w[0].timecode() > w[1].timecode()));
+    }
+
+    #[test]
+    fn decoder_test() {
+        let mut encoder = Encoder::new(48_000, FrameRate::Fps25).unwrap();
+
+        encoder.set_timecode(Timecode::new(1, 2, 3, 4).unwrap());
         encoder.encode_frame();
-        let mut buffer = encoder.get_buffer().to_vec();
+        let buffer = encoder.get_buffer().to_vec();
 
         let mut decoder = Decoder::new(1920, 6).unwrap();
 
         decoder.queue_flush();
         assert_eq!(0, decoder.queue_length());
 
-        decoder.write(buffer.as_mut_slice());
-        decoder.write(buffer.as_mut_slice());
+        decoder.write(&buffer);
+        decoder.write(&buffer);
 
         assert_eq!(1, decoder.queue_length());
 
         let frame = decoder.read().unwrap();
 
-        assert_eq!(1, frame.hours);
-        assert_eq!(2, frame.mins);
-        assert_eq!(3, frame.secs);
-        assert_eq!(4, frame.frame);
+        // The first frame is completed by the first transition of the second one, offsets are
+        // only accurate to about one bit
+        assert!(frame.off_start().abs() <= 24);
+        assert!((frame.off_end() - 1919).abs() < 24);
+        assert!(!frame.is_reverse());
+
+        let tc = frame.timecode();
+        assert_eq!(1, tc.hours());
+        assert_eq!(2, tc.mins());
+        assert_eq!(3, tc.secs());
+        assert_eq!(4, tc.frame());
+    }
+
+    #[test]
+    fn decoder_accepts_all_sample_formats() {
+        let mut encoder = Encoder::new(48_000, FrameRate::Fps25).unwrap();
+        encoder.set_timecode(Timecode::new(1, 2, 3, 4).unwrap());
+        encoder.encode_frame();
+        let buffer = encoder.get_buffer().to_vec();
+
+        let as_i16: Vec<i16> = buffer.iter().map(|&s| (i16::from(s) - 128) << 8).collect();
+        let as_u16: Vec<u16> = buffer.iter().map(|&s| u16::from(s) << 8).collect();
+        let as_f64: Vec<f64> = buffer
+            .iter()
+            .map(|&s| (f64::from(s) - 128.0) / 127.0)
+            .collect();
+
+        let mut decoder = Decoder::new(1920, 6).unwrap();
+        for _ in 0..2 {
+            decoder.write_i16(&as_i16);
+            decoder.write_u16(&as_u16);
+            decoder.write_f64(&as_f64);
+        }
+        assert_eq!(5, decoder.queue_length());
+        while let Some(frame) = decoder.read() {
+            assert_eq!(frame.timecode().to_string(), "01:02:03:04");
+        }
+    }
+
+    #[test]
+    fn encoder_float_output_decodes_below_8_bit_range() {
+        let mut encoder = Encoder::new(48_000, FrameRate::Fps25).unwrap();
+        encoder.set_timecode(Timecode::new(10, 0, 0, 0).unwrap());
+        encoder.set_volume(-50.0).unwrap();
+
+        let mut audio = Vec::new();
+        let mut buffer = vec![0.0f32; encoder.get_buffer_size()];
+        for _ in 0..3 {
+            let len = encoder.encode_frame_f32(&mut buffer).unwrap();
+            audio.extend_from_slice(&buffer[..len]);
+            encoder.increase_timecode();
+        }
+        assert_eq!(audio.len(), 3 * 1920);
+        assert!(encoder.encode_frame_f32(&mut buffer[..1000]).is_err());
+
+        // Scale up, libltc's decoder works on 8 bit samples internally.
+        let loud: Vec<f32> = audio.iter().map(|s| s * 100.0).collect();
+        let mut decoder = Decoder::new(1920, 4).unwrap();
+        let frames: Vec<_> = decoder.decode(&loud).collect();
+        // The last frame is only completed by the first transition of the next one.
+        assert_eq!(frames.len(), 2);
+        assert_eq!(frames[0].timecode().to_string(), "10:00:00:00");
+    }
+
+    #[test]
+    fn write_interleaved_matches_mono_decoding() {
+        let mut encoder = Encoder::new(44_100, FrameRate::Fps30).unwrap();
+        let mut mono = Vec::new();
+        for _ in 0..20 {
+            encoder.encode_frame();
+            mono.extend(
+                encoder
+                    .get_buffer()
+                    .iter()
+                    .map(|&s| (i16::from(s) - 128) << 8),
+            );
+            encoder.increase_timecode();
+        }
+        let stereo: Vec<i16> = mono.iter().flat_map(|&s| [s / 2, s]).collect();
+
+        let mut decoder = Decoder::new(1470, 32).unwrap();
+        let expected: Vec<_> = decoder.decode(&mono).collect();
+        assert_eq!(expected.len(), 19);
+
+        let mut decoder = Decoder::new(1470, 32).unwrap();
+        decoder.write_interleaved(&stereo, 2, 1);
+        assert_eq!(decoder.position(), mono.len() as i64);
+        assert_eq!(decoder.frames().collect::<Vec<_>>(), expected);
+
+        // A trailing incomplete sample frame is ignored.
+        let mut decoder = Decoder::new(1470, 32).unwrap();
+        decoder.write_interleaved(&stereo[..stereo.len() - 1], 2, 1);
+        assert_eq!(decoder.position(), mono.len() as i64 - 1);
+    }
+
+    #[test]
+    #[should_panic]
+    fn write_interleaved_panics_on_invalid_channel() {
+        let mut decoder = Decoder::new(1920, 32).unwrap();
+        decoder.write_interleaved(&[0.0_f32; 6], 2, 2);
     }
 }
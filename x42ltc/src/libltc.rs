@@ -0,0 +1,245 @@
+// x42ltc: src/libltc.rs
+//
+// Copyright 2019-2020 Johannes Maibaum <jmaibaum@gmail.com>
+//
+// This file is free software; you can redistribute it and/or modify it
+// under the terms of the GNU Lesser General Public License as
+// published by the Free Software Foundation; either version 3 of the
+// License, or (at your option) any later version.
+//
+// This file is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public
+// License along with this program.  If not, see <http://www.gnu.org/licenses/>.
+//
+// SPDX-License-Identifier: LGPL-3.0-or-later
+use x42ltc_sys::{self as ffi, LTCFrameExt};
+
+use crate::{BgFlags, DecodedFrame, Error, FrameRate, LtcFrame};
+
+/// The libltc encoder, used by [`Encoder`](crate::Encoder) unless the `pure-rust` feature is
+/// enabled.
+pub(crate) struct RawEncoder {
+    pointer: *mut ffi::LTCEncoder,
+}
+
+impl RawEncoder {
+    pub(crate) fn new(sample_rate: u32, frame_rate: FrameRate, flags: BgFlags) -> Option<Self> {
+        let pointer = unsafe {
+            ffi::ltc_encoder_create(
+                f64::from(sample_rate),
+                frame_rate.as_f64(),
+                frame_rate.tv_standard(),
+                flags.bits() as i32,
+            )
+        };
+        (!pointer.is_null()).then_some(RawEncoder { pointer })
+    }
+
+    pub(crate) fn reinitialize(
+        &mut self,
+        sample_rate: u32,
+        frame_rate: FrameRate,
+        flags: BgFlags,
+    ) -> bool {
+        let rv = unsafe {
+            ffi::ltc_encoder_reinit(
+                self.pointer,
+                f64::from(sample_rate),
+                frame_rate.as_f64(),
+                frame_rate.tv_standard(),
+                flags.bits() as i32,
+            )
+        };
+        rv == 0
+    }
+
+    pub(crate) fn set_buffer_size(&mut self, sample_rate: f64, fps: f64) -> bool {
+        unsafe { ffi::ltc_encoder_set_bufsize(self.pointer, sample_rate, fps) == 0 }
+    }
+
+    pub(crate) fn buffer_size(&self) -> usize {
+        unsafe { ffi::ltc_encoder_get_buffersize(self.pointer) }
+    }
+
+    pub(crate) fn reset(&mut self) {
+        unsafe {
+            ffi::ltc_encoder_reset(self.pointer);
+        }
+    }
+
+    pub(crate) fn set_filter(&mut self, rise_time: f64) {
+        unsafe {
+            ffi::ltc_encoder_set_filter(self.pointer, rise_time);
+        }
+    }
+
+    pub(crate) fn set_volume(&mut self, volume_in_dbfs: f64) -> bool {
+        unsafe { ffi::ltc_encoder_set_volume(self.pointer, volume_in_dbfs) == 0 }
+    }
+
+    pub(crate) fn frame(&self) -> LtcFrame {
+        let mut frame = ffi::LTCFrame::default();
+        unsafe {
+            ffi::ltc_encoder_get_frame(self.pointer, &mut frame);
+        }
+        LtcFrame::from_ffi(&frame)
+    }
+
+    pub(crate) fn set_frame(&mut self, frame: &LtcFrame) {
+        let mut frame = frame.to_ffi();
+        unsafe {
+            ffi::ltc_encoder_set_frame(self.pointer, &mut frame);
+        }
+    }
+
+    pub(crate) fn encode_byte(&mut self, byte: u8, speed: f64) -> bool {
+        unsafe { ffi::ltc_encoder_encode_byte(self.pointer, i32::from(byte), speed) == 0 }
+    }
+
+    pub(crate) fn encode_frame(&mut self) {
+        unsafe {
+            ffi::ltc_encoder_encode_frame(self.pointer);
+        }
+    }
+
+    pub(crate) fn encode_reversed_frame(&mut self) {
+        unsafe {
+            ffi::ltc_encoder_encode_reversed_frame(self.pointer);
+        }
+    }
+
+    pub(crate) fn end_encode(&mut self) -> bool {
+        unsafe { ffi::ltc_encoder_end_encode(self.pointer) == 0 }
+    }
+
+    pub(crate) fn buffered_len(&self) -> usize {
+        let mut buf_len = 0;
+        unsafe {
+            ffi::ltc_encoder_get_bufptr(self.pointer, &mut buf_len, 0);
+        }
+        buf_len as usize
+    }
+
+    /// The accumulated samples.  The internal buffer is flushed, but the returned slice stays
+    /// valid until the next call taking `&mut self`.
+    pub(crate) fn take_buffer(&self) -> &[u8] {
+        let mut buf_len = 0;
+        let buf_ptr = unsafe { ffi::ltc_encoder_get_bufptr(self.pointer, &mut buf_len, 1) };
+        unsafe { std::slice::from_raw_parts(buf_ptr, buf_len as usize) }
+    }
+
+    /// Copy the accumulated samples to `buffer`, which must be large enough, and flush.
+    pub(crate) fn copy_buffer(&mut self, buffer: &mut [u8]) -> usize {
+        unsafe { ffi::ltc_encoder_copy_buffer(self.pointer, buffer.as_mut_ptr()) as usize }
+    }
+
+    pub(crate) fn flush_buffer(&mut self) {
+        unsafe {
+            ffi::ltc_encoder_buffer_flush(self.pointer);
+        }
+    }
+}
+
+impl Drop for RawEncoder {
+    fn drop(&mut self) {
+        unsafe {
+            ffi::ltc_encoder_free(self.pointer);
+        }
+    }
+}
+
+unsafe impl Send for RawEncoder {}
+
+/// The libltc decoder, used by [`Decoder`](crate::Decoder) unless the `pure-rust` feature is
+/// enabled.
+pub(crate) struct RawDecoder {
+    pointer: *mut ffi::LTCDecoder,
+}
+
+impl RawDecoder {
+    pub(crate) fn new(audio_frames_per_video_frame: i32, queue_size: i32) -> Option<Self> {
+        let pointer = unsafe { ffi::ltc_decoder_create(audio_frames_per_video_frame, queue_size) };
+        (!pointer.is_null()).then_some(RawDecoder { pointer })
+    }
+
+    pub(crate) fn queue_flush(&mut self) {
+        unsafe {
+            ffi::ltc_decoder_queue_flush(self.pointer);
+        }
+    }
+
+    pub(crate) fn queue_length(&self) -> i32 {
+        unsafe { ffi::ltc_decoder_queue_length(self.pointer) }
+    }
+
+    // libltc does not modify the buffers, the pointers are only non-const for historic reasons.
+
+    pub(crate) fn write(&mut self, data: &[u8], posinfo: i64) {
+        unsafe {
+            ffi::ltc_decoder_write(self.pointer, data.as_ptr() as *mut _, data.len(), posinfo);
+        }
+    }
+
+    pub(crate) fn write_f32(&mut self, data: &[f32], posinfo: i64) {
+        unsafe {
+            ffi::ltc_decoder_write_float(
+                self.pointer,
+                data.as_ptr() as *mut _,
+                data.len(),
+                posinfo,
+            );
+        }
+    }
+
+    pub(crate) fn write_f64(&mut self, data: &[f64], posinfo: i64) {
+        unsafe {
+            ffi::ltc_decoder_write_double(
+                self.pointer,
+                data.as_ptr() as *mut _,
+                data.len(),
+                posinfo,
+            );
+        }
+    }
+
+    pub(crate) fn write_i16(&mut self, data: &[i16], posinfo: i64) {
+        unsafe {
+            ffi::ltc_decoder_write_s16(self.pointer, data.as_ptr() as *mut _, data.len(), posinfo);
+        }
+    }
+
+    pub(crate) fn write_u16(&mut self, data: &[u16], posinfo: i64) {
+        unsafe {
+            ffi::ltc_decoder_write_u16(self.pointer, data.as_ptr() as *mut _, data.len(), posinfo);
+        }
+    }
+
+    /// The next queued frame, or an error if its time of day is not a valid timecode.
+    pub(crate) fn read(&mut self) -> Option<Result<DecodedFrame, Error>> {
+        let mut frame = LTCFrameExt::default();
+        if unsafe { ffi::ltc_decoder_read(self.pointer, &mut frame) } == 0 {
+            return None;
+        }
+        Some(DecodedFrame::new(
+            LtcFrame::from_ffi(&frame.ltc),
+            frame.off_start,
+            frame.off_end,
+            frame.reverse != 0,
+            frame.sample_min,
+            frame.sample_max,
+            frame.volume,
+        ))
+    }
+}
+
+impl Drop for RawDecoder {
+    fn drop(&mut self) {
+        unsafe {
+            ffi::ltc_decoder_free(self.pointer);
+        }
+    }
+}
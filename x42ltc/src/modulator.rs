@@ -0,0 +1,170 @@
+// x42ltc: src/modulator.rs
+//
+// Copyright 2019-2020 Johannes Maibaum <jmaibaum@gmail.com>
+//
+// This file is free software; you can redistribute it and/or modify it
+// under the terms of the GNU Lesser General Public License as
+// published by the Free Software Foundation; either version 3 of the
+// License, or (at your option) any later version.
+//
+// This file is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public
+// License along with this program.  If not, see <http://www.gnu.org/licenses/>.
+//
+// SPDX-License-Identifier: LGPL-3.0-or-later
+#[cfg(not(any(feature = "std", test)))]
+use crate::float::Float;
+use crate::FrameRate;
+
+/// The signal rise time in µs recommended by SMPTE 12M, and used by libltc by default.
+pub(crate) const DEFAULT_RISE_TIME: f64 = 40.0;
+
+/// The signal level in dBFS used by libltc by default.
+pub(crate) const DEFAULT_VOLUME: f64 = -3.0;
+
+/// Biphase-mark modulation of LTC frames into floating point samples.
+///
+/// This follows the algorithm of libltc's encoder (see `encoder.c`), but computes the signal at
+/// full precision instead of 8 bit unsigned samples.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Modulator {
+    sample_rate: f64,
+    samples_per_clock: f64,
+    filter_const: f64,
+    amplitude: f64,
+    state: bool,
+    sample_remainder: f64,
+}
+
+impl Modulator {
+    pub(crate) fn new(sample_rate: u32, frame_rate: FrameRate) -> Modulator {
+        let mut modulator = Modulator {
+            sample_rate: 0.0,
+            samples_per_clock: 0.0,
+            filter_const: 0.0,
+            amplitude: 0.0,
+            state: false,
+            sample_remainder: 0.5,
+        };
+        modulator.reinitialize(sample_rate, frame_rate);
+        modulator.set_volume(DEFAULT_VOLUME);
+        modulator
+    }
+
+    /// Like `ltc_encoder_reinit()`: resets the biphase state and the filter.
+    pub(crate) fn reinitialize(&mut self, sample_rate: u32, frame_rate: FrameRate) {
+        self.sample_rate = f64::from(sample_rate);
+        self.samples_per_clock = self.sample_rate / (frame_rate.as_f64() * 80.0);
+        self.set_filter(DEFAULT_RISE_TIME);
+        self.reset();
+    }
+
+    pub(crate) fn reset(&mut self) {
+        self.state = false;
+        self.sample_remainder = 0.5;
+    }
+
+    /// Set the 10% to 90% rise time of the signal in µs, `0.0` giving a square wave.
+    pub(crate) fn set_filter(&mut self, rise_time: f64) {
+        self.filter_const = if rise_time <= 0.0 {
+            0.0
+        } else {
+            1.0 - (-1.0 / (self.sample_rate * rise_time / 2_000_000.0 / core::f64::consts::E)).exp()
+        };
+    }
+
+    pub(crate) fn set_volume(&mut self, volume_in_dbfs: f64) {
+        self.amplitude = 10f64.powf(volume_in_dbfs / 20.0);
+    }
+
+    /// The number of samples the next call to [`.modulate()`](#method.modulate) with the same
+    /// `bytes` and `reverse` produces.
+    pub(crate) fn samples_needed(&self, bytes: &[u8], reverse: bool) -> usize {
+        let mut len = 0;
+        let mut modulator = *self;
+        modulator.cells(bytes, reverse, |n, _| len += n);
+        len
+    }
+
+    /// Modulate `bytes` LSB first at speed 1.0, or MSB first starting with the last byte at speed
+    /// -1.0 if `reverse` is set, passing each sample in -1.0..=1.0 to `write`.
+    pub(crate) fn modulate(&mut self, bytes: &[u8], reverse: bool, mut write: impl FnMut(f64)) {
+        let filter_const = self.filter_const;
+        let amplitude = self.amplitude;
+        self.cells(bytes, reverse, |n, state| {
+            let target = if state { amplitude } else { -amplitude };
+            for i in 0..n {
+                // libltc fills each half cell symmetrically, rising from the center towards the
+                // target level in the first half.
+                let distance = i.min(n - 1 - i);
+                if filter_const > 0.0 {
+                    write(target * (1.0 - (1.0 - filter_const).powi(distance as i32 + 1)));
+                } else {
+                    write(target);
+                }
+            }
+        });
+    }
+
+    /// Split the biphase-mark code of `bytes` into runs of constant level, passing the number of
+    /// samples and the level of each run to `run`.
+    fn cells(&mut self, bytes: &[u8], reverse: bool, mut run: impl FnMut(usize, bool)) {
+        let full = self.samples_per_clock;
+        let half = full / 2.0;
+        for i in 0..bytes.len() {
+            let byte = if reverse {
+                bytes[bytes.len() - 1 - i]
+            } else {
+                bytes[i]
+            };
+            for bit in 0..8 {
+                // Like libltc, reverse frames are encoded MSB first.
+                let mask = if reverse { 0x80 >> bit } else { 1 << bit };
+                let cells = if byte & mask == 0 { 1 } else { 2 };
+                let length = if cells == 1 { full } else { half };
+                for _ in 0..cells {
+                    let n = (length + self.sample_remainder) as usize;
+                    self.sample_remainder += length - n as f64;
+                    self.state = !self.state;
+                    run(n, self.state);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn modulator_output_has_expected_length_and_level() {
+        let mut modulator = Modulator::new(48_000, FrameRate::Fps25);
+        modulator.set_volume(-6.0);
+        modulator.set_filter(0.0);
+
+        let bytes = [0, 0, 0, 0, 0, 0, 0, 0, 0xFC, 0xBF];
+        assert_eq!(modulator.samples_needed(&bytes, false), 1920);
+
+        let mut samples = Vec::new();
+        modulator.modulate(&bytes, false, |sample| samples.push(sample));
+        assert_eq!(samples.len(), 1920);
+        let amplitude = 10f64.powf(-6.0 / 20.0);
+        assert!(samples.iter().all(|s| (s.abs() - amplitude).abs() < 1e-12));
+        // Every bit cell starts with a transition, zeros keep their level for the full cell.
+        assert!(samples[..24].iter().all(|&s| s > 0.0));
+        assert!(samples[24..48].iter().all(|&s| s < 0.0));
+
+        // Played backwards, the sync word comes first and ends with two ones.
+        let mut reversed = Vec::new();
+        modulator.reset();
+        modulator.modulate(&bytes, true, |sample| reversed.push(sample));
+        assert_eq!(reversed.len(), 1920);
+        assert!(reversed[..12].iter().all(|&s| s > 0.0));
+        assert!(reversed[12..24].iter().all(|&s| s < 0.0));
+    }
+}
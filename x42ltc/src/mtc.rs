@@ -0,0 +1,595 @@
+// x42ltc: src/mtc.rs
+//
+// Copyright 2019-2020 Johannes Maibaum <jmaibaum@gmail.com>
+//
+// This file is free software; you can redistribute it and/or modify it
+// under the terms of the GNU Lesser General Public License as
+// published by the Free Software Foundation; either version 3 of the
+// License, or (at your option) any later version.
+//
+// This file is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public
+// License along with this program.  If not, see <http://www.gnu.org/licenses/>.
+//
+// SPDX-License-Identifier: LGPL-3.0-or-later
+use alloc::vec::Vec;
+
+use crate::{DecodedFrame, Direction, Error, FrameRate, Timecode};
+
+/// Status byte of an MTC quarter frame message.
+const QUARTER_FRAME: u8 = 0xF1;
+const SYSEX_START: u8 = 0xF0;
+const SYSEX_END: u8 = 0xF7;
+
+/// The header of a full frame message, sent to all devices, up to the hours byte.
+const FULL_FRAME_HEADER: [u8; 5] = [SYSEX_START, 0x7F, 0x7F, 0x01, 0x01];
+const FULL_FRAME_LEN: usize = 10;
+
+/// The MTC rate code of `frame_rate`, `None` for rates MTC cannot carry.
+///
+/// 23.976 fps is sent as 24 fps and 29.97 fps non-drop-frame as 30 fps, as usual.
+pub(crate) fn rate_code(frame_rate: FrameRate) -> Option<u8> {
+    match frame_rate {
+        FrameRate::Fps23_976 | FrameRate::Fps24 => Some(0),
+        FrameRate::Fps25 => Some(1),
+        FrameRate::Fps29_97Df => Some(2),
+        FrameRate::Fps29_97 | FrameRate::Fps30 => Some(3),
+        _ => None,
+    }
+}
+
+pub(crate) fn frame_rate_from_code(code: u8) -> FrameRate {
+    match code & 3 {
+        0 => FrameRate::Fps24,
+        1 => FrameRate::Fps25,
+        2 => FrameRate::Fps29_97Df,
+        _ => FrameRate::Fps30,
+    }
+}
+
+/// The MTC rate code of `frame_rate`, checking that `timecode` is valid at that rate.
+fn validate(timecode: &Timecode, frame_rate: FrameRate) -> Result<u8, Error> {
+    let code = rate_code(frame_rate).ok_or(Error::ValueOutOfRange)?;
+    timecode.to_frame_number(frame_rate)?;
+    Ok(code)
+}
+
+/// The timecode `frames` frames from `timecode`, wrapping around at midnight.
+fn offset(timecode: &Timecode, frame_rate: FrameRate, frames: i64) -> Option<Timecode> {
+    let frame_number = timecode.to_frame_number(frame_rate).ok()?;
+    let frames_per_day = i64::from(frame_rate.frames_per_day());
+    let frame_number = (i64::from(frame_number) + frames).rem_euclid(frames_per_day);
+    Some(Timecode::from_frame_number(frame_number as u32, frame_rate))
+}
+
+/// A MIDI Time Code message.
+///
+/// # Example
+///
+/// ```
+/// use x42ltc::{FrameRate, MtcMessage, Timecode};
+///
+/// let tc: Timecode = "01:02:03;04".parse().unwrap();
+/// let full_frame = MtcMessage::full_frame(&tc, FrameRate::Fps29_97Df).unwrap();
+/// assert_eq!(
+///     full_frame.to_bytes(),
+///     [0xF0, 0x7F, 0x7F, 0x01, 0x01, 0x41, 0x02, 0x03, 0x04, 0xF7]
+/// );
+///
+/// let quarter_frames = MtcMessage::quarter_frames(&tc, FrameRate::Fps29_97Df).unwrap();
+/// assert_eq!(quarter_frames[0].to_bytes(), [0xF1, 0x04]);
+/// assert_eq!(quarter_frames[7].to_bytes(), [0xF1, 0x74]);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MtcMessage {
+    /// A quarter frame message carrying one nibble of the timecode, given by its data byte
+    /// (`0nnndddd`, the piece number `n` followed by the value `d`).
+    QuarterFrame(u8),
+    /// A full frame SysEx message, given by its hours byte including the rate code, minutes,
+    /// seconds and frames.
+    FullFrame([u8; 4]),
+}
+
+impl MtcMessage {
+    /// The eight quarter frame messages transmitting `timecode` at `frame_rate`, in the order of
+    /// their piece numbers.
+    ///
+    /// Returns `Error::ValueOutOfRange` if `timecode` is not valid at `frame_rate`, or MTC cannot
+    /// carry `frame_rate`, i.e. above 30 fps.
+    pub fn quarter_frames(
+        timecode: &Timecode,
+        frame_rate: FrameRate,
+    ) -> Result<[MtcMessage; 8], Error> {
+        let code = validate(timecode, frame_rate)?;
+        let values = [
+            timecode.frame() & 0x0F,
+            timecode.frame() >> 4,
+            timecode.secs() & 0x0F,
+            timecode.secs() >> 4,
+            timecode.mins() & 0x0F,
+            timecode.mins() >> 4,
+            timecode.hours() & 0x0F,
+            timecode.hours() >> 4 | code << 1,
+        ];
+        let mut messages = [MtcMessage::QuarterFrame(0); 8];
+        for (piece, (message, value)) in messages.iter_mut().zip(values).enumerate() {
+            *message = MtcMessage::QuarterFrame((piece as u8) << 4 | value);
+        }
+        Ok(messages)
+    }
+
+    /// The full frame message locating to `timecode` at `frame_rate`, addressed to all devices.
+    ///
+    /// Returns `Error::ValueOutOfRange` like [`quarter_frames()`](#method.quarter_frames).
+    pub fn full_frame(timecode: &Timecode, frame_rate: FrameRate) -> Result<MtcMessage, Error> {
+        let code = validate(timecode, frame_rate)?;
+        Ok(MtcMessage::FullFrame([
+            code << 5 | timecode.hours(),
+            timecode.mins(),
+            timecode.secs(),
+            timecode.frame(),
+        ]))
+    }
+
+    /// The MIDI bytes of this message.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        match self {
+            MtcMessage::QuarterFrame(data) => alloc::vec![QUARTER_FRAME, *data],
+            MtcMessage::FullFrame(time) => {
+                let mut bytes = FULL_FRAME_HEADER.to_vec();
+                bytes.extend_from_slice(time);
+                bytes.push(SYSEX_END);
+                bytes
+            }
+        }
+    }
+}
+
+/// Timecode received by an [`MtcParser`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MtcEvent {
+    /// Eight quarter frames were received in a row.
+    ///
+    /// Transmitting them takes two frames, so the timecode is compensated to be the one of the
+    /// frame starting with the next quarter frame: two frames after the transmitted one when
+    /// running forward, two frames before when running backwards.
+    QuarterFrames {
+        timecode: Timecode,
+        direction: Direction,
+    },
+    /// A full frame message, which locates to the given timecode.
+    FullFrame(Timecode),
+}
+
+/// Parses a MIDI byte stream for MTC messages.
+///
+/// Messages other than MTC are skipped, also when system real time messages are interleaved.
+/// The timecodes reported are counted at the rate given by the MTC rate code, which is
+/// [`FrameRate::Fps24`] for 23.976 fps and [`FrameRate::Fps30`] for 29.97 fps non-drop-frame.
+///
+/// # Example
+///
+/// ```
+/// use x42ltc::{Direction, FrameRate, MtcEvent, MtcMessage, MtcParser, Timecode};
+///
+/// let tc: Timecode = "10:00:00:00".parse().unwrap();
+/// let bytes: Vec<u8> = MtcMessage::quarter_frames(&tc, FrameRate::Fps25)
+///     .unwrap()
+///     .iter()
+///     .flat_map(|message| message.to_bytes())
+///     .collect();
+///
+/// let mut parser = MtcParser::new();
+/// let events: Vec<_> = bytes.iter().filter_map(|&byte| parser.push(byte)).collect();
+/// assert_eq!(
+///     events,
+///     [MtcEvent::QuarterFrames {
+///         timecode: "10:00:00:02".parse::<Timecode>().unwrap().with_frame_rate(FrameRate::Fps25),
+///         direction: Direction::Forward,
+///     }]
+/// );
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct MtcParser {
+    status: Option<u8>,
+    pieces: [u8; 8],
+    /// Bit `n` is set once piece `n` of the current sequence was received.
+    received: u8,
+    last_piece: Option<u8>,
+    direction: Option<Direction>,
+    sysex: [u8; FULL_FRAME_LEN],
+    sysex_len: usize,
+}
+
+impl MtcParser {
+    /// Create a parser waiting for the first status byte.
+    pub fn new() -> MtcParser {
+        MtcParser::default()
+    }
+
+    /// Parse the next byte of the stream, returns an event once a message completes one.
+    pub fn push(&mut self, byte: u8) -> Option<MtcEvent> {
+        match byte {
+            // System real time messages may appear anywhere, even within SysEx.
+            0xF8..=0xFF => None,
+            SYSEX_END => {
+                let complete = self.status == Some(SYSEX_START) && self.push_sysex(byte);
+                self.status = None;
+                complete.then(|| self.full_frame()).flatten()
+            }
+            0x80..=0xF7 => {
+                self.status = Some(byte);
+                self.sysex_len = 0;
+                if byte == SYSEX_START {
+                    self.push_sysex(byte);
+                }
+                None
+            }
+            _ => match self.status {
+                Some(QUARTER_FRAME) => {
+                    // System common messages have no running status.
+                    self.status = None;
+                    self.quarter_frame(byte)
+                }
+                Some(SYSEX_START) => {
+                    self.push_sysex(byte);
+                    None
+                }
+                _ => None,
+            },
+        }
+    }
+
+    /// Returns whether the byte still fit into a full frame message.
+    fn push_sysex(&mut self, byte: u8) -> bool {
+        if self.sysex_len == FULL_FRAME_LEN {
+            return false;
+        }
+        self.sysex[self.sysex_len] = byte;
+        self.sysex_len += 1;
+        true
+    }
+
+    fn full_frame(&self) -> Option<MtcEvent> {
+        let message = &self.sysex[..self.sysex_len];
+        // The device ID at index 2 is not checked.
+        if message.len() != FULL_FRAME_LEN || message[3..5] != FULL_FRAME_HEADER[3..] {
+            return None;
+        }
+        let frame_rate = frame_rate_from_code(message[5] >> 5);
+        let timecode = Timecode::new(message[5] & 0x1F, message[6], message[7], message[8])
+            .ok()?
+            .with_frame_rate(frame_rate);
+        timecode.to_frame_number(frame_rate).ok()?;
+        Some(MtcEvent::FullFrame(timecode))
+    }
+
+    fn quarter_frame(&mut self, data: u8) -> Option<MtcEvent> {
+        let piece = data >> 4;
+        let direction = match self.last_piece {
+            Some(last) if piece == (last + 1) % 8 => Some(Direction::Forward),
+            Some(last) if piece == (last + 7) % 8 => Some(Direction::Reverse),
+            _ => None,
+        };
+        if direction.is_none() || self.direction.is_some_and(|last| direction != Some(last)) {
+            // Start over after a dropout or a change of direction.
+            self.received = 0;
+        }
+        self.direction = direction;
+        self.last_piece = Some(piece);
+        self.pieces[usize::from(piece)] = data & 0x0F;
+        self.received |= 1 << piece;
+
+        let last_of_sequence = match direction? {
+            Direction::Forward => 7,
+            Direction::Reverse => 0,
+        };
+        if piece != last_of_sequence || self.received != 0xFF {
+            return None;
+        }
+        self.received = 0;
+
+        let p = &self.pieces;
+        let frame_rate = frame_rate_from_code(p[7] >> 1);
+        let timecode = Timecode::new(
+            p[6] | (p[7] & 0x01) << 4,
+            p[4] | (p[5] & 0x03) << 4,
+            p[2] | (p[3] & 0x03) << 4,
+            p[0] | (p[1] & 0x01) << 4,
+        )
+        .ok()?;
+        let (direction, frames) = match direction? {
+            Direction::Forward => (Direction::Forward, 2),
+            Direction::Reverse => (Direction::Reverse, -2),
+        };
+        Some(MtcEvent::QuarterFrames {
+            timecode: offset(&timecode, frame_rate, frames)?,
+            direction,
+        })
+    }
+}
+
+/// An MTC message scheduled at an absolute sample position.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TimedMtcMessage {
+    /// The sample position, in the time base of the [`DecodedFrame`] offsets.
+    pub offset: i64,
+    pub message: MtcMessage,
+}
+
+/// Converts frames from a [`Decoder`](crate::Decoder) to MTC, scheduled at the sample offsets
+/// of the LTC.
+///
+/// Each LTC frame is split into four quarter frame messages, spread evenly over the frame, so
+/// that the eight messages of a timecode take two LTC frames.  When running backwards, the
+/// pieces are sent in reverse order.  Whenever the LTC jumps, changes direction or drops out, a
+/// full frame message is sent at the start of the next frame and the quarter frames start over.
+///
+/// A frame is only decoded at its end, so in a live setting the messages are one frame late.
+///
+/// # Example
+///
+/// ```
+/// use x42ltc::{Decoder, Encoder, FrameRate, MtcBridge, MtcMessage};
+///
+/// let mut encoder = Encoder::new(48_000, FrameRate::Fps25).unwrap();
+/// let mut decoder = Decoder::new(1920, 32).unwrap();
+/// let mut bridge = MtcBridge::new(FrameRate::Fps25).unwrap();
+/// let mut messages = Vec::new();
+/// for _ in 0..4 {
+///     encoder.encode_frame();
+///     for frame in decoder.decode(encoder.get_buffer()) {
+///         messages.extend(bridge.process(&frame));
+///     }
+///     encoder.increase_timecode();
+/// }
+///
+/// // A full frame message, then four quarter frames per LTC frame, starting at the same offset.
+/// assert_eq!(messages.len(), 1 + 3 * 4);
+/// assert!(matches!(messages[0].message, MtcMessage::FullFrame(_)));
+/// assert_eq!(messages[0].offset, messages[1].offset);
+/// ```
+#[derive(Debug, Clone)]
+pub struct MtcBridge {
+    frame_rate: FrameRate,
+    /// The frame number and direction of the previous frame.
+    previous: Option<(u32, bool)>,
+    /// The timecode transmitted by the quarter frames, if only half of them were sent.
+    pending: Option<Timecode>,
+}
+
+impl MtcBridge {
+    /// Create a bridge for LTC at `frame_rate`.
+    ///
+    /// Returns `Error::ValueOutOfRange` if MTC cannot carry `frame_rate`.
+    pub fn new(frame_rate: FrameRate) -> Result<MtcBridge, Error> {
+        rate_code(frame_rate).ok_or(Error::ValueOutOfRange)?;
+        Ok(MtcBridge {
+            frame_rate,
+            previous: None,
+            pending: None,
+        })
+    }
+
+    /// The frame rate of the LTC.
+    pub fn frame_rate(&self) -> FrameRate {
+        self.frame_rate
+    }
+
+    /// Forget the previous frame, so that the next one starts with a full frame message.
+    pub fn reset(&mut self) {
+        self.previous = None;
+        self.pending = None;
+    }
+
+    /// The messages to send for `frame`, in order.  Frames which are not valid timecode at the
+    /// frame rate of the bridge produce no messages.
+    pub fn process(&mut self, frame: &DecodedFrame) -> Vec<TimedMtcMessage> {
+        let mut messages = Vec::new();
+        let timecode = frame.timecode().with_frame_rate(self.frame_rate);
+        let frame_number = match timecode.to_frame_number(self.frame_rate) {
+            Ok(frame_number) => frame_number,
+            Err(_) => {
+                self.reset();
+                return messages;
+            }
+        };
+        let reverse = frame.is_reverse();
+        let step: i64 = if reverse { -1 } else { 1 };
+
+        let frames_per_day = i64::from(self.frame_rate.frames_per_day());
+        let contiguous = self.previous.is_some_and(|(previous, previous_reverse)| {
+            let expected = (i64::from(previous) + step).rem_euclid(frames_per_day);
+            previous_reverse == reverse && i64::from(frame_number) == expected
+        });
+        if !contiguous {
+            self.pending = None;
+            if let Ok(message) = MtcMessage::full_frame(&timecode, self.frame_rate) {
+                messages.push(TimedMtcMessage {
+                    offset: frame.off_start(),
+                    message,
+                });
+            }
+        }
+        self.previous = Some((frame_number, reverse));
+
+        // The first half of the pieces is sent during the frame whose timecode they carry.
+        let (transmitted, first_half) = match self.pending.take() {
+            Some(transmitted) => (transmitted, false),
+            None => {
+                self.pending = Some(timecode);
+                (timecode, true)
+            }
+        };
+        let Ok(pieces) = MtcMessage::quarter_frames(&transmitted, self.frame_rate) else {
+            return messages;
+        };
+        let pieces = match (first_half, reverse) {
+            (true, false) => [pieces[0], pieces[1], pieces[2], pieces[3]],
+            (false, false) => [pieces[4], pieces[5], pieces[6], pieces[7]],
+            (true, true) => [pieces[7], pieces[6], pieces[5], pieces[4]],
+            (false, true) => [pieces[3], pieces[2], pieces[1], pieces[0]],
+        };
+        let length = frame.off_end() - frame.off_start() + 1;
+        for (quarter, message) in pieces.into_iter().enumerate() {
+            messages.push(TimedMtcMessage {
+                offset: frame.off_start() + quarter as i64 * length / 4,
+                message,
+            });
+        }
+        messages
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::encode_ltc;
+    use crate::{Decoder, Encoder};
+
+    fn bytes(messages: &[TimedMtcMessage]) -> Vec<u8> {
+        messages
+            .iter()
+            .flat_map(|timed| timed.message.to_bytes())
+            .collect()
+    }
+
+    /// The MTC messages for `ltc`, decoded at 48 kHz.
+    fn bridge(frame_rate: FrameRate, ltc: Vec<Vec<u8>>) -> Vec<TimedMtcMessage> {
+        let mut decoder = Decoder::with_frame_rate(48_000, frame_rate, 32).unwrap();
+        let mut bridge = MtcBridge::new(frame_rate).unwrap();
+        let mut messages = Vec::new();
+        for audio in ltc {
+            for frame in decoder.decode(&audio) {
+                messages.extend(bridge.process(&frame));
+            }
+        }
+        messages
+    }
+
+    #[test]
+    fn mtc_rejects_unsupported_rates() {
+        let tc = Timecode::new(0, 0, 0, 0).unwrap();
+        assert!(MtcMessage::full_frame(&tc, FrameRate::Fps50).is_err());
+        assert!(MtcMessage::quarter_frames(&tc, FrameRate::Fps60).is_err());
+        assert!(MtcBridge::new(FrameRate::Fps48).is_err());
+        let dropped: Timecode = "00:01:00;00".parse().unwrap();
+        assert!(MtcMessage::full_frame(&dropped, FrameRate::Fps29_97Df).is_err());
+        assert_eq!(
+            MtcMessage::full_frame(&tc, FrameRate::Fps23_976).unwrap(),
+            MtcMessage::full_frame(&tc, FrameRate::Fps24).unwrap()
+        );
+    }
+
+    #[test]
+    fn mtc_parser_reads_full_frames_between_other_messages() {
+        let tc: Timecode = "23:59:59;29".parse().unwrap();
+        let mut stream = alloc::vec![0x90, 0x40, 0x7F, 0xF8];
+        let full_frame = MtcMessage::full_frame(&tc, FrameRate::Fps29_97Df).unwrap();
+        let full_frame = full_frame.to_bytes();
+        stream.extend_from_slice(&full_frame[..4]);
+        // Timing clock within the SysEx message.
+        stream.push(0xF8);
+        stream.extend_from_slice(&full_frame[4..]);
+        // Another SysEx message is ignored.
+        stream.extend_from_slice(&[0xF0, 0x7E, 0x7F, 0x06, 0x01, 0xF7]);
+
+        let mut parser = MtcParser::new();
+        let events: Vec<_> = stream.iter().filter_map(|&b| parser.push(b)).collect();
+        assert_eq!(
+            events,
+            [MtcEvent::FullFrame(
+                tc.with_frame_rate(FrameRate::Fps29_97Df)
+            )]
+        );
+    }
+
+    #[test]
+    fn mtc_bridge_round_trips_through_parser() {
+        for (frame_rate, start) in [
+            (FrameRate::Fps24, "01:00:00:00"),
+            (FrameRate::Fps25, "10:59:59:20"),
+            (FrameRate::Fps29_97Df, "00:00:59;20"),
+            (FrameRate::Fps30, "23:59:59:28"),
+        ] {
+            let ltc = encode_ltc(48_000, frame_rate, start, 21, Direction::Forward);
+            let messages = bridge(frame_rate, ltc);
+            assert!(messages
+                .windows(2)
+                .all(|pair| pair[0].offset <= pair[1].offset));
+            assert_eq!(messages.len(), 1 + 20 * 4);
+
+            let mut parser = MtcParser::new();
+            let events: Vec<_> = bytes(&messages)
+                .iter()
+                .filter_map(|&b| parser.push(b))
+                .collect();
+            assert_eq!(events.len(), 1 + 10, "{}", frame_rate);
+            let MtcEvent::FullFrame(first) = events[0] else {
+                panic!("{:?}", events[0]);
+            };
+            let start: Timecode = start.parse().unwrap();
+            assert_eq!(first, start.with_frame_rate(frame_rate));
+            for (i, event) in events[1..].iter().enumerate() {
+                assert_eq!(
+                    *event,
+                    MtcEvent::QuarterFrames {
+                        timecode: first
+                            .checked_add(crate::FrameCount(2 * i as i64 + 2))
+                            .unwrap(),
+                        direction: Direction::Forward,
+                    }
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn mtc_bridge_follows_reverse_ltc_and_jumps() {
+        let ltc = encode_ltc(
+            48_000,
+            FrameRate::Fps25,
+            "10:00:00:10",
+            9,
+            Direction::Reverse,
+        );
+        let messages = bridge(FrameRate::Fps25, ltc);
+        let mut parser = MtcParser::new();
+        let events: Vec<_> = bytes(&messages)
+            .iter()
+            .filter_map(|&b| parser.push(b))
+            .collect();
+        let start: Timecode = "10:00:00:10".parse().unwrap();
+        let start = start.with_frame_rate(FrameRate::Fps25);
+        assert_eq!(events[0], MtcEvent::FullFrame(start));
+        assert_eq!(
+            events[1],
+            MtcEvent::QuarterFrames {
+                timecode: start.checked_sub(crate::FrameCount(2)).unwrap(),
+                direction: Direction::Reverse,
+            }
+        );
+        assert_eq!(events.len(), 1 + 4);
+
+        // Decoding the same frame twice looks like a jump.
+        let mut encoder = Encoder::new(48_000, FrameRate::Fps25).unwrap();
+        let mut decoder = Decoder::new(1920, 32).unwrap();
+        let mut bridge = MtcBridge::new(FrameRate::Fps25).unwrap();
+        let mut full_frames = 0;
+        for _ in 0..6 {
+            encoder.encode_frame();
+            for frame in decoder.decode(encoder.get_buffer()) {
+                full_frames += bridge
+                    .process(&frame)
+                    .iter()
+                    .filter(|timed| matches!(timed.message, MtcMessage::FullFrame(_)))
+                    .count();
+            }
+        }
+        assert_eq!(full_frames, 5);
+    }
+}
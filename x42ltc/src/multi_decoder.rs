@@ -0,0 +1,178 @@
+// x42ltc: src/multi_decoder.rs
+//
+// Copyright 2019-2020 Johannes Maibaum <jmaibaum@gmail.com>
+//
+// This file is free software; you can redistribute it and/or modify it
+// under the terms of the GNU Lesser General Public License as
+// published by the Free Software Foundation; either version 3 of the
+// License, or (at your option) any later version.
+//
+// This file is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public
+// License along with this program.  If not, see <http://www.gnu.org/licenses/>.
+//
+// SPDX-License-Identifier: LGPL-3.0-or-later
+use alloc::collections::VecDeque;
+use alloc::vec::Vec;
+
+use crate::{DecodedFrame, Decoder, Error, Sample};
+
+/// Decodes all channels of interleaved audio, to find the one carrying LTC.
+///
+/// Each channel gets its own [`Decoder`].  The frames decoded from any channel are collected
+/// until they are read, tagged with their channel index (counting from 0).
+///
+/// # Example
+///
+/// ```
+/// use x42ltc::{Encoder, FrameRate, MultiDecoder};
+///
+/// let mut encoder = Encoder::new(48_000, FrameRate::Fps25).unwrap();
+/// let mut decoder = MultiDecoder::new(4, 1920, 32).unwrap();
+/// for _ in 0..10 {
+///     encoder.encode_frame();
+///     // Four channels, LTC on the third one.
+///     let audio: Vec<u8> = encoder
+///         .get_buffer()
+///         .iter()
+///         .flat_map(|&s| [128, 128, s, 128])
+///         .collect();
+///     decoder.write(&audio);
+///     encoder.increase_timecode();
+/// }
+/// assert_eq!(decoder.ltc_channel(), Some(2));
+/// assert!(decoder.frames().all(|(channel, _)| channel == 2));
+/// ```
+pub struct MultiDecoder {
+    decoders: Vec<Decoder>,
+    frame_counts: Vec<u64>,
+    queue: VecDeque<(usize, DecodedFrame)>,
+}
+
+impl MultiDecoder {
+    /// Create a decoder for `channels` channels.  The other arguments are passed on to
+    /// [`Decoder::new()`] for each channel.
+    pub fn new(
+        channels: usize,
+        audio_frames_per_video_frame: i32,
+        queue_size: i32,
+    ) -> Result<MultiDecoder, Error> {
+        if channels == 0 {
+            return Err(Error::ValueOutOfRange);
+        }
+        let decoders = (0..channels)
+            .map(|_| Decoder::new(audio_frames_per_video_frame, queue_size))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(MultiDecoder {
+            decoders,
+            frame_counts: alloc::vec![0; channels],
+            queue: VecDeque::new(),
+        })
+    }
+
+    /// The number of channels.
+    pub fn channels(&self) -> usize {
+        self.decoders.len()
+    }
+
+    /// The decoder of channel `channel`, e.g. to read its [`position()`](Decoder::position).
+    ///
+    /// # Panics
+    ///
+    /// Panics if `channel` is out of range.
+    pub fn decoder(&self, channel: usize) -> &Decoder {
+        &self.decoders[channel]
+    }
+
+    /// Writes interleaved audio data with [`.channels()`](#method.channels) channels into the
+    /// decoders, see [`Decoder::write_interleaved()`].
+    pub fn write<S: Sample>(&mut self, data: &[S]) {
+        let channels = self.decoders.len();
+        for (channel, decoder) in self.decoders.iter_mut().enumerate() {
+            decoder.write_interleaved(data, channels, channel);
+            for frame in decoder.frames() {
+                self.frame_counts[channel] += 1;
+                self.queue.push_back((channel, frame));
+            }
+        }
+    }
+
+    /// The number of valid frames decoded from channel `channel` so far.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `channel` is out of range.
+    pub fn frame_count(&self, channel: usize) -> u64 {
+        self.frame_counts[channel]
+    }
+
+    /// The channel which valid LTC frames were decoded from so far, or the one with the most
+    /// frames if there are several.  Of channels with equally many valid frames, the one with the
+    /// fewest [skipped](Decoder::skipped_frames) frames is chosen.  Returns `None` if no frame
+    /// was decoded yet.
+    pub fn ltc_channel(&self) -> Option<usize> {
+        let (channel, &count) =
+            self.frame_counts
+                .iter()
+                .enumerate()
+                .rev()
+                .max_by_key(|&(channel, count)| {
+                    (
+                        count,
+                        core::cmp::Reverse(self.decoders[channel].skipped_frames()),
+                    )
+                })?;
+        (count > 0).then_some(channel)
+    }
+
+    /// Reads the next decoded frame and its channel.
+    ///
+    /// Frames are collected by [`.write()`](#method.write) until they are read, in the order of
+    /// the channels for each write.
+    pub fn read(&mut self) -> Option<(usize, DecodedFrame)> {
+        self.queue.pop_front()
+    }
+
+    /// Returns an iterator draining all frames collected so far, with their channels.
+    pub fn frames(&mut self) -> impl Iterator<Item = (usize, DecodedFrame)> + '_ {
+        self.queue.drain(..)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Encoder, FrameRate};
+
+    #[test]
+    fn multi_decoder_finds_ltc_channel() {
+        assert!(MultiDecoder::new(0, 1920, 32).is_err());
+
+        let mut encoder = Encoder::new(48_000, FrameRate::Fps25).unwrap();
+        let mut decoder = MultiDecoder::new(3, 1920, 32).unwrap();
+        assert_eq!(decoder.ltc_channel(), None);
+        for _ in 0..30 {
+            encoder.encode_frame();
+            let audio: Vec<f32> = encoder
+                .get_buffer()
+                .iter()
+                .flat_map(|&s| [0.0, (f32::from(s) - 128.0) / 128.0, 0.5])
+                .collect();
+            decoder.write(&audio);
+            encoder.increase_timecode();
+        }
+        assert_eq!(decoder.ltc_channel(), Some(1));
+        assert_eq!(decoder.frame_count(0), 0);
+        assert_eq!(decoder.frame_count(1), 29);
+        assert_eq!(decoder.decoder(2).position(), 30 * 1920);
+        let (channel, frame) = decoder.read().unwrap();
+        assert_eq!(channel, 1);
+        assert_eq!(frame.timecode().to_string(), "00:00:00:00");
+        assert_eq!(decoder.frames().count(), 28);
+        assert_eq!(decoder.read(), None);
+    }
+}
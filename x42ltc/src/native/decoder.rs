@@ -0,0 +1,324 @@
+// x42ltc: src/native/decoder.rs
+//
+// Copyright 2019-2020 Johannes Maibaum <jmaibaum@gmail.com>
+//
+// This file is free software; you can redistribute it and/or modify it
+// under the terms of the GNU Lesser General Public License as
+// published by the Free Software Foundation; either version 3 of the
+// License, or (at your option) any later version.
+//
+// This file is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public
+// License along with this program.  If not, see <http://www.gnu.org/licenses/>.
+//
+// SPDX-License-Identifier: LGPL-3.0-or-later
+use alloc::vec;
+use alloc::vec::Vec;
+
+use super::SAMPLE_CENTER;
+#[cfg(not(any(feature = "std", test)))]
+use crate::float::Float;
+use crate::{DecodedFrame, Error, LtcFrame};
+
+/// The number of bits of an LTC frame.
+const FRAME_BIT_COUNT: u32 = 80;
+
+/// The sync word as seen by the decoder, which shifts in the most recent bit from the right.
+const SYNC_FORWARD: u16 = 0x3FFD;
+const SYNC_REVERSE: u16 = 0xBFFC;
+
+/// libltc converts other sample formats in chunks of this many samples.
+const CONVERSION_BUF_SIZE: usize = 1024;
+
+/// A decoded frame waiting in the queue.
+#[derive(Clone, Copy)]
+struct QueuedFrame {
+    frame: LtcFrame,
+    off_start: i64,
+    off_end: i64,
+    reverse: bool,
+    sample_min: u8,
+    sample_max: u8,
+    volume: f64,
+}
+
+/// A port of libltc's decoder (`decoder.c`), producing identical frames and offsets.
+pub(crate) struct RawDecoder {
+    queue: Vec<QueuedFrame>,
+    queue_read_off: usize,
+    queue_write_off: usize,
+
+    biphase_state: bool,
+    snd_to_biphase_period: f64,
+    snd_to_biphase_cnt: i32,
+    snd_to_biphase_lmt: i32,
+    snd_to_biphase_state: bool,
+    snd_to_biphase_min: u8,
+    snd_to_biphase_max: u8,
+
+    decoder_sync_word: u16,
+    frame: [u8; 10],
+    bit_cnt: u32,
+    frame_start_off: i64,
+    frame_start_prev: i64,
+}
+
+impl RawDecoder {
+    pub(crate) fn new(audio_frames_per_video_frame: i32, queue_size: i32) -> Option<Self> {
+        if queue_size < 1 {
+            return None;
+        }
+        let empty = QueuedFrame {
+            frame: LtcFrame::from_bytes([0; 10]),
+            off_start: 0,
+            off_end: 0,
+            reverse: false,
+            sample_min: 0,
+            sample_max: 0,
+            volume: 0.0,
+        };
+        let snd_to_biphase_period = f64::from(audio_frames_per_video_frame / 80);
+        Some(RawDecoder {
+            queue: vec![empty; queue_size as usize],
+            queue_read_off: 0,
+            queue_write_off: 0,
+            biphase_state: true,
+            snd_to_biphase_period,
+            snd_to_biphase_cnt: 0,
+            snd_to_biphase_lmt: (snd_to_biphase_period * 3.0 / 4.0) as i32,
+            snd_to_biphase_state: false,
+            snd_to_biphase_min: SAMPLE_CENTER,
+            snd_to_biphase_max: SAMPLE_CENTER,
+            decoder_sync_word: 0,
+            frame: [0; 10],
+            bit_cnt: 0,
+            frame_start_off: 0,
+            frame_start_prev: -1,
+        })
+    }
+
+    pub(crate) fn queue_flush(&mut self) {
+        self.queue_read_off = self.queue_write_off;
+    }
+
+    pub(crate) fn queue_length(&self) -> i32 {
+        let len = self.queue.len();
+        ((self.queue_write_off + len - self.queue_read_off) % len) as i32
+    }
+
+    pub(crate) fn write(&mut self, data: &[u8], posinfo: i64) {
+        for (i, &sample) in data.iter().enumerate() {
+            self.decode_sample(sample, i as i64, posinfo);
+        }
+    }
+
+    pub(crate) fn write_f32(&mut self, data: &[f32], posinfo: i64) {
+        self.write_converted(data, posinfo, |s| {
+            (128.0 + f64::from(s) * 127.0) as i32 as u8
+        });
+    }
+
+    pub(crate) fn write_f64(&mut self, data: &[f64], posinfo: i64) {
+        self.write_converted(data, posinfo, |s| (128.0 + s * 127.0) as i32 as u8);
+    }
+
+    pub(crate) fn write_i16(&mut self, data: &[i16], posinfo: i64) {
+        self.write_converted(data, posinfo, |s| (128 + (s >> 8)) as u8);
+    }
+
+    pub(crate) fn write_u16(&mut self, data: &[u16], posinfo: i64) {
+        self.write_converted(data, posinfo, |s| (s >> 8) as u8);
+    }
+
+    /// Convert to unsigned 8 bit like libltc's `LTCWRITE_TEMPLATE`, whose chunking determines
+    /// the sample offsets of the frames.
+    fn write_converted<S: Copy>(&mut self, data: &[S], posinfo: i64, convert: impl Fn(S) -> u8) {
+        for (chunk_index, chunk) in data.chunks(CONVERSION_BUF_SIZE).enumerate() {
+            let chunk_posinfo = posinfo + (chunk_index * CONVERSION_BUF_SIZE) as i64;
+            for (i, &sample) in chunk.iter().enumerate() {
+                self.decode_sample(convert(sample), i as i64, chunk_posinfo);
+            }
+        }
+    }
+
+    /// The next queued frame, or an error if its time of day is not a valid timecode.
+    pub(crate) fn read(&mut self) -> Option<Result<DecodedFrame, Error>> {
+        if self.queue_read_off == self.queue_write_off {
+            return None;
+        }
+        let queued = self.queue[self.queue_read_off];
+        self.queue_read_off = (self.queue_read_off + 1) % self.queue.len();
+        Some(DecodedFrame::new(
+            queued.frame,
+            queued.off_start,
+            queued.off_end,
+            queued.reverse,
+            queued.sample_min,
+            queued.sample_max,
+            queued.volume,
+        ))
+    }
+
+    /// Track the signal level and detect biphase-mark transitions, see `decode_ltc()`.
+    fn decode_sample(&mut self, sample: u8, offset: i64, posinfo: i64) {
+        let center = i32::from(SAMPLE_CENTER);
+        let min = i32::from(self.snd_to_biphase_min);
+        let max = i32::from(self.snd_to_biphase_max);
+        self.snd_to_biphase_min = (center - (center - min) * 15 / 16) as u8;
+        self.snd_to_biphase_max = (center + (max - center) * 15 / 16) as u8;
+        self.snd_to_biphase_min = self.snd_to_biphase_min.min(sample);
+        self.snd_to_biphase_max = self.snd_to_biphase_max.max(sample);
+
+        let min = i32::from(self.snd_to_biphase_min);
+        let max = i32::from(self.snd_to_biphase_max);
+        let min_threshold = center - (center - min) * 8 / 16;
+        let max_threshold = center + (max - center) * 8 / 16;
+        let sample_value = i32::from(sample);
+
+        if (self.snd_to_biphase_state && sample_value > max_threshold)
+            || (!self.snd_to_biphase_state && sample_value < min_threshold)
+        {
+            if self.snd_to_biphase_cnt > self.snd_to_biphase_lmt {
+                // A single transition within a bit period: 0.
+                self.biphase_state = true;
+                self.parse_bit(false, offset, posinfo);
+            } else {
+                // Two transitions within a bit period: 1.
+                self.snd_to_biphase_cnt *= 2;
+                self.biphase_state = !self.biphase_state;
+                if self.biphase_state {
+                    self.parse_bit(true, offset, posinfo);
+                }
+            }
+
+            if f64::from(self.snd_to_biphase_cnt) > self.snd_to_biphase_period * 4.0 {
+                // Long silence in between, restart without using it for speed tracking.
+                self.bit_cnt = 0;
+            } else {
+                self.snd_to_biphase_period =
+                    (self.snd_to_biphase_period * 3.0 + f64::from(self.snd_to_biphase_cnt)) / 4.0;
+                self.snd_to_biphase_lmt = (self.snd_to_biphase_period * 3.0 / 4.0) as i32;
+            }
+
+            self.snd_to_biphase_cnt = 0;
+            self.snd_to_biphase_state = !self.snd_to_biphase_state;
+        }
+        self.snd_to_biphase_cnt += 1;
+    }
+
+    /// Shift a bit into the current frame and queue it on a sync word, see `parse_ltc()`.
+    fn parse_bit(&mut self, bit: bool, offset: i64, posinfo: i64) {
+        if self.bit_cnt == 0 {
+            self.frame = [0; 10];
+            self.frame_start_off = if self.frame_start_prev < 0 {
+                (posinfo as f64 - self.snd_to_biphase_period) as i64
+            } else {
+                self.frame_start_prev
+            };
+        }
+        self.frame_start_prev = offset + posinfo;
+
+        if self.bit_cnt >= FRAME_BIT_COUNT {
+            // Drop the oldest bit.
+            for k in 0..10 {
+                let next = self.frame.get(k + 1).map_or(0, |byte| byte << 7);
+                self.frame[k] = (self.frame[k] >> 1) | next;
+            }
+            self.frame_start_off =
+                (self.frame_start_off as f64 + self.snd_to_biphase_period.ceil()) as i64;
+            self.bit_cnt -= 1;
+        }
+
+        self.decoder_sync_word <<= 1;
+        if bit {
+            self.decoder_sync_word |= 1;
+            if self.bit_cnt < FRAME_BIT_COUNT {
+                self.frame[(self.bit_cnt >> 3) as usize] |= 1 << (self.bit_cnt & 7);
+            }
+        }
+        self.bit_cnt += 1;
+
+        let off_end = posinfo + offset - 1;
+        if self.decoder_sync_word == SYNC_FORWARD {
+            if self.bit_cnt == FRAME_BIT_COUNT {
+                self.queue_frame(self.frame, self.frame_start_off, off_end, false);
+            }
+            self.bit_cnt = 0;
+        }
+
+        if self.decoder_sync_word == SYNC_REVERSE {
+            if self.bit_cnt == FRAME_BIT_COUNT {
+                // The bits arrived in reverse order, and the sync word preceding the data
+                // belongs to the next frame.
+                let mut frame = self.frame.map(u8::reverse_bits);
+                frame[..8].reverse();
+                let correction = 16.0 * self.snd_to_biphase_period;
+                self.queue_frame(
+                    frame,
+                    (self.frame_start_off as f64 - correction) as i64,
+                    (off_end as f64 - correction) as i64,
+                    true,
+                );
+            }
+            self.bit_cnt = 0;
+        }
+    }
+
+    fn queue_frame(&mut self, frame: [u8; 10], off_start: i64, off_end: i64, reverse: bool) {
+        let volume = if self.snd_to_biphase_max <= self.snd_to_biphase_min {
+            f64::NEG_INFINITY
+        } else {
+            20.0 * (f64::from(self.snd_to_biphase_max - self.snd_to_biphase_min) / 255.0).log10()
+        };
+        self.queue[self.queue_write_off] = QueuedFrame {
+            frame: LtcFrame::from_bytes(frame),
+            off_start,
+            off_end,
+            reverse,
+            sample_min: self.snd_to_biphase_min,
+            sample_max: self.snd_to_biphase_max,
+            volume,
+        };
+        self.queue_write_off = (self.queue_write_off + 1) % self.queue.len();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{BgFlags, FrameRate, Timecode};
+
+    #[test]
+    fn native_decoder_decodes_square_wave() {
+        let mut encoder =
+            super::super::RawEncoder::new(48_000, FrameRate::Fps25, BgFlags::empty()).unwrap();
+        let mut frame = encoder.frame();
+        frame.set_timecode(
+            Timecode::new(1, 2, 3, 4).unwrap(),
+            FrameRate::Fps25,
+            BgFlags::empty(),
+        );
+        encoder.set_frame(&frame);
+        encoder.set_filter(0.0);
+
+        let mut decoder = RawDecoder::new(1920, 4).unwrap();
+        for i in 0..3 {
+            encoder.encode_frame();
+            decoder.write(encoder.take_buffer(), 1920 * i);
+        }
+
+        // The last frame is only completed by the first transition of the next one.
+        assert_eq!(decoder.queue_length(), 2);
+        let decoded = decoder.read().unwrap().unwrap();
+        assert_eq!(decoded.frame, frame);
+        assert_eq!(decoded.off_end, 1919);
+        assert!(!decoded.reverse);
+        assert_eq!(decoder.read().unwrap().unwrap().off_start, 1920);
+        assert!(decoder.read().is_none());
+        assert!(RawDecoder::new(1920, 0).is_none());
+    }
+}
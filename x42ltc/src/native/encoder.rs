@@ -0,0 +1,278 @@
+// x42ltc: src/native/encoder.rs
+//
+// Copyright 2019-2020 Johannes Maibaum <jmaibaum@gmail.com>
+//
+// This file is free software; you can redistribute it and/or modify it
+// under the terms of the GNU Lesser General Public License as
+// published by the Free Software Foundation; either version 3 of the
+// License, or (at your option) any later version.
+//
+// This file is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public
+// License along with this program.  If not, see <http://www.gnu.org/licenses/>.
+//
+// SPDX-License-Identifier: LGPL-3.0-or-later
+use alloc::vec;
+use alloc::vec::Vec;
+use core::cell::Cell;
+
+use super::SAMPLE_CENTER;
+#[cfg(not(any(feature = "std", test)))]
+use crate::float::Float;
+use crate::modulator::{DEFAULT_RISE_TIME, DEFAULT_VOLUME};
+use crate::{BgFlags, FrameRate, LtcFrame};
+
+/// A port of libltc's encoder (`encoder.c`), producing identical 8 bit samples.
+pub(crate) struct RawEncoder {
+    sample_rate: f64,
+    samples_per_clock: f64,
+    samples_per_clock_2: f64,
+    sample_remainder: f64,
+    filter_const: f64,
+    enc_lo: u8,
+    enc_hi: u8,
+    state: bool,
+    frame: LtcFrame,
+    buffer: Vec<u8>,
+    // Reading the buffer through a shared reference flushes it, like `ltc_encoder_get_bufptr()`.
+    offset: Cell<usize>,
+}
+
+impl RawEncoder {
+    pub(crate) fn new(sample_rate: u32, frame_rate: FrameRate, flags: BgFlags) -> Option<Self> {
+        if sample_rate < 1 {
+            return None;
+        }
+        let mut encoder = RawEncoder {
+            sample_rate: 0.0,
+            samples_per_clock: 0.0,
+            samples_per_clock_2: 0.0,
+            sample_remainder: 0.5,
+            filter_const: 0.0,
+            enc_lo: 0,
+            enc_hi: 0,
+            state: false,
+            frame: LtcFrame::new(),
+            buffer: Vec::new(),
+            offset: Cell::new(0),
+        };
+        encoder.set_buffer_size(f64::from(sample_rate), frame_rate.as_f64());
+        encoder.set_volume(DEFAULT_VOLUME);
+        encoder
+            .reinitialize(sample_rate, frame_rate, flags)
+            .then_some(encoder)
+    }
+
+    pub(crate) fn reinitialize(
+        &mut self,
+        sample_rate: u32,
+        frame_rate: FrameRate,
+        flags: BgFlags,
+    ) -> bool {
+        let sample_rate = f64::from(sample_rate);
+        let fps = frame_rate.as_f64();
+        if sample_rate < 1.0 || buffer_size(sample_rate, fps) > self.buffer.len() {
+            return false;
+        }
+
+        self.state = false;
+        self.offset.set(0);
+        self.sample_rate = sample_rate;
+        self.set_filter(DEFAULT_RISE_TIME);
+        self.samples_per_clock = sample_rate / (fps * 80.0);
+        self.samples_per_clock_2 = self.samples_per_clock / 2.0;
+        self.sample_remainder = 0.5;
+
+        let frame = &mut self.frame;
+        if !flags.contains(BgFlags::BGF_DONT_TOUCH) {
+            frame.set_col_frame(false);
+            frame.set_binary_group_flag_bit1(flags.contains(BgFlags::TC_CLOCK));
+            if frame_rate.is_625_50() {
+                frame.set_biphase_mark_phase_correction(false);
+                frame.set_binary_group_flag_bit0(flags.contains(BgFlags::USE_DATE));
+            } else {
+                frame.set_binary_group_flag_bit0(false);
+                frame.set_binary_group_flag_bit2(flags.contains(BgFlags::USE_DATE));
+            }
+        }
+        if !flags.contains(BgFlags::NO_PARITY) {
+            frame.set_parity(frame_rate);
+        }
+        frame.set_dfbit((fps * 100.0).round_ties_even() == 2997.0);
+        true
+    }
+
+    pub(crate) fn set_buffer_size(&mut self, sample_rate: f64, fps: f64) -> bool {
+        self.offset.set(0);
+        self.buffer = vec![0; buffer_size(sample_rate, fps)];
+        true
+    }
+
+    pub(crate) fn buffer_size(&self) -> usize {
+        self.buffer.len()
+    }
+
+    pub(crate) fn reset(&mut self) {
+        self.state = false;
+        self.offset.set(0);
+    }
+
+    pub(crate) fn set_filter(&mut self, rise_time: f64) {
+        self.filter_const = if rise_time <= 0.0 {
+            0.0
+        } else {
+            1.0 - (-1.0 / (self.sample_rate * rise_time / 2_000_000.0 / core::f64::consts::E)).exp()
+        };
+    }
+
+    pub(crate) fn set_volume(&mut self, volume_in_dbfs: f64) -> bool {
+        if volume_in_dbfs > 0.0 {
+            return false;
+        }
+        let pp = (127.0 * 10f64.powf(volume_in_dbfs / 20.0)).round_ties_even();
+        if !(1.0..=127.0).contains(&pp) {
+            return false;
+        }
+        self.enc_lo = SAMPLE_CENTER - pp as u8;
+        self.enc_hi = SAMPLE_CENTER + pp as u8;
+        true
+    }
+
+    pub(crate) fn frame(&self) -> LtcFrame {
+        self.frame
+    }
+
+    pub(crate) fn set_frame(&mut self, frame: &LtcFrame) {
+        self.frame = *frame;
+    }
+
+    /// Encode one byte of the frame, LSB first, or MSB first if `speed` is negative.
+    pub(crate) fn encode_byte(&mut self, byte: u8, speed: f64) -> bool {
+        if byte > 9 || speed == 0.0 {
+            return false;
+        }
+        let value = self.frame.to_bytes()[usize::from(byte)];
+        let spc = self.samples_per_clock * speed.abs();
+        let sph = self.samples_per_clock_2 * speed.abs();
+
+        // Like libltc, keep going after an overflow and report it at the end.
+        let mut ok = true;
+        for bit in 0..8 {
+            let mask = if speed < 0.0 { 0x80 >> bit } else { 1 << bit };
+            if value & mask == 0 {
+                ok &= self.add_cell(spc);
+            } else {
+                ok &= self.add_cell(sph);
+                ok &= self.add_cell(sph);
+            }
+        }
+        ok
+    }
+
+    pub(crate) fn encode_frame(&mut self) {
+        for byte in 0..10 {
+            self.encode_byte(byte, 1.0);
+        }
+    }
+
+    pub(crate) fn encode_reversed_frame(&mut self) {
+        for byte in (0..10).rev() {
+            self.encode_byte(byte, -1.0);
+        }
+    }
+
+    /// Add the terminating transition after the last frame.
+    pub(crate) fn end_encode(&mut self) -> bool {
+        self.state = !self.state;
+        self.add_values(1)
+    }
+
+    /// Toggle the biphase state and fill a (half) bit cell of `length` samples, carrying the
+    /// fractional part over to the next cell.
+    fn add_cell(&mut self, length: f64) -> bool {
+        let n = (length + self.sample_remainder) as usize;
+        self.sample_remainder = length + self.sample_remainder - n as f64;
+        self.state = !self.state;
+        self.add_values(n)
+    }
+
+    fn add_values(&mut self, n: usize) -> bool {
+        let target = if self.state { self.enc_hi } else { self.enc_lo };
+        let offset = self.offset.get();
+        if offset + n >= self.buffer.len() {
+            return false;
+        }
+
+        let wave = &mut self.buffer[offset..offset + n];
+        let tcf = self.filter_const;
+        if tcf > 0.0 {
+            // Low-pass filter, starting at the center for both halves of the cell.
+            let mut value = SAMPLE_CENTER;
+            for i in 0..n.div_ceil(2) {
+                value = (f64::from(value) + tcf * (f64::from(target) - f64::from(value))) as u8;
+                wave[i] = value;
+                wave[n - i - 1] = value;
+            }
+        } else {
+            wave.fill(target);
+        }
+
+        self.offset.set(offset + n);
+        true
+    }
+
+    pub(crate) fn buffered_len(&self) -> usize {
+        self.offset.get()
+    }
+
+    /// The accumulated samples.  The internal buffer is flushed, but the returned slice stays
+    /// valid until the next call taking `&mut self`.
+    pub(crate) fn take_buffer(&self) -> &[u8] {
+        &self.buffer[..self.offset.replace(0)]
+    }
+
+    /// Copy the accumulated samples to `buffer`, which must be large enough, and flush.
+    pub(crate) fn copy_buffer(&mut self, buffer: &mut [u8]) -> usize {
+        let samples = self.take_buffer();
+        buffer[..samples.len()].copy_from_slice(samples);
+        samples.len()
+    }
+
+    pub(crate) fn flush_buffer(&mut self) {
+        self.offset.set(0);
+    }
+}
+
+/// The buffer size holding exactly one frame, as computed by `ltc_encoder_set_bufsize()`.
+fn buffer_size(sample_rate: f64, fps: f64) -> usize {
+    1 + (sample_rate / fps).ceil() as usize
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn native_encoder_writes_square_wave() {
+        let mut encoder = RawEncoder::new(48_000, FrameRate::Fps25, BgFlags::empty()).unwrap();
+        assert_eq!(encoder.buffer_size(), 1921);
+        assert!(encoder.set_volume(0.0));
+        encoder.set_filter(0.0);
+
+        encoder.set_frame(&LtcFrame::from_bytes([0, 0, 0, 0, 0, 0, 0, 0, 0xFC, 0xBF]));
+        encoder.encode_frame();
+        let samples = encoder.take_buffer();
+        assert_eq!(samples.len(), 1920);
+        assert!(samples[..24].iter().all(|&s| s == 255));
+        assert!(samples[24..48].iter().all(|&s| s == 1));
+        assert_eq!(encoder.buffered_len(), 0);
+
+        assert!(!encoder.set_volume(-50.0));
+        assert!(!encoder.encode_byte(10, 1.0));
+        assert!(!encoder.encode_byte(0, 0.0));
+    }
+}
@@ -0,0 +1,134 @@
+// x42ltc: src/native/mod.rs
+//
+// Copyright 2019-2020 Johannes Maibaum <jmaibaum@gmail.com>
+//
+// This file is free software; you can redistribute it and/or modify it
+// under the terms of the GNU Lesser General Public License as
+// published by the Free Software Foundation; either version 3 of the
+// License, or (at your option) any later version.
+//
+// This file is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public
+// License along with this program.  If not, see <http://www.gnu.org/licenses/>.
+//
+// SPDX-License-Identifier: LGPL-3.0-or-later
+mod decoder;
+mod encoder;
+
+pub(crate) use decoder::RawDecoder;
+pub(crate) use encoder::RawEncoder;
+
+/// The center value of unsigned 8 bit samples.
+const SAMPLE_CENTER: u8 = 128;
+
+#[cfg(all(test, feature = "libltc"))]
+mod tests {
+    use crate::{libltc, BgFlags, FrameRate, LtcFrame, Timecode};
+
+    fn timecode_frame(frame_rate: FrameRate, flags: BgFlags) -> LtcFrame {
+        let mut frame = LtcFrame::new();
+        frame.set_dfbit(frame_rate.is_drop_frame());
+        frame.set_user_bits(0x1234_5678);
+        frame.set_timecode(Timecode::new(9, 59, 58, 7).unwrap(), frame_rate, flags);
+        frame
+    }
+
+    #[test]
+    fn native_encoder_matches_libltc() {
+        let flags = BgFlags::USE_DATE | BgFlags::TC_CLOCK;
+        for (sample_rate, frame_rate) in [
+            (48_000, FrameRate::Fps25),
+            (44_100, FrameRate::Fps29_97Df),
+            (192_000, FrameRate::Fps23_976),
+            (8_000, FrameRate::Fps30),
+        ] {
+            let mut native = super::RawEncoder::new(sample_rate, frame_rate, flags).unwrap();
+            let mut libltc = libltc::RawEncoder::new(sample_rate, frame_rate, flags).unwrap();
+            assert_eq!(native.frame(), libltc.frame());
+            assert_eq!(native.buffer_size(), libltc.buffer_size());
+
+            let frame = timecode_frame(frame_rate, flags);
+            native.set_frame(&frame);
+            libltc.set_frame(&frame);
+            for (volume, rise_time) in [(-3.0, 40.0), (-18.0, 0.0), (0.0, 25.0), (-40.0, 60.0)] {
+                assert_eq!(native.set_volume(volume), libltc.set_volume(volume));
+                native.set_filter(rise_time);
+                libltc.set_filter(rise_time);
+
+                native.encode_frame();
+                libltc.encode_frame();
+                assert_eq!(native.take_buffer(), libltc.take_buffer());
+                native.encode_reversed_frame();
+                libltc.encode_reversed_frame();
+                assert_eq!(native.take_buffer(), libltc.take_buffer());
+            }
+
+            for speed in [0.75, -1.5, 2.0] {
+                for byte in 0..10 {
+                    assert_eq!(
+                        native.encode_byte(byte, speed),
+                        libltc.encode_byte(byte, speed)
+                    );
+                }
+                assert_eq!(native.end_encode(), libltc.end_encode());
+                assert_eq!(native.buffered_len(), libltc.buffered_len());
+                assert_eq!(native.take_buffer(), libltc.take_buffer());
+            }
+
+            assert_eq!(
+                native.reinitialize(sample_rate / 2, FrameRate::Fps24, BgFlags::empty()),
+                libltc.reinitialize(sample_rate / 2, FrameRate::Fps24, BgFlags::empty())
+            );
+            assert_eq!(native.frame(), libltc.frame());
+        }
+    }
+
+    #[test]
+    fn native_decoder_matches_libltc() {
+        let mut encoder =
+            libltc::RawEncoder::new(44_100, FrameRate::Fps30, BgFlags::empty()).unwrap();
+        encoder.set_frame(&timecode_frame(FrameRate::Fps30, BgFlags::empty()));
+        let mut audio = Vec::new();
+        for i in 0..40 {
+            if i < 20 {
+                encoder.encode_frame();
+            } else {
+                encoder.encode_reversed_frame();
+            }
+            audio.extend_from_slice(encoder.take_buffer());
+            let mut frame = encoder.frame();
+            frame.increment(FrameRate::Fps30, BgFlags::empty());
+            encoder.set_frame(&frame);
+        }
+        let as_f32: Vec<f32> = audio
+            .iter()
+            .map(|&s| (f32::from(s) - 128.0) / 100.0)
+            .collect();
+
+        let mut native = super::RawDecoder::new(1470, 8).unwrap();
+        let mut libltc = libltc::RawDecoder::new(1470, 8).unwrap();
+        let mut decoded = 0;
+        for (i, chunk) in audio.chunks(1000).enumerate() {
+            let posinfo = 1000 * i as i64;
+            if i % 2 == 0 {
+                native.write(chunk, posinfo);
+                libltc.write(chunk, posinfo);
+            } else {
+                let chunk = &as_f32[posinfo as usize..][..chunk.len()];
+                native.write_f32(chunk, posinfo);
+                libltc.write_f32(chunk, posinfo);
+            }
+            assert_eq!(native.queue_length(), libltc.queue_length());
+            while let Some(frame) = libltc.read() {
+                assert_eq!(native.read().unwrap().ok(), frame.ok());
+                decoded += 1;
+            }
+            assert!(native.read().is_none());
+        }
+        assert!(decoded > 30);
+    }
+}
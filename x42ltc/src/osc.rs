@@ -0,0 +1,344 @@
+// x42ltc: src/osc.rs
+//
+// Copyright 2019-2020 Johannes Maibaum <jmaibaum@gmail.com>
+//
+// This file is free software; you can redistribute it and/or modify it
+// under the terms of the GNU Lesser General Public License as
+// published by the Free Software Foundation; either version 3 of the
+// License, or (at your option) any later version.
+//
+// This file is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public
+// License along with this program.  If not, see <http://www.gnu.org/licenses/>.
+//
+// SPDX-License-Identifier: LGPL-3.0-or-later
+use std::io;
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr, ToSocketAddrs, UdpSocket};
+
+use crate::{DecodedFrame, Decoder};
+
+/// How the timecode is passed in an OSC message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum OscArguments {
+    /// One string argument, e.g. `"10:00:00:00"`, or `"10:00:00;00"` for drop-frame timecode.
+    String,
+    /// Four 32 bit integer arguments: hours, minutes, seconds and frame.
+    Integers,
+}
+
+/// The layout of the OSC messages sent for decoded frames.
+///
+/// # Example
+///
+/// ```
+/// use x42ltc::{Decoder, Encoder, FrameRate, OscArguments, OscConfig};
+///
+/// let mut encoder = Encoder::new(48_000, FrameRate::Fps25).unwrap();
+/// let mut decoder = Decoder::new(1920, 32).unwrap();
+/// for _ in 0..2 {
+///     encoder.encode_frame();
+///     decoder.write(encoder.get_buffer());
+///     encoder.increase_timecode();
+/// }
+/// let frame = decoder.read().unwrap();
+///
+/// let config = OscConfig::new("/ltc");
+/// assert_eq!(config.encode(&frame), b"/ltc\0\0\0\0,s\0\x0000:00:00:00\0");
+///
+/// let config = OscConfig::new("/ltc").arguments(OscArguments::Integers).reverse(true);
+/// assert_eq!(&config.encode(&frame)[8..16], b",iiiiF\0\0");
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct OscConfig {
+    /// The OSC address pattern, starting with `/`.
+    pub address: String,
+    pub arguments: OscArguments,
+    /// Append the sample offset of the start of the frame as 64 bit integer.
+    pub sample_offset: bool,
+    /// Append whether the frame was decoded from reverse LTC, as `T` or `F` type tag.
+    pub reverse: bool,
+}
+
+impl OscConfig {
+    /// Create a configuration for messages to `address` with a string argument.
+    pub fn new(address: impl Into<String>) -> OscConfig {
+        OscConfig {
+            address: address.into(),
+            arguments: OscArguments::String,
+            sample_offset: false,
+            reverse: false,
+        }
+    }
+
+    /// Returns this configuration passing the timecode as `arguments`.
+    pub fn arguments(mut self, arguments: OscArguments) -> OscConfig {
+        self.arguments = arguments;
+        self
+    }
+
+    /// Returns this configuration with or without the sample offset argument.
+    pub fn sample_offset(mut self, sample_offset: bool) -> OscConfig {
+        self.sample_offset = sample_offset;
+        self
+    }
+
+    /// Returns this configuration with or without the reverse flag argument.
+    pub fn reverse(mut self, reverse: bool) -> OscConfig {
+        self.reverse = reverse;
+        self
+    }
+
+    /// Whether the address pattern is valid: printable ASCII starting with `/`, without
+    /// spaces, `#` or `,`.
+    pub fn is_valid(&self) -> bool {
+        self.address.starts_with('/')
+            && self
+                .address
+                .bytes()
+                .all(|c| c.is_ascii_graphic() && c != b'#' && c != b',')
+    }
+
+    /// The OSC message for `frame`.
+    pub fn encode(&self, frame: &DecodedFrame) -> Vec<u8> {
+        let tc = frame.timecode();
+        let mut type_tags = String::from(",");
+        let mut arguments = Vec::new();
+        match self.arguments {
+            OscArguments::String => {
+                type_tags.push('s');
+                push_string(&mut arguments, &tc.to_string());
+            }
+            OscArguments::Integers => {
+                for value in [tc.hours(), tc.mins(), tc.secs(), tc.frame()] {
+                    type_tags.push('i');
+                    arguments.extend_from_slice(&i32::from(value).to_be_bytes());
+                }
+            }
+        }
+        if self.sample_offset {
+            type_tags.push('h');
+            arguments.extend_from_slice(&frame.off_start().to_be_bytes());
+        }
+        if self.reverse {
+            // Booleans are carried by the type tag alone.
+            type_tags.push(if frame.is_reverse() { 'T' } else { 'F' });
+        }
+
+        let mut message = Vec::new();
+        push_string(&mut message, &self.address);
+        push_string(&mut message, &type_tags);
+        message.extend_from_slice(&arguments);
+        message
+    }
+}
+
+/// Append an OSC string: null terminated and padded with nulls to a multiple of four bytes.
+fn push_string(bytes: &mut Vec<u8>, string: &str) {
+    bytes.extend_from_slice(string.as_bytes());
+    let padding = 4 - string.len() % 4;
+    bytes.resize(bytes.len() + padding, 0);
+}
+
+/// Sends decoded frames as OSC messages over UDP.
+///
+/// # Example
+///
+/// ```
+/// use std::net::UdpSocket;
+/// use x42ltc::{Decoder, Encoder, FrameRate, OscConfig, OscSender};
+///
+/// let media_server = UdpSocket::bind("127.0.0.1:0").unwrap();
+/// let sender = OscSender::new(media_server.local_addr().unwrap(), OscConfig::new("/tc")).unwrap();
+///
+/// let mut encoder = Encoder::new(48_000, FrameRate::Fps25).unwrap();
+/// let mut decoder = Decoder::new(1920, 32).unwrap();
+/// for _ in 0..3 {
+///     encoder.encode_frame();
+///     decoder.write(encoder.get_buffer());
+///     encoder.increase_timecode();
+/// }
+/// assert_eq!(sender.forward(&mut decoder).unwrap(), 2);
+///
+/// let mut buffer = [0; 64];
+/// let len = media_server.recv(&mut buffer).unwrap();
+/// assert_eq!(&buffer[..len], b"/tc\0,s\0\x0000:00:00:00\0");
+/// ```
+#[derive(Debug)]
+pub struct OscSender {
+    socket: UdpSocket,
+    destination: SocketAddr,
+    config: OscConfig,
+}
+
+impl OscSender {
+    /// Create a sender of messages laid out by `config` to `destination`.  The socket is bound
+    /// to an arbitrary local port.
+    ///
+    /// Returns an error of kind [`InvalidInput`](io::ErrorKind::InvalidInput) if the address
+    /// pattern is not [valid](OscConfig::is_valid).
+    pub fn new(destination: impl ToSocketAddrs, config: OscConfig) -> io::Result<OscSender> {
+        if !config.is_valid() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "invalid OSC address pattern",
+            ));
+        }
+        let destination = destination
+            .to_socket_addrs()?
+            .next()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "no destination address"))?;
+        let socket = match destination {
+            SocketAddr::V4(_) => UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0))?,
+            SocketAddr::V6(_) => UdpSocket::bind((Ipv6Addr::UNSPECIFIED, 0))?,
+        };
+        Ok(OscSender {
+            socket,
+            destination,
+            config,
+        })
+    }
+
+    /// The socket the messages are sent from, e.g. to enable broadcast.
+    pub fn socket(&self) -> &UdpSocket {
+        &self.socket
+    }
+
+    /// The address the messages are sent to.
+    pub fn destination(&self) -> SocketAddr {
+        self.destination
+    }
+
+    /// The layout of the messages.
+    pub fn config(&self) -> &OscConfig {
+        &self.config
+    }
+
+    /// Send the message for `frame`.
+    pub fn send_frame(&self, frame: &DecodedFrame) -> io::Result<()> {
+        self.socket
+            .send_to(&self.config.encode(frame), self.destination)?;
+        Ok(())
+    }
+
+    /// Send the messages for all frames queued in `decoder`, returns the number of frames sent.
+    pub fn forward(&self, decoder: &mut Decoder) -> io::Result<usize> {
+        let mut sent = 0;
+        for frame in decoder.frames() {
+            self.send_frame(&frame)?;
+            sent += 1;
+        }
+        Ok(sent)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::test_util::encode_ltc;
+    use crate::{Direction, FrameRate};
+
+    /// The frames decoded from `ltc` at 48 kHz.
+    fn decode(ltc: Vec<Vec<u8>>) -> Vec<DecodedFrame> {
+        let mut decoder = Decoder::with_sample_rate(48_000).unwrap();
+        ltc.iter()
+            .flat_map(|audio| decoder.decode(audio).collect::<Vec<_>>())
+            .collect()
+    }
+
+    #[test]
+    fn osc_config_encodes_all_argument_styles() {
+        let ltc = encode_ltc(
+            48_000,
+            FrameRate::Fps29_97Df,
+            "01:02:03;04",
+            3,
+            Direction::Forward,
+        );
+        let frame = &decode(ltc)[1];
+        assert_eq!(frame.off_start(), 1602);
+
+        let config = OscConfig::new("/composition/tc");
+        assert_eq!(
+            config.encode(frame),
+            b"/composition/tc\0,s\0\x0001:02:03;05\0"
+        );
+
+        let config = config
+            .arguments(OscArguments::Integers)
+            .sample_offset(true)
+            .reverse(true);
+        let mut expected = b"/composition/tc\0,iiiihF\0".to_vec();
+        for value in [1i32, 2, 3, 5] {
+            expected.extend_from_slice(&value.to_be_bytes());
+        }
+        expected.extend_from_slice(&1602i64.to_be_bytes());
+        assert_eq!(config.encode(frame), expected);
+
+        let ltc = encode_ltc(
+            48_000,
+            FrameRate::Fps25,
+            "10:00:00:10",
+            2,
+            Direction::Reverse,
+        );
+        let frame = &decode(ltc)[0];
+        assert_eq!(
+            OscConfig::new("/r").reverse(true).encode(frame),
+            b"/r\0\0,sT\x0010:00:00:10\0"
+        );
+    }
+
+    #[test]
+    fn osc_config_validates_address_pattern() {
+        assert!(OscConfig::new("/tc").is_valid());
+        assert!(OscConfig::new("/layer*/tc").is_valid());
+        assert!(!OscConfig::new("tc").is_valid());
+        assert!(!OscConfig::new("/time code").is_valid());
+        assert!(!OscConfig::new("/tc#1").is_valid());
+        assert!(!OscConfig::new("/tc,s").is_valid());
+        assert!(!OscConfig::new("/tcé").is_valid());
+        assert_eq!(
+            OscSender::new("127.0.0.1:9000", OscConfig::new("tc"))
+                .unwrap_err()
+                .kind(),
+            io::ErrorKind::InvalidInput
+        );
+    }
+
+    #[test]
+    fn osc_sender_sends_decoded_frames_over_loopback() {
+        let receiver = UdpSocket::bind("127.0.0.1:0").unwrap();
+        receiver
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+        let config = OscConfig::new("/ltc").sample_offset(true);
+        let sender = OscSender::new(receiver.local_addr().unwrap(), config.clone()).unwrap();
+
+        let ltc = encode_ltc(
+            48_000,
+            FrameRate::Fps25,
+            "23:59:59:23",
+            4,
+            Direction::Forward,
+        );
+        let frames = decode(ltc);
+        assert_eq!(frames.len(), 3);
+        for frame in &frames {
+            sender.send_frame(frame).unwrap();
+        }
+
+        let mut buffer = [0; 64];
+        for frame in &frames {
+            let (len, source) = receiver.recv_from(&mut buffer).unwrap();
+            assert_eq!(source.port(), sender.socket().local_addr().unwrap().port());
+            assert_eq!(buffer[..len], config.encode(frame));
+        }
+        assert_eq!(&buffer[12..24], b"00:00:00:00\0");
+    }
+}
@@ -0,0 +1,1056 @@
+// x42ltc: src/timecode.rs
+//
+// Copyright 2019-2020 Johannes Maibaum <jmaibaum@gmail.com>
+//
+// This file is free software; you can redistribute it and/or modify it
+// under the terms of the GNU Lesser General Public License as
+// published by the Free Software Foundation; either version 3 of the
+// License, or (at your option) any later version.
+//
+// This file is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public
+// License along with this program.  If not, see <http://www.gnu.org/licenses/>.
+//
+// SPDX-License-Identifier: LGPL-3.0-or-later
+use core::cmp::Ordering;
+use core::fmt;
+use core::hash::{Hash, Hasher};
+use core::ops::{Add, Sub};
+use core::str::FromStr;
+
+#[cfg(feature = "libltc")]
+use x42ltc_sys::SMPTETimecode;
+
+use crate::{Error, FrameRate};
+
+/// The highest frame number a [`Timecode`] can hold, plus one.
+const MAX_FRAMES: u8 = 60;
+
+/// The number of days from 1967-01-01 to 2067-01-01, after which two-digit years repeat.
+const DAYS_PER_CENTURY: i64 = 36_525;
+
+/// A calendar date as carried in the LTC user bits (SMPTE 309).
+///
+/// LTC only transports a two-digit year. Years `00..=66` are interpreted as `2000..=2066`, years
+/// `67..=99` as `1967..=1999`, which is the same convention libltc's `ltcdump` uses.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Date {
+    year: u16,
+    month: u8,
+    day: u8,
+}
+
+impl Date {
+    /// Create a new date.
+    ///
+    /// Returns `Error::ValueOutOfRange` if `year` is outside of `1967..=2066`, or if `month` and
+    /// `day` do not form a valid day of that year.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let date = x42ltc::Date::new(2008, 12, 31).unwrap();
+    /// assert_eq!(date.to_string(), "2008-12-31");
+    /// assert!(x42ltc::Date::new(2009, 2, 29).is_err());
+    /// ```
+    pub fn new(year: u16, month: u8, day: u8) -> Result<Date, Error> {
+        if !(1967..=2066).contains(&year)
+            || !(1..=12).contains(&month)
+            || day == 0
+            || day > days_in_month(year, month)
+        {
+            return Err(Error::ValueOutOfRange);
+        }
+        Ok(Date { year, month, day })
+    }
+
+    /// The full (four-digit) year.
+    pub fn year(&self) -> u16 {
+        self.year
+    }
+
+    /// The month, `1..=12`.
+    pub fn month(&self) -> u8 {
+        self.month
+    }
+
+    /// The day of the month, `1..=31`.
+    pub fn day(&self) -> u8 {
+        self.day
+    }
+
+    /// Create a date from the two-digit year representation used by libltc.
+    pub(crate) fn from_two_digit_year(years: u8, month: u8, day: u8) -> Result<Date, Error> {
+        if years > 99 {
+            return Err(Error::ValueOutOfRange);
+        }
+        let year = if years < 67 { 2000 } else { 1900 } + u16::from(years);
+        Date::new(year, month, day)
+    }
+
+    /// The two-digit year representation used by libltc.
+    pub(crate) fn two_digit_year(&self) -> u8 {
+        (self.year % 100) as u8
+    }
+
+    /// The number of days since 1967-01-01.
+    fn day_index(&self) -> i64 {
+        let years: i64 = (1967..self.year)
+            .map(|year| i64::from(days_in_year(year)))
+            .sum();
+        let months: i64 = (1..self.month)
+            .map(|month| i64::from(days_in_month(self.year, month)))
+            .sum();
+        years + months + i64::from(self.day) - 1
+    }
+
+    /// The date `days` days after this one, wrapping around within `1967..=2066` like the
+    /// two-digit year does.
+    fn add_days(&self, days: i64) -> Date {
+        let mut index = (self.day_index() + days.rem_euclid(DAYS_PER_CENTURY)) % DAYS_PER_CENTURY;
+        let mut year = 1967;
+        while index >= i64::from(days_in_year(year)) {
+            index -= i64::from(days_in_year(year));
+            year += 1;
+        }
+        let mut month = 1;
+        while index >= i64::from(days_in_month(year, month)) {
+            index -= i64::from(days_in_month(year, month));
+            month += 1;
+        }
+        Date {
+            year,
+            month,
+            day: index as u8 + 1,
+        }
+    }
+}
+
+impl fmt::Display for Date {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:04}-{:02}-{:02}", self.year, self.month, self.day)
+    }
+}
+
+impl FromStr for Date {
+    type Err = Error;
+
+    /// Parses the `YYYY-MM-DD` notation produced by `Display`.
+    fn from_str(s: &str) -> Result<Date, Error> {
+        let bytes = s.as_bytes();
+        if bytes.len() != 10 || bytes[4] != b'-' || bytes[7] != b'-' {
+            return Err(Error::ParseFailed);
+        }
+        let field = |range: core::ops::Range<usize>| -> Result<u16, Error> {
+            let digits = &s[range];
+            if !digits.bytes().all(|b| b.is_ascii_digit()) {
+                return Err(Error::ParseFailed);
+            }
+            digits.parse().map_err(|_| Error::ParseFailed)
+        };
+        Date::new(field(0..4)?, field(5..7)? as u8, field(8..10)? as u8)
+    }
+}
+
+fn days_in_year(year: u16) -> u16 {
+    if days_in_month(year, 2) == 29 {
+        366
+    } else {
+        365
+    }
+}
+
+fn days_in_month(year: u16, month: u8) -> u8 {
+    match month {
+        2 if year.is_multiple_of(4) && (!year.is_multiple_of(100) || year.is_multiple_of(400)) => {
+            29
+        }
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// A timezone as an offset from UTC, as carried in the LTC user bits (SMPTE 309).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct TimezoneOffset {
+    minutes: i16,
+}
+
+impl TimezoneOffset {
+    /// Coordinated Universal Time.
+    pub const UTC: TimezoneOffset = TimezoneOffset { minutes: 0 };
+
+    /// Create a timezone offset from a number of minutes east of UTC.
+    ///
+    /// Returns `Error::ValueOutOfRange` unless the offset is strictly less than 24 hours.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let offset = x42ltc::TimezoneOffset::from_minutes(-330).unwrap();
+    /// assert_eq!(offset.to_string(), "-0530");
+    /// ```
+    pub fn from_minutes(minutes: i16) -> Result<TimezoneOffset, Error> {
+        if minutes.abs() >= 24 * 60 {
+            return Err(Error::ValueOutOfRange);
+        }
+        Ok(TimezoneOffset { minutes })
+    }
+
+    /// The number of minutes east of UTC.
+    pub fn minutes(&self) -> i16 {
+        self.minutes
+    }
+
+    /// The timezone with the given SMPTE 309 code, as stored in the last two user bits nibbles.
+    pub(crate) fn from_smpte_code(code: u8) -> Option<TimezoneOffset> {
+        SMPTE_TIMEZONES
+            .iter()
+            .find(|&&(c, _)| c == code)
+            .map(|&(_, minutes)| TimezoneOffset { minutes })
+    }
+
+    /// The SMPTE 309 code of this timezone, if it has one.
+    #[cfg(feature = "pure-rust")]
+    pub(crate) fn smpte_code(&self) -> Option<u8> {
+        SMPTE_TIMEZONES
+            .iter()
+            .find(|&&(_, minutes)| minutes == self.minutes)
+            .map(|&(code, _)| code)
+    }
+}
+
+/// The SMPTE 309 timezone codes and their offsets in minutes, as in libltc's `smpte_timezones`.
+const SMPTE_TIMEZONES: [(u8, i16); 51] = [
+    (0x00, 0),
+    (0x01, -60),
+    (0x02, -120),
+    (0x03, -180),
+    (0x04, -240),
+    (0x05, -300),
+    (0x06, -360),
+    (0x07, -420),
+    (0x08, -480),
+    (0x09, -540),
+    (0x0A, -30),
+    (0x0B, -90),
+    (0x0C, -150),
+    (0x0D, -210),
+    (0x0E, -270),
+    (0x0F, -330),
+    (0x10, -600),
+    (0x11, -660),
+    (0x12, -720),
+    (0x13, 780),
+    (0x14, 720),
+    (0x15, 660),
+    (0x16, 600),
+    (0x17, 540),
+    (0x18, 480),
+    (0x19, 420),
+    (0x1A, -390),
+    (0x1B, -450),
+    (0x1C, -510),
+    (0x1D, -570),
+    (0x1E, -630),
+    (0x1F, -690),
+    (0x20, 360),
+    (0x21, 300),
+    (0x22, 240),
+    (0x23, 180),
+    (0x24, 120),
+    (0x25, 60),
+    (0x2A, 690),
+    (0x2B, 630),
+    (0x2C, 570),
+    (0x2D, 510),
+    (0x2E, 450),
+    (0x2F, 390),
+    (0x32, 765),
+    (0x3A, 330),
+    (0x3B, 270),
+    (0x3C, 210),
+    (0x3D, 150),
+    (0x3E, 90),
+    (0x3F, 30),
+];
+
+impl fmt::Display for TimezoneOffset {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let sign = if self.minutes < 0 { '-' } else { '+' };
+        let minutes = self.minutes.abs();
+        write!(f, "{}{:02}{:02}", sign, minutes / 60, minutes % 60)
+    }
+}
+
+impl FromStr for TimezoneOffset {
+    type Err = Error;
+
+    /// Parses the `"+HHMM"` / `"-HHMM"` notation used by libltc.
+    fn from_str(s: &str) -> Result<TimezoneOffset, Error> {
+        let bytes = s.as_bytes();
+        if bytes.len() != 5 || !bytes[1..].iter().all(u8::is_ascii_digit) {
+            return Err(Error::ParseFailed);
+        }
+        let sign = match bytes[0] {
+            b'+' => 1,
+            b'-' => -1,
+            _ => return Err(Error::ParseFailed),
+        };
+        let hours: i16 = s[1..3].parse().map_err(|_| Error::ParseFailed)?;
+        let minutes: i16 = s[3..5].parse().map_err(|_| Error::ParseFailed)?;
+        if minutes >= 60 {
+            return Err(Error::ValueOutOfRange);
+        }
+        TimezoneOffset::from_minutes(sign * (hours * 60 + minutes))
+    }
+}
+
+/// An SMPTE timecode, optionally with the date and timezone carried in the LTC user bits.
+///
+/// Timecodes are compared by date, time of day and drop-frame flag only, so timecodes which
+/// differ just in their timezone or frame rate are equal.  They are ordered chronologically: by
+/// date first (timecodes without a date sort before those with one), then by time of day.
+///
+/// # Example
+///
+/// ```
+/// let tc: x42ltc::Timecode = "01:02:03;04".parse().unwrap();
+/// assert_eq!(tc.hours(), 1);
+/// assert_eq!(tc.frame(), 4);
+/// assert!(tc.is_drop_frame());
+/// assert_eq!(tc.to_string(), "01:02:03;04");
+/// ```
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Timecode {
+    date: Option<Date>,
+    hours: u8,
+    mins: u8,
+    secs: u8,
+    frame: u8,
+    drop_frame: bool,
+    timezone: Option<TimezoneOffset>,
+    frame_rate: Option<FrameRate>,
+}
+
+impl Timecode {
+    /// Create a new non-drop-frame timecode without date and timezone.
+    ///
+    /// Returns `Error::ValueOutOfRange` if `hours` > 23, `mins` > 59, `secs` > 59 or `frame` > 59.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let tc = x42ltc::Timecode::new(23, 59, 59, 24).unwrap();
+    /// assert_eq!(tc.to_string(), "23:59:59:24");
+    /// assert!(x42ltc::Timecode::new(24, 0, 0, 0).is_err());
+    /// ```
+    pub fn new(hours: u8, mins: u8, secs: u8, frame: u8) -> Result<Timecode, Error> {
+        if hours >= 24 || mins >= 60 || secs >= 60 || frame >= MAX_FRAMES {
+            return Err(Error::ValueOutOfRange);
+        }
+        Ok(Timecode {
+            hours,
+            mins,
+            secs,
+            frame,
+            ..Timecode::default()
+        })
+    }
+
+    /// Returns this timecode with the given date attached.
+    pub fn with_date(mut self, date: Date) -> Timecode {
+        self.date = Some(date);
+        self
+    }
+
+    /// Returns this timecode with the given timezone attached.
+    pub fn with_timezone(mut self, timezone: TimezoneOffset) -> Timecode {
+        self.timezone = Some(timezone);
+        self
+    }
+
+    /// Returns this timecode marked as drop-frame or non-drop-frame timecode.
+    pub fn with_drop_frame(mut self, drop_frame: bool) -> Timecode {
+        self.drop_frame = drop_frame;
+        self
+    }
+
+    /// Returns this timecode counting frames at the given rate, which is required for
+    /// [`.checked_add()`](#method.checked_add) and the other arithmetic methods.  It is marked as
+    /// drop-frame timecode if the rate is.
+    pub fn with_frame_rate(mut self, frame_rate: FrameRate) -> Timecode {
+        self.frame_rate = Some(frame_rate);
+        self.drop_frame = frame_rate.is_drop_frame();
+        self
+    }
+
+    /// The hours, `0..=23`.
+    pub fn hours(&self) -> u8 {
+        self.hours
+    }
+
+    /// The minutes, `0..=59`.
+    pub fn mins(&self) -> u8 {
+        self.mins
+    }
+
+    /// The seconds, `0..=59`.
+    pub fn secs(&self) -> u8 {
+        self.secs
+    }
+
+    /// The frame number within the second.
+    pub fn frame(&self) -> u8 {
+        self.frame
+    }
+
+    /// The date carried in the user bits, if any.
+    pub fn date(&self) -> Option<Date> {
+        self.date
+    }
+
+    /// The timezone carried in the user bits, if any.
+    pub fn timezone(&self) -> Option<TimezoneOffset> {
+        self.timezone
+    }
+
+    /// Whether this is drop-frame timecode.
+    pub fn is_drop_frame(&self) -> bool {
+        self.drop_frame
+    }
+
+    /// The frame rate set with [`.with_frame_rate()`](#method.with_frame_rate), if any.
+    pub fn frame_rate(&self) -> Option<FrameRate> {
+        self.frame_rate
+    }
+
+    /// The number of frames since 00:00:00:00 at `frame_rate`, skipping the dropped frame
+    /// numbers at drop-frame rates.  The date is ignored.
+    ///
+    /// Returns `Error::ValueOutOfRange` if the frame number is too large for `frame_rate`, or is
+    /// one of the dropped ones.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use x42ltc::{FrameRate, Timecode};
+    ///
+    /// let tc: Timecode = "00:10:00;00".parse().unwrap();
+    /// assert_eq!(tc.to_frame_number(FrameRate::Fps29_97Df).unwrap(), 17_982);
+    /// assert_eq!(tc.to_frame_number(FrameRate::Fps30).unwrap(), 18_000);
+    ///
+    /// let dropped: Timecode = "00:01:00;00".parse().unwrap();
+    /// assert!(dropped.to_frame_number(FrameRate::Fps29_97Df).is_err());
+    /// ```
+    pub fn to_frame_number(&self, frame_rate: FrameRate) -> Result<u32, Error> {
+        let fps = frame_rate.nominal_fps();
+        let dropped = frame_rate.dropped_frames();
+        let mins = 60 * u32::from(self.hours) + u32::from(self.mins);
+        let frame = u32::from(self.frame);
+        if frame >= fps || (self.secs == 0 && mins % 10 != 0 && frame < dropped) {
+            return Err(Error::ValueOutOfRange);
+        }
+        let secs = 60 * mins + u32::from(self.secs);
+        Ok(fps * secs + frame - dropped * (mins - mins / 10))
+    }
+
+    /// The timecode `frame_number` frames after 00:00:00:00 at `frame_rate`, wrapping around
+    /// after 24 hours.  The inverse of [`.to_frame_number()`](#method.to_frame_number).
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use x42ltc::{FrameRate, Timecode};
+    ///
+    /// let tc = Timecode::from_frame_number(1800, FrameRate::Fps29_97Df);
+    /// assert_eq!(tc.to_string(), "00:01:00;02");
+    /// assert_eq!(tc.frame_rate(), Some(FrameRate::Fps29_97Df));
+    /// ```
+    pub fn from_frame_number(frame_number: u32, frame_rate: FrameRate) -> Timecode {
+        let fps = frame_rate.nominal_fps();
+        let dropped = frame_rate.dropped_frames();
+        let mut frames = frame_number % frame_rate.frames_per_day();
+        if dropped > 0 {
+            // Add the skipped frame numbers back: 9 minutes with drops per 10 minutes, plus the
+            // minutes with drops in the current block of 10.
+            let frames_per_10_mins = 600 * fps - 9 * dropped;
+            let frames_per_min = 60 * fps - dropped;
+            let rest = frames % frames_per_10_mins;
+            frames += dropped
+                * (9 * (frames / frames_per_10_mins)
+                    + rest.saturating_sub(dropped) / frames_per_min);
+        }
+        let secs = frames / fps;
+        Timecode {
+            hours: (secs / 3600) as u8,
+            mins: (secs / 60 % 60) as u8,
+            secs: (secs % 60) as u8,
+            frame: (frames % fps) as u8,
+            ..Timecode::default()
+        }
+        .with_frame_rate(frame_rate)
+    }
+
+    /// The time in seconds since 00:00:00:00 at `frame_rate`, i.e. real time for fractional
+    /// rates, not the time of day the timecode reads.
+    ///
+    /// Returns `Error::ValueOutOfRange` like [`.to_frame_number()`](#method.to_frame_number).
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use x42ltc::{FrameRate, Timecode};
+    ///
+    /// let tc: Timecode = "01:00:00;00".parse().unwrap();
+    /// assert_eq!(tc.to_seconds(FrameRate::Fps29_97Df).unwrap(), 107_892.0 * 1001.0 / 30_000.0);
+    /// assert_eq!(tc.to_seconds(FrameRate::Fps30).unwrap(), 3600.0);
+    /// ```
+    pub fn to_seconds(&self, frame_rate: FrameRate) -> Result<f64, Error> {
+        let frame_number = self.to_frame_number(frame_rate)?;
+        Ok(
+            f64::from(frame_number) * f64::from(frame_rate.denominator())
+                / f64::from(frame_rate.numerator()),
+        )
+    }
+
+    /// The position of the first sample of this frame in a stream starting at 00:00:00:00 at
+    /// `frame_rate` and `sample_rate`, rounded to the nearest sample like the encoder does.
+    ///
+    /// Returns `Error::ValueOutOfRange` like [`.to_frame_number()`](#method.to_frame_number).
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use x42ltc::{FrameRate, Timecode};
+    ///
+    /// let tc: Timecode = "00:00:01:00".parse().unwrap();
+    /// assert_eq!(tc.to_sample_position(FrameRate::Fps25, 48_000).unwrap(), 48_000);
+    /// assert_eq!(tc.to_sample_position(FrameRate::Fps29_97, 48_000).unwrap(), 48_048);
+    /// ```
+    pub fn to_sample_position(
+        &self,
+        frame_rate: FrameRate,
+        sample_rate: u32,
+    ) -> Result<u64, Error> {
+        let frame_number = u64::from(self.to_frame_number(frame_rate)?);
+        let numerator = u64::from(frame_rate.numerator());
+        let samples = frame_number * u64::from(sample_rate) * u64::from(frame_rate.denominator());
+        Ok((2 * samples + numerator) / (2 * numerator))
+    }
+
+    /// The timecode `frames` frames after this one, counting frames at the rate set with
+    /// [`.with_frame_rate()`](#method.with_frame_rate) and skipping the dropped frame numbers at
+    /// drop-frame rates.  The time of day wraps around after 24 hours, moving the date, if any, to
+    /// the next or previous day.
+    ///
+    /// Returns `Error::ValueOutOfRange` if no frame rate is set, or the timecode is not valid at
+    /// it, see [`.to_frame_number()`](#method.to_frame_number).
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use x42ltc::{Date, FrameCount, FrameRate, Timecode};
+    ///
+    /// let tc = Timecode::new(23, 59, 59, 29)
+    ///     .unwrap()
+    ///     .with_date(Date::new(2020, 2, 28).unwrap())
+    ///     .with_frame_rate(FrameRate::Fps29_97Df);
+    /// let next = tc.checked_add(FrameCount(1)).unwrap();
+    /// assert_eq!(next.to_string(), "00:00:00;00");
+    /// assert_eq!(next.date(), Some(Date::new(2020, 2, 29).unwrap()));
+    ///
+    /// let later = next.checked_add(FrameCount(1800)).unwrap();
+    /// assert_eq!(later.to_string(), "00:01:00;02");
+    ///
+    /// // Parsed timecodes do not know their frame rate.
+    /// let parsed: Timecode = "01:00:00;00".parse().unwrap();
+    /// assert!(parsed.checked_add(FrameCount(1)).is_err());
+    /// ```
+    pub fn checked_add(&self, frames: FrameCount) -> Result<Timecode, Error> {
+        let (frame_rate, frame_number) = self.frame_number_for_arithmetic()?;
+        let frames_per_day = i64::from(frame_rate.frames_per_day());
+        let offset = frames.0;
+        let frames = i64::from(frame_number) + offset.rem_euclid(frames_per_day);
+        let days = offset.div_euclid(frames_per_day) + frames / frames_per_day;
+
+        let mut tc = Timecode::from_frame_number((frames % frames_per_day) as u32, frame_rate);
+        tc.date = self.date.map(|date| date.add_days(days));
+        tc.timezone = self.timezone;
+        Ok(tc)
+    }
+
+    /// The timecode `frames` frames before this one.  The counterpart of
+    /// [`.checked_add()`](#method.checked_add).
+    pub fn checked_sub(&self, frames: FrameCount) -> Result<Timecode, Error> {
+        let frames = frames.0.checked_neg().ok_or(Error::ValueOutOfRange)?;
+        self.checked_add(FrameCount(frames))
+    }
+
+    /// The number of frames from `earlier` to this timecode, counting frames at the rate of this
+    /// timecode.  Whole days are included only if both timecodes carry a date.
+    ///
+    /// Returns `Error::ValueOutOfRange` if this timecode has no frame rate, or either timecode is
+    /// not valid at it.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use x42ltc::{FrameCount, FrameRate, Timecode};
+    ///
+    /// let start: Timecode = "00:00:59;29".parse().unwrap();
+    /// let start = start.with_frame_rate(FrameRate::Fps29_97Df);
+    /// let end: Timecode = "00:01:00;02".parse().unwrap();
+    /// assert!(end.frames_since(&start).is_err());
+    ///
+    /// let end = end.with_frame_rate(FrameRate::Fps29_97Df);
+    /// assert_eq!(end.frames_since(&start).unwrap(), FrameCount(1));
+    /// assert_eq!(start.frames_since(&end).unwrap(), FrameCount(-1));
+    /// ```
+    pub fn frames_since(&self, earlier: &Timecode) -> Result<FrameCount, Error> {
+        let (frame_rate, frame_number) = self.frame_number_for_arithmetic()?;
+        let earlier_frame_number = earlier.to_frame_number(frame_rate)?;
+        let days = match (self.date, earlier.date) {
+            (Some(date), Some(earlier_date)) => date.day_index() - earlier_date.day_index(),
+            _ => 0,
+        };
+        Ok(FrameCount(
+            days * i64::from(frame_rate.frames_per_day()) + i64::from(frame_number)
+                - i64::from(earlier_frame_number),
+        ))
+    }
+
+    /// The frame rate and frame number used for arithmetic.
+    ///
+    /// Returns `Error::ValueOutOfRange` if no frame rate is set, or the timecode is not valid at
+    /// it.
+    fn frame_number_for_arithmetic(&self) -> Result<(FrameRate, u32), Error> {
+        let frame_rate = self.frame_rate.ok_or(Error::ValueOutOfRange)?;
+        Ok((frame_rate, self.to_frame_number(frame_rate)?))
+    }
+
+    /// The fields timecodes are compared and hashed by, in chronological order.
+    fn key(&self) -> (Option<Date>, u8, u8, u8, u8, bool) {
+        (
+            self.date,
+            self.hours,
+            self.mins,
+            self.secs,
+            self.frame,
+            self.drop_frame,
+        )
+    }
+}
+
+impl PartialEq for Timecode {
+    fn eq(&self, other: &Timecode) -> bool {
+        self.key() == other.key()
+    }
+}
+
+impl Eq for Timecode {}
+
+impl PartialOrd for Timecode {
+    fn partial_cmp(&self, other: &Timecode) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Timecode {
+    fn cmp(&self, other: &Timecode) -> Ordering {
+        self.key().cmp(&other.key())
+    }
+}
+
+impl Hash for Timecode {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.key().hash(state)
+    }
+}
+
+/// A signed number of frames, as added to and subtracted from a [`Timecode`] with
+/// [`Timecode::checked_add()`] and [`Timecode::checked_sub()`] or the `+` and `-` operators, and
+/// as returned by [`Timecode::frames_since()`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct FrameCount(pub i64);
+
+impl Add<FrameCount> for Timecode {
+    type Output = Result<Timecode, Error>;
+
+    /// Like [`Timecode::checked_add()`].
+    fn add(self, frames: FrameCount) -> Result<Timecode, Error> {
+        self.checked_add(frames)
+    }
+}
+
+impl Sub<FrameCount> for Timecode {
+    type Output = Result<Timecode, Error>;
+
+    /// Like [`Timecode::checked_sub()`].
+    fn sub(self, frames: FrameCount) -> Result<Timecode, Error> {
+        self.checked_sub(frames)
+    }
+}
+
+impl Sub for Timecode {
+    type Output = Result<FrameCount, Error>;
+
+    /// Like [`Timecode::frames_since()`].
+    fn sub(self, earlier: Timecode) -> Result<FrameCount, Error> {
+        self.frames_since(&earlier)
+    }
+}
+
+impl fmt::Display for Timecode {
+    /// Formats the time of day as `HH:MM:SS:FF`, or `HH:MM:SS;FF` for drop-frame timecode.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:02}:{:02}:{:02}{}{:02}",
+            self.hours,
+            self.mins,
+            self.secs,
+            if self.drop_frame { ';' } else { ':' },
+            self.frame
+        )
+    }
+}
+
+impl FromStr for Timecode {
+    type Err = Error;
+
+    /// Parses `HH:MM:SS:FF`, or `HH:MM:SS;FF` (also `HH:MM:SS.FF`) for drop-frame timecode.
+    fn from_str(s: &str) -> Result<Timecode, Error> {
+        let bytes = s.as_bytes();
+        if bytes.len() != 11 || bytes[2] != b':' || bytes[5] != b':' {
+            return Err(Error::ParseFailed);
+        }
+        let drop_frame = match bytes[8] {
+            b':' => false,
+            b';' | b'.' => true,
+            _ => return Err(Error::ParseFailed),
+        };
+        let field = |start: usize| -> Result<u8, Error> {
+            let digits = &s[start..start + 2];
+            if !digits.bytes().all(|b| b.is_ascii_digit()) {
+                return Err(Error::ParseFailed);
+            }
+            digits.parse().map_err(|_| Error::ParseFailed)
+        };
+        Ok(Timecode::new(field(0)?, field(3)?, field(6)?, field(9)?)?.with_drop_frame(drop_frame))
+    }
+}
+
+#[cfg(feature = "libltc")]
+impl TryFrom<SMPTETimecode> for Timecode {
+    type Error = Error;
+
+    /// Convert from libltc's representation.
+    ///
+    /// An all-zero date and an empty timezone string are mapped to `None`.  Since `SMPTETimecode`
+    /// does not carry the drop-frame flag, the result is always non-drop-frame timecode.
+    fn try_from(stime: SMPTETimecode) -> Result<Timecode, Error> {
+        let mut tc = Timecode::new(stime.hours, stime.mins, stime.secs, stime.frame)?;
+        if (stime.years, stime.months, stime.days) != (0, 0, 0) {
+            tc.date = Some(Date::from_two_digit_year(
+                stime.years,
+                stime.months,
+                stime.days,
+            )?);
+        }
+        tc.timezone = timezone_from_c(&stime.timezone)?;
+        Ok(tc)
+    }
+}
+
+#[cfg(feature = "libltc")]
+impl From<Timecode> for SMPTETimecode {
+    fn from(tc: Timecode) -> SMPTETimecode {
+        let mut stime = SMPTETimecode {
+            hours: tc.hours,
+            mins: tc.mins,
+            secs: tc.secs,
+            frame: tc.frame,
+            ..SMPTETimecode::default()
+        };
+        if let Some(date) = tc.date {
+            stime.years = date.two_digit_year();
+            stime.months = date.month;
+            stime.days = date.day;
+        }
+        if let Some(timezone) = tc.timezone {
+            for (dst, src) in stime.timezone.iter_mut().zip(timezone.to_string().bytes()) {
+                *dst = src as _;
+            }
+        }
+        stime
+    }
+}
+
+/// Parse the NUL-terminated timezone string of an `SMPTETimecode`.
+#[cfg(feature = "libltc")]
+fn timezone_from_c(timezone: &[std::os::raw::c_char; 6]) -> Result<Option<TimezoneOffset>, Error> {
+    let len = timezone.iter().position(|&c| c == 0).unwrap_or(6);
+    if len == 0 {
+        return Ok(None);
+    }
+    let bytes: Vec<u8> = timezone[..len].iter().map(|&c| c as u8).collect();
+    let s = std::str::from_utf8(&bytes).map_err(|_| Error::ParseFailed)?;
+    s.parse().map(Some)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{BgFlags, LtcFrame};
+
+    #[test]
+    #[cfg(feature = "libltc")]
+    fn timecode_round_trips_through_smpte_timecode() {
+        let stime = SMPTETimecode {
+            timezone: [43, 48, 49, 48, 48, 0], // b"+0100\0"
+            years: 8,
+            months: 12,
+            days: 31,
+            hours: 23,
+            mins: 59,
+            secs: 59,
+            frame: 24,
+        };
+
+        let tc = Timecode::try_from(stime).unwrap();
+        assert_eq!(tc.date(), Some(Date::new(2008, 12, 31).unwrap()));
+        assert_eq!(tc.timezone().unwrap().minutes(), 60);
+        assert_eq!(SMPTETimecode::from(tc), stime);
+
+        let stime = SMPTETimecode::default();
+        let tc = Timecode::try_from(stime).unwrap();
+        assert_eq!(tc.date(), None);
+        assert_eq!(tc.timezone(), None);
+        assert_eq!(SMPTETimecode::from(tc), stime);
+    }
+
+    #[test]
+    fn timecode_parsing_rejects_malformed_input() {
+        assert!("1:02:03:04".parse::<Timecode>().is_err());
+        assert!("01:02:03-04".parse::<Timecode>().is_err());
+        assert!("01:60:03:04".parse::<Timecode>().is_err());
+        assert!("+1:02:03:04".parse::<Timecode>().is_err());
+        assert!("+2500".parse::<TimezoneOffset>().is_err());
+        assert_eq!(
+            "2008-12-31".parse::<Date>().unwrap(),
+            Date::new(2008, 12, 31).unwrap()
+        );
+        assert!("2008-12-32".parse::<Date>().is_err());
+        assert!("08-12-31".parse::<Date>().is_err());
+    }
+
+    #[test]
+    fn timecodes_are_ordered_chronologically() {
+        let early = Timecode::new(0, 0, 59, 24).unwrap();
+        let late = Timecode::new(0, 1, 0, 0).unwrap();
+        assert!(early < late);
+
+        let tomorrow = early.with_date(Date::new(2020, 1, 2).unwrap());
+        let today = late.with_date(Date::new(2020, 1, 1).unwrap());
+        assert!(today < tomorrow);
+    }
+
+    #[test]
+    fn frame_numbers_match_ltc_frame_increment() {
+        // LTC can only carry frame numbers up to 39, so this covers the rates up to 30 fps.
+        for frame_rate in FrameRate::ALL.into_iter().filter(|r| r.nominal_fps() <= 30) {
+            let mut frame = LtcFrame::new();
+            frame.set_dfbit(frame_rate.is_drop_frame());
+            for frame_number in 0..frame_rate.frames_per_day() {
+                let tc = frame.timecode(false).unwrap().with_frame_rate(frame_rate);
+                assert_eq!(tc.to_frame_number(frame_rate).unwrap(), frame_number);
+                assert_eq!(Timecode::from_frame_number(frame_number, frame_rate), tc);
+                assert_eq!(
+                    frame.increment(frame_rate, BgFlags::NO_PARITY),
+                    frame_number + 1 == frame_rate.frames_per_day()
+                );
+            }
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "libltc")]
+    fn frame_numbers_match_libltc_frame_increment() {
+        // Compare against libltc itself, not the port `LtcFrame::increment()` may use.
+        for frame_rate in FrameRate::ALL.into_iter().filter(|r| r.nominal_fps() <= 30) {
+            let mut frame = LtcFrame::new();
+            frame.set_dfbit(frame_rate.is_drop_frame());
+            let mut raw = frame.to_ffi();
+            for frame_number in 0..frame_rate.frames_per_day() {
+                let tc = LtcFrame::from_ffi(&raw).timecode(false).unwrap();
+                assert_eq!(
+                    tc.to_frame_number(frame_rate).unwrap(),
+                    frame_number,
+                    "{} at {} fps",
+                    tc,
+                    frame_rate
+                );
+                let wrapped = unsafe {
+                    x42ltc_sys::ltc_frame_increment(
+                        &mut raw,
+                        frame_rate.nominal_fps() as i32,
+                        frame_rate.tv_standard(),
+                        BgFlags::NO_PARITY.bits() as i32,
+                    )
+                };
+                assert_eq!(
+                    wrapped == 1,
+                    frame_number + 1 == frame_rate.frames_per_day()
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn frame_numbers_round_trip_at_59_94_drop_frame() {
+        let frame_rate = FrameRate::Fps59_94Df;
+        assert_eq!(frame_rate.frames_per_day(), 5_178_816);
+        let mut previous = Timecode::from_frame_number(0, frame_rate);
+        for frame_number in 1..frame_rate.frames_per_day() {
+            let tc = Timecode::from_frame_number(frame_number, frame_rate);
+            assert_eq!(tc.to_frame_number(frame_rate).unwrap(), frame_number);
+            assert!(tc > previous);
+            previous = tc;
+        }
+        assert_eq!(previous.to_string(), "23:59:59;59");
+
+        let tc: Timecode = "00:01:00;04".parse().unwrap();
+        assert_eq!(tc.to_frame_number(frame_rate).unwrap(), 3600);
+        assert!("00:01:00;03"
+            .parse::<Timecode>()
+            .unwrap()
+            .to_frame_number(frame_rate)
+            .is_err());
+    }
+
+    #[test]
+    fn timecode_operators_match_checked_methods() {
+        let tc = Timecode::new(10, 0, 0, 0)
+            .unwrap()
+            .with_frame_rate(FrameRate::Fps29_97Df);
+        let later = (tc + FrameCount(1800)).unwrap();
+        assert_eq!(later, tc.checked_add(FrameCount(1800)).unwrap());
+        assert_eq!((later - FrameCount(1800)).unwrap(), tc);
+        assert_eq!((later - tc).unwrap(), FrameCount(1800));
+        assert_eq!((tc - later).unwrap(), FrameCount(-1800));
+        assert!((Timecode::default() + FrameCount(1)).is_err());
+    }
+
+    #[test]
+    fn timecodes_compare_by_date_and_time_of_day() {
+        let tc = Timecode::new(10, 0, 0, 0).unwrap();
+        let other = tc
+            .with_timezone(TimezoneOffset::UTC)
+            .with_frame_rate(FrameRate::Fps25);
+        assert_eq!(tc, other);
+        assert_eq!(tc.cmp(&other), Ordering::Equal);
+        assert_ne!(tc, tc.with_drop_frame(true));
+
+        let date = Date::new(2000, 1, 1).unwrap();
+        assert!(tc < Timecode::new(10, 0, 0, 1).unwrap());
+        assert!(tc.with_date(date) > Timecode::new(23, 0, 0, 0).unwrap());
+        assert!(
+            tc.with_date(date)
+                < Timecode::new(0, 0, 0, 0)
+                    .unwrap()
+                    .with_date(date.add_days(1))
+        );
+    }
+
+    #[test]
+    fn timecode_arithmetic_wraps_around_midnight() {
+        let tc = Timecode::new(0, 0, 0, 0)
+            .unwrap()
+            .with_date(Date::new(2000, 1, 1).unwrap())
+            .with_frame_rate(FrameRate::Fps25);
+        let before = tc.checked_sub(FrameCount(1)).unwrap();
+        assert_eq!(before.to_string(), "23:59:59:24");
+        assert_eq!(before.date(), Some(Date::new(1999, 12, 31).unwrap()));
+        assert_eq!(tc.frames_since(&before).unwrap(), FrameCount(1));
+
+        let far = tc
+            .checked_add(FrameCount(10 * 24 * 60 * 60 * 25 + 3))
+            .unwrap();
+        assert_eq!(far.to_string(), "00:00:00:03");
+        assert_eq!(far.date(), Some(Date::new(2000, 1, 11).unwrap()));
+        assert_eq!(
+            far.frames_since(&tc).unwrap(),
+            FrameCount(10 * 24 * 60 * 60 * 25 + 3)
+        );
+
+        let wrapped = Timecode::new(12, 0, 0, 0)
+            .unwrap()
+            .with_date(Date::new(2066, 12, 31).unwrap())
+            .with_frame_rate(FrameRate::Fps25);
+        let wrapped = wrapped.checked_add(FrameCount(12 * 60 * 60 * 25)).unwrap();
+        assert_eq!(wrapped.date(), Some(Date::new(1967, 1, 1).unwrap()));
+        let wrapped = wrapped.checked_sub(FrameCount(1)).unwrap();
+        assert_eq!(wrapped.date(), Some(Date::new(2066, 12, 31).unwrap()));
+
+        let undated = Timecode::new(23, 0, 0, 0)
+            .unwrap()
+            .with_frame_rate(FrameRate::Fps24);
+        assert_eq!(
+            undated
+                .checked_add(FrameCount(24 * 60 * 60))
+                .unwrap()
+                .to_string(),
+            "00:00:00:00"
+        );
+        assert_eq!(
+            Timecode::new(1, 0, 0, 0)
+                .unwrap()
+                .with_frame_rate(FrameRate::Fps24)
+                .frames_since(&undated)
+                .unwrap(),
+            FrameCount(-22 * 60 * 60 * 24)
+        );
+    }
+
+    #[test]
+    fn timecode_arithmetic_rejects_timecodes_without_valid_frame_rate() {
+        let parsed: Timecode = "01:00:00:00".parse().unwrap();
+        assert!(parsed.checked_add(FrameCount(1)).is_err());
+        assert!(parsed.frames_since(&parsed).is_err());
+
+        let tc = parsed.with_frame_rate(FrameRate::Fps25);
+        assert!(tc.checked_sub(FrameCount(i64::MIN)).is_err());
+        let frame_29 = Timecode::new(1, 0, 0, 29).unwrap();
+        assert!(tc.frames_since(&frame_29).is_err());
+        assert_eq!(
+            tc.with_frame_rate(FrameRate::Fps30)
+                .frames_since(&frame_29)
+                .unwrap(),
+            FrameCount(-29)
+        );
+    }
+
+    #[test]
+    fn timecodes_convert_to_sample_positions() {
+        let tc: Timecode = "01:00:00;00".parse().unwrap();
+        assert_eq!(
+            tc.to_sample_position(FrameRate::Fps29_97Df, 48_000)
+                .unwrap(),
+            172_799_827
+        );
+        assert_eq!(
+            tc.to_sample_position(FrameRate::Fps24, 44_100).unwrap(),
+            158_760_000
+        );
+        let dropped: Timecode = "00:01:00;02".parse().unwrap();
+        assert!(dropped
+            .to_sample_position(FrameRate::Fps59_94Df, 48_000)
+            .is_err());
+    }
+}
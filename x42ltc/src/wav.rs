@@ -0,0 +1,500 @@
+// x42ltc: src/wav.rs
+//
+// Copyright 2019-2020 Johannes Maibaum <jmaibaum@gmail.com>
+//
+// This file is free software; you can redistribute it and/or modify it
+// under the terms of the GNU Lesser General Public License as
+// published by the Free Software Foundation; either version 3 of the
+// License, or (at your option) any later version.
+//
+// This file is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public
+// License along with this program.  If not, see <http://www.gnu.org/licenses/>.
+//
+// SPDX-License-Identifier: LGPL-3.0-or-later
+use std::io::{self, Seek, SeekFrom, Write};
+
+use crate::{sample_to_i16, sample_to_i24, Direction, Encoder};
+
+pub(crate) const WAVE_FORMAT_PCM: u16 = 1;
+pub(crate) const WAVE_FORMAT_IEEE_FLOAT: u16 = 3;
+
+/// The size of the `bext` chunk without coding history (EBU Tech 3285).
+const BEXT_SIZE: u32 = 602;
+
+/// Sample formats of [`WavWriter`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SampleFormat {
+    /// Unsigned 8 bit PCM, the native format of the encoder.
+    U8,
+    /// Signed 16 bit PCM.
+    I16,
+    /// Signed 24 bit PCM.
+    I24,
+    /// 32 bit IEEE float.
+    F32,
+}
+
+impl SampleFormat {
+    fn bytes_per_sample(&self) -> u16 {
+        match self {
+            SampleFormat::U8 => 1,
+            SampleFormat::I16 => 2,
+            SampleFormat::I24 => 3,
+            SampleFormat::F32 => 4,
+        }
+    }
+}
+
+/// The layout of a WAV file written by [`WavWriter`].
+///
+/// # Example
+///
+/// ```
+/// use x42ltc::{SampleFormat, WavConfig};
+///
+/// // LTC on the second channel of a stereo file.
+/// let config = WavConfig::new(48_000, SampleFormat::I24).channels(2, 1);
+/// assert_eq!(config.ltc_channel, 1);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct WavConfig {
+    pub sample_rate: u32,
+    pub format: SampleFormat,
+    pub channels: u16,
+    /// The channel carrying the LTC, counting from 0.  All other channels are silent.
+    pub ltc_channel: u16,
+    /// The position of the first sample in samples since midnight, as stored in the
+    /// `TimeReference` of the Broadcast Wave `bext` chunk.
+    pub time_reference: u64,
+}
+
+impl WavConfig {
+    /// Create a configuration for a mono file starting at midnight.
+    pub fn new(sample_rate: u32, format: SampleFormat) -> WavConfig {
+        WavConfig {
+            sample_rate,
+            format,
+            channels: 1,
+            ltc_channel: 0,
+            time_reference: 0,
+        }
+    }
+
+    /// Returns this configuration with `channels` channels, the LTC on `ltc_channel`.
+    pub fn channels(mut self, channels: u16, ltc_channel: u16) -> WavConfig {
+        self.channels = channels;
+        self.ltc_channel = ltc_channel;
+        self
+    }
+
+    /// Returns this configuration with the given `TimeReference`, see
+    /// [`Timecode::to_sample_position()`](crate::Timecode::to_sample_position).
+    pub fn time_reference(mut self, time_reference: u64) -> WavConfig {
+        self.time_reference = time_reference;
+        self
+    }
+}
+
+/// Writes LTC to a Broadcast Wave file.
+///
+/// The chunk sizes in the header are filled in by [`.finish()`](#method.finish), or on a best
+/// effort basis when the writer is dropped.
+///
+/// # Example
+///
+/// ```
+/// use std::io::Cursor;
+/// use x42ltc::{Encoder, FrameRate, SampleFormat, Timecode, WavConfig, WavWriter};
+///
+/// let start: Timecode = "10:00:00:00".parse().unwrap();
+/// let time_reference = start.to_sample_position(FrameRate::Fps25, 48_000).unwrap();
+/// let config = WavConfig::new(48_000, SampleFormat::I16).time_reference(time_reference);
+///
+/// let mut encoder = Encoder::new(48_000, FrameRate::Fps25).unwrap();
+/// encoder.set_timecode(start);
+/// let mut wav = WavWriter::new(Cursor::new(Vec::new()), config).unwrap();
+/// for _ in 0..25 {
+///     wav.write_frame(&mut encoder).unwrap();
+///     encoder.increase_timecode();
+/// }
+/// let file = wav.finish().unwrap().into_inner();
+/// assert_eq!(file.len(), 12 + 8 + 16 + 8 + 602 + 8 + 2 * 48_000);
+/// ```
+pub struct WavWriter<W: Write + Seek> {
+    writer: Option<W>,
+    config: WavConfig,
+    data_len: u32,
+    block: Vec<u8>,
+    scratch: Vec<u8>,
+}
+
+impl<W: Write + Seek> WavWriter<W> {
+    /// Write the header to `writer`.
+    ///
+    /// Returns an error of kind `InvalidInput` if there are no channels, or `ltc_channel` is not
+    /// one of them.
+    pub fn new(mut writer: W, config: WavConfig) -> io::Result<WavWriter<W>> {
+        if config.channels == 0 || config.ltc_channel >= config.channels {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "the LTC channel must be one of the channels",
+            ));
+        }
+        writer.write_all(&header(&config, 0))?;
+        Ok(WavWriter {
+            writer: Some(writer),
+            config,
+            data_len: 0,
+            block: Vec::new(),
+            scratch: Vec::new(),
+        })
+    }
+
+    /// The configuration the file is written with.
+    pub fn config(&self) -> WavConfig {
+        self.config
+    }
+
+    /// The number of sample frames (one sample per channel) written so far.
+    pub fn len(&self) -> u64 {
+        u64::from(self.data_len / self.block_align())
+    }
+
+    /// Whether no samples have been written yet.
+    pub fn is_empty(&self) -> bool {
+        self.data_len == 0
+    }
+
+    /// Encode the current frame of `encoder` in the sample format of the file and write it,
+    /// like [`Encoder::encode_frame_f32()`] and friends.  The timecode of the encoder is not
+    /// changed.
+    ///
+    /// Returns the number of samples written per channel.
+    pub fn write_frame(&mut self, encoder: &mut Encoder) -> io::Result<usize> {
+        self.encode_frame(encoder, Direction::Forward)
+    }
+
+    /// Like [`.write_frame()`](#method.write_frame), but with the bits of the frame in reverse
+    /// order, as if played backwards, like [`Encoder::encode_reversed_frame()`].
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::io::Cursor;
+    /// use x42ltc::{Encoder, FrameRate, SampleFormat, WavConfig, WavWriter};
+    ///
+    /// let mut encoder = Encoder::new(48_000, FrameRate::Fps25).unwrap();
+    /// encoder.set_timecode("10:00:00:00".parse().unwrap());
+    /// let config = WavConfig::new(48_000, SampleFormat::F32);
+    /// let mut wav = WavWriter::new(Cursor::new(Vec::new()), config).unwrap();
+    /// for _ in 0..25 {
+    ///     wav.write_reversed_frame(&mut encoder).unwrap();
+    ///     encoder.decrease_timecode();
+    /// }
+    /// assert_eq!(wav.len(), 48_000);
+    /// ```
+    pub fn write_reversed_frame(&mut self, encoder: &mut Encoder) -> io::Result<usize> {
+        self.encode_frame(encoder, Direction::Reverse)
+    }
+
+    /// Write unsigned 8 bit samples as produced by [`Encoder::get_buffer()`] or the
+    /// [`Generator`](crate::Generator), converted to the sample format of the file.  Use this for
+    /// variable-speed LTC, which [`.write_frame()`](#method.write_frame) cannot produce.
+    pub fn write_samples(&mut self, samples: &[u8]) -> io::Result<()> {
+        self.block.clear();
+        for &sample in samples {
+            let value = i32::from(sample) - 128;
+            match self.config.format {
+                SampleFormat::U8 => push_sample(&mut self.block, &self.config, &[sample]),
+                SampleFormat::I16 => push_sample(
+                    &mut self.block,
+                    &self.config,
+                    &((value << 8) as i16).to_le_bytes(),
+                ),
+                SampleFormat::I24 => push_sample(
+                    &mut self.block,
+                    &self.config,
+                    &(value << 16).to_le_bytes()[..3],
+                ),
+                SampleFormat::F32 => {
+                    let value = value as f32 / 127.0;
+                    push_sample(&mut self.block, &self.config, &value.to_le_bytes())
+                }
+            }
+        }
+        self.write_block()
+    }
+
+    /// Fill in the chunk sizes and return the underlying writer, positioned at the end of the
+    /// file.
+    pub fn finish(mut self) -> io::Result<W> {
+        self.update_header()?;
+        Ok(self.writer.take().unwrap())
+    }
+
+    fn encode_frame(&mut self, encoder: &mut Encoder, direction: Direction) -> io::Result<usize> {
+        let reverse = direction == Direction::Reverse;
+        let config = self.config;
+        let too_small = |_| io::Error::other("encoder buffer too small");
+        let block = &mut self.block;
+        block.clear();
+        let len = match config.format {
+            SampleFormat::U8 => {
+                self.scratch.resize(encoder.get_buffer_size(), 0);
+                if reverse {
+                    encoder.encode_reversed_frame();
+                } else {
+                    encoder.encode_frame();
+                }
+                let len = encoder
+                    .copy_audio_to_buffer(&mut self.scratch)
+                    .map_err(too_small)?;
+                for &sample in &self.scratch[..len] {
+                    push_sample(block, &config, &[sample]);
+                }
+                len
+            }
+            // The full precision signal is written as it is computed, without a buffer.
+            format => encoder
+                .modulate(usize::MAX, reverse, |value| match format {
+                    SampleFormat::I16 => {
+                        push_sample(block, &config, &sample_to_i16(value).to_le_bytes())
+                    }
+                    SampleFormat::I24 => push_sample(block, &config, &sample_to_i24(value)),
+                    _ => push_sample(block, &config, &(value as f32).to_le_bytes()),
+                })
+                .map_err(too_small)?,
+        };
+        self.write_block()?;
+        Ok(len)
+    }
+
+    fn block_align(&self) -> u32 {
+        u32::from(self.config.channels * self.config.format.bytes_per_sample())
+    }
+
+    fn write_block(&mut self) -> io::Result<()> {
+        let data_len = u32::try_from(self.block.len())
+            .ok()
+            .and_then(|len| len.checked_add(self.data_len))
+            .filter(|&len| len <= u32::MAX - header_len(&self.config))
+            .ok_or_else(|| io::Error::other("WAV files cannot exceed 4 GiB"))?;
+        self.writer.as_mut().unwrap().write_all(&self.block)?;
+        self.data_len = data_len;
+        Ok(())
+    }
+
+    fn update_header(&mut self) -> io::Result<()> {
+        let writer = self.writer.as_mut().unwrap();
+        writer.seek(SeekFrom::Start(0))?;
+        writer.write_all(&header(&self.config, self.data_len))?;
+        if self.data_len % 2 == 1 {
+            // Chunks are padded to an even size.
+            writer.seek(SeekFrom::End(0))?;
+            writer.write_all(&[0])?;
+        }
+        writer.seek(SeekFrom::End(0))?;
+        writer.flush()
+    }
+}
+
+impl<W: Write + Seek> Drop for WavWriter<W> {
+    fn drop(&mut self) {
+        if self.writer.is_some() {
+            let _ = self.update_header();
+        }
+    }
+}
+
+/// Append one sample frame to `block`, with `ltc` on the LTC channel and silence on all others.
+fn push_sample(block: &mut Vec<u8>, config: &WavConfig, ltc: &[u8]) {
+    for channel in 0..config.channels {
+        if channel == config.ltc_channel {
+            block.extend_from_slice(ltc);
+        } else if config.format == SampleFormat::U8 {
+            block.push(128);
+        } else {
+            block.extend(std::iter::repeat_n(0, ltc.len()));
+        }
+    }
+}
+
+fn header_len(config: &WavConfig) -> u32 {
+    let fact_len = if config.format == SampleFormat::F32 {
+        12
+    } else {
+        0
+    };
+    12 + 8 + fmt_len(config) + fact_len + 8 + BEXT_SIZE + 8
+}
+
+fn fmt_len(config: &WavConfig) -> u32 {
+    if config.format == SampleFormat::F32 {
+        18
+    } else {
+        16
+    }
+}
+
+/// The RIFF header and all chunks up to the start of the sample data.
+fn header(config: &WavConfig, data_len: u32) -> Vec<u8> {
+    let mut header = Vec::with_capacity(header_len(config) as usize);
+    let block_align = config.channels * config.format.bytes_per_sample();
+    let riff_len = header_len(config) - 8 + data_len + data_len % 2;
+
+    header.extend_from_slice(b"RIFF");
+    header.extend_from_slice(&riff_len.to_le_bytes());
+    header.extend_from_slice(b"WAVE");
+
+    header.extend_from_slice(b"fmt ");
+    header.extend_from_slice(&fmt_len(config).to_le_bytes());
+    let format_tag = match config.format {
+        SampleFormat::F32 => WAVE_FORMAT_IEEE_FLOAT,
+        _ => WAVE_FORMAT_PCM,
+    };
+    header.extend_from_slice(&format_tag.to_le_bytes());
+    header.extend_from_slice(&config.channels.to_le_bytes());
+    header.extend_from_slice(&config.sample_rate.to_le_bytes());
+    header.extend_from_slice(&(config.sample_rate * u32::from(block_align)).to_le_bytes());
+    header.extend_from_slice(&block_align.to_le_bytes());
+    header.extend_from_slice(&(8 * config.format.bytes_per_sample()).to_le_bytes());
+    if config.format == SampleFormat::F32 {
+        // No extra format bytes, and the number of samples per channel in the `fact` chunk, as
+        // required for non-PCM formats.
+        header.extend_from_slice(&0u16.to_le_bytes());
+        header.extend_from_slice(b"fact");
+        header.extend_from_slice(&4u32.to_le_bytes());
+        header.extend_from_slice(&(data_len / u32::from(block_align)).to_le_bytes());
+    }
+
+    // Broadcast Wave extension, version 1.  Only the time reference is filled in, all text
+    // fields are left empty.
+    header.extend_from_slice(b"bext");
+    header.extend_from_slice(&BEXT_SIZE.to_le_bytes());
+    let mut bext = [0; BEXT_SIZE as usize];
+    // Description, Originator, OriginatorReference, OriginationDate and OriginationTime precede
+    // the TimeReference.
+    bext[338..346].copy_from_slice(&config.time_reference.to_le_bytes());
+    bext[346..348].copy_from_slice(&1u16.to_le_bytes());
+    header.extend_from_slice(&bext);
+
+    header.extend_from_slice(b"data");
+    header.extend_from_slice(&data_len.to_le_bytes());
+    header
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::{Decoder, FrameRate, Timecode};
+
+    fn read_u32(file: &[u8], offset: usize) -> u32 {
+        u32::from_le_bytes(file[offset..offset + 4].try_into().unwrap())
+    }
+
+    #[test]
+    fn wav_writer_puts_ltc_on_chosen_channel() {
+        let start: Timecode = "01:00:00:00".parse().unwrap();
+        let time_reference = start.to_sample_position(FrameRate::Fps25, 48_000).unwrap();
+        let config = WavConfig::new(48_000, SampleFormat::I16)
+            .channels(3, 2)
+            .time_reference(time_reference);
+
+        let mut encoder = crate::Encoder::new(48_000, FrameRate::Fps25).unwrap();
+        encoder.set_timecode(start);
+        let mut wav = WavWriter::new(Cursor::new(Vec::new()), config).unwrap();
+        for _ in 0..10 {
+            assert_eq!(wav.write_frame(&mut encoder).unwrap(), 1920);
+            encoder.increase_timecode();
+        }
+        assert_eq!(wav.len(), 19_200);
+        let file = wav.finish().unwrap().into_inner();
+
+        assert_eq!(&file[..4], b"RIFF");
+        assert_eq!(read_u32(&file, 4) as usize, file.len() - 8);
+        assert_eq!(&file[36..40], b"bext");
+        assert_eq!(read_u32(&file, 44 + 338), 172_800_000);
+        let data = header_len(&config) as usize;
+        assert_eq!(&file[data - 8..data - 4], b"data");
+        assert_eq!(read_u32(&file, data - 4) as usize, 3 * 2 * 19_200);
+
+        let channels: Vec<[i16; 3]> = file[data..]
+            .chunks_exact(6)
+            .map(|frame| {
+                let sample = |i: usize| i16::from_le_bytes([frame[2 * i], frame[2 * i + 1]]);
+                [sample(0), sample(1), sample(2)]
+            })
+            .collect();
+        assert!(channels.iter().all(|frame| frame[0] == 0 && frame[1] == 0));
+
+        let ltc: Vec<i16> = channels.iter().map(|frame| frame[2]).collect();
+        let mut decoder = Decoder::new(1920, 16).unwrap();
+        let frames: Vec<_> = decoder.decode(&ltc).collect();
+        assert_eq!(frames.len(), 9);
+        assert_eq!(frames[0].timecode().to_string(), "01:00:00:00");
+    }
+
+    #[test]
+    fn wav_writer_writes_reverse_ltc_at_full_precision() {
+        let config = WavConfig::new(48_000, SampleFormat::I16);
+        let mut encoder = crate::Encoder::new(48_000, FrameRate::Fps25).unwrap();
+        encoder.set_timecode("01:00:00:00".parse().unwrap());
+        let mut wav = WavWriter::new(Cursor::new(Vec::new()), config).unwrap();
+        for _ in 0..10 {
+            assert_eq!(wav.write_reversed_frame(&mut encoder).unwrap(), 1920);
+            encoder.decrease_timecode();
+        }
+        // Not clamped to the -42 dBFS floor of the 8 bit output.
+        encoder.set_volume(-48.0).unwrap();
+        wav.write_reversed_frame(&mut encoder).unwrap();
+        let file = wav.finish().unwrap().into_inner();
+
+        let data = header_len(&config) as usize;
+        let ltc: Vec<i16> = file[data..]
+            .chunks_exact(2)
+            .map(|s| i16::from_le_bytes([s[0], s[1]]))
+            .collect();
+        assert_eq!(ltc[19_200..].iter().map(|s| s.abs()).max(), Some(130));
+
+        let mut decoder = Decoder::new(1920, 16).unwrap();
+        let frames: Vec<_> = decoder.decode(&ltc[..19_200]).collect();
+        assert_eq!(frames.len(), 9);
+        assert!(frames.iter().all(|frame| frame.is_reverse()));
+        assert_eq!(frames[0].timecode().to_string(), "01:00:00:00");
+        assert_eq!(frames[8].timecode().to_string(), "00:59:59:17");
+    }
+
+    #[test]
+    fn wav_writer_converts_8_bit_samples() {
+        let config = WavConfig::new(8_000, SampleFormat::F32);
+        let mut wav = WavWriter::new(Cursor::new(Vec::new()), config).unwrap();
+        wav.write_samples(&[128, 255, 1]).unwrap();
+        let file = wav.finish().unwrap().into_inner();
+
+        assert_eq!(&file[38..42], b"fact");
+        assert_eq!(read_u32(&file, 46), 3);
+        let data = header_len(&config) as usize;
+        let samples: Vec<f32> = file[data..]
+            .chunks_exact(4)
+            .map(|s| f32::from_le_bytes(s.try_into().unwrap()))
+            .collect();
+        assert_eq!(samples, [0.0, 1.0, -1.0]);
+
+        let config = WavConfig::new(8_000, SampleFormat::U8).channels(2, 0);
+        let mut wav = WavWriter::new(Cursor::new(Vec::new()), config).unwrap();
+        wav.write_samples(&[200]).unwrap();
+        let file = wav.finish().unwrap().into_inner();
+        let data = header_len(&config) as usize;
+        assert_eq!(&file[data..], [200, 128]);
+
+        let config = WavConfig::new(8_000, SampleFormat::U8).channels(2, 2);
+        assert!(WavWriter::new(Cursor::new(Vec::new()), config).is_err());
+    }
+}
@@ -0,0 +1,447 @@
+// x42ltc: src/wav_decoder.rs
+//
+// Copyright 2019-2020 Johannes Maibaum <jmaibaum@gmail.com>
+//
+// This file is free software; you can redistribute it and/or modify it
+// under the terms of the GNU Lesser General Public License as
+// published by the Free Software Foundation; either version 3 of the
+// License, or (at your option) any later version.
+//
+// This file is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public
+// License along with this program.  If not, see <http://www.gnu.org/licenses/>.
+//
+// SPDX-License-Identifier: LGPL-3.0-or-later
+use std::io::{self, Read};
+
+use crate::wav::{WAVE_FORMAT_IEEE_FLOAT, WAVE_FORMAT_PCM};
+use crate::{DecodedFrame, Decoder, FrameRate};
+
+const WAVE_FORMAT_EXTENSIBLE: u16 = 0xFFFE;
+
+/// The number of sample frames read from the file at once.
+const CHUNK_FRAMES: usize = 4096;
+
+/// The sample formats [`decode_wav()`] can read.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Format {
+    U8,
+    I16,
+    I24,
+    I32,
+    F32,
+    F64,
+}
+
+impl Format {
+    fn from_header(format_tag: u16, bits_per_sample: u16) -> Option<Format> {
+        match (format_tag, bits_per_sample) {
+            (WAVE_FORMAT_PCM, 8) => Some(Format::U8),
+            (WAVE_FORMAT_PCM, 16) => Some(Format::I16),
+            (WAVE_FORMAT_PCM, 24) => Some(Format::I24),
+            (WAVE_FORMAT_PCM, 32) => Some(Format::I32),
+            (WAVE_FORMAT_IEEE_FLOAT, 32) => Some(Format::F32),
+            (WAVE_FORMAT_IEEE_FLOAT, 64) => Some(Format::F64),
+            _ => None,
+        }
+    }
+
+    fn bytes_per_sample(&self) -> usize {
+        match self {
+            Format::U8 => 1,
+            Format::I16 => 2,
+            Format::I24 => 3,
+            Format::I32 | Format::F32 => 4,
+            Format::F64 => 8,
+        }
+    }
+}
+
+/// Decode the LTC on channel `channel` (counting from 0) of a WAV file.
+///
+/// The header is read right away, so that unsupported files are rejected with an error of kind
+/// [`InvalidData`](io::ErrorKind::InvalidData).  PCM with 8, 16, 24 or 32 bits per sample and
+/// 32 or 64 bit IEEE float are supported, also when wrapped in `WAVE_FORMAT_EXTENSIBLE`.
+///
+/// The decoder is set up for 25 fps at the sample rate of the file, which is close enough for
+/// any frame rate.  Use [`decode_wav_with_frame_rate()`] if the frame rate is known.  The sample
+/// offsets of the decoded frames count from the first sample of the file.
+///
+/// # Example
+///
+/// ```
+/// use std::io::Cursor;
+/// use x42ltc::{Encoder, FrameRate, SampleFormat, WavConfig, WavWriter};
+///
+/// let mut encoder = Encoder::new(44_100, FrameRate::Fps30).unwrap();
+/// let config = WavConfig::new(44_100, SampleFormat::I24).channels(2, 1);
+/// let mut wav = WavWriter::new(Cursor::new(Vec::new()), config).unwrap();
+/// for _ in 0..10 {
+///     wav.write_frame(&mut encoder).unwrap();
+///     encoder.increase_timecode();
+/// }
+/// let file = wav.finish().unwrap();
+///
+/// let frames: Vec<_> = x42ltc::decode_wav(Cursor::new(file.into_inner()), 1)
+///     .unwrap()
+///     .collect();
+/// assert_eq!(frames.len(), 9);
+/// assert_eq!(frames[1].timecode().to_string(), "00:00:00:01");
+/// assert_eq!(frames[1].off_start(), 1470);
+/// ```
+pub fn decode_wav<R: Read>(reader: R, channel: u16) -> io::Result<WavFrames<R>> {
+    decode_wav_with_frame_rate(reader, channel, FrameRate::Fps25)
+}
+
+/// Like [`decode_wav()`], with the decoder set up for `frame_rate`.
+pub fn decode_wav_with_frame_rate<R: Read>(
+    mut reader: R,
+    channel: u16,
+    frame_rate: FrameRate,
+) -> io::Result<WavFrames<R>> {
+    let mut riff = [0; 12];
+    reader.read_exact(&mut riff)?;
+    if &riff[..4] != b"RIFF" || &riff[8..] != b"WAVE" {
+        return Err(invalid_data("not a WAV file"));
+    }
+
+    let mut layout = None;
+    loop {
+        let mut chunk = [0; 8];
+        reader.read_exact(&mut chunk)?;
+        let len = u32::from_le_bytes(chunk[4..].try_into().unwrap());
+        match &chunk[..4] {
+            b"fmt " => {
+                // Read no more than the 40 bytes of WAVE_FORMAT_EXTENSIBLE and skip the rest,
+                // the length comes from the file and may be anything.
+                let mut buffer = [0; 40];
+                let prefix = buffer.len().min(len as usize);
+                reader.read_exact(&mut buffer[..prefix])?;
+                let padded = u64::from(len) + u64::from(len % 2);
+                io::copy(
+                    &mut reader.by_ref().take(padded - prefix as u64),
+                    &mut io::sink(),
+                )?;
+                let fmt = &buffer[..prefix];
+                if fmt.len() < 16 {
+                    return Err(invalid_data("fmt chunk too short"));
+                }
+                let u16_at = |i: usize| u16::from_le_bytes([fmt[i], fmt[i + 1]]);
+                let mut format_tag = u16_at(0);
+                if format_tag == WAVE_FORMAT_EXTENSIBLE && fmt.len() >= 26 {
+                    // The sub format GUID starts with the actual format tag.
+                    format_tag = u16_at(24);
+                }
+                let format = Format::from_header(format_tag, u16_at(14))
+                    .ok_or_else(|| invalid_data("unsupported sample format"))?;
+                let channels = u16_at(2);
+                if channel >= channels {
+                    return Err(invalid_data("channel out of range"));
+                }
+                let sample_rate = u32::from_le_bytes(fmt[4..8].try_into().unwrap());
+                layout = Some((format, channels, sample_rate));
+            }
+            b"data" => {
+                let (format, channels, sample_rate) =
+                    layout.ok_or_else(|| invalid_data("data chunk before fmt chunk"))?;
+                let decoder = Decoder::with_frame_rate(sample_rate, frame_rate, 32)
+                    .map_err(|_| invalid_data("unsupported sample rate"))?;
+                // Streamed files may not know the length of their data.
+                let len = if len == u32::MAX {
+                    u64::MAX
+                } else {
+                    u64::from(len)
+                };
+                let block_align = format.bytes_per_sample() * usize::from(channels);
+                return Ok(WavFrames {
+                    reader: reader.take(len),
+                    decoder,
+                    format,
+                    channels,
+                    channel,
+                    sample_rate,
+                    buffer: vec![0; CHUNK_FRAMES * block_align],
+                    filled: 0,
+                    samples: Samples::default(),
+                    error: None,
+                });
+            }
+            _ => {
+                let padded = u64::from(len) + u64::from(len % 2);
+                io::copy(&mut reader.by_ref().take(padded), &mut io::sink())?;
+            }
+        }
+    }
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+/// Iterator over the LTC frames of a WAV file, returned by [`decode_wav()`].
+///
+/// The iteration ends at the end of the sample data, or at the first read error, which is then
+/// available from [`.error()`](#method.error).
+pub struct WavFrames<R> {
+    reader: io::Take<R>,
+    decoder: Decoder,
+    format: Format,
+    channels: u16,
+    channel: u16,
+    sample_rate: u32,
+    buffer: Vec<u8>,
+    filled: usize,
+    samples: Samples,
+    error: Option<io::Error>,
+}
+
+/// The samples of the LTC channel, converted for the decoder and reused for each chunk.
+#[derive(Default)]
+struct Samples {
+    u8: Vec<u8>,
+    i16: Vec<i16>,
+    f32: Vec<f32>,
+    f64: Vec<f64>,
+}
+
+impl<R: Read> WavFrames<R> {
+    /// The sample rate of the file.
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    /// The number of channels of the file.
+    pub fn channels(&self) -> u16 {
+        self.channels
+    }
+
+    /// The error which ended the iteration, if any.
+    pub fn error(&self) -> Option<&io::Error> {
+        self.error.as_ref()
+    }
+
+    /// Feed the next chunk of samples to the decoder, returns `false` at the end of the data.
+    fn read_chunk(&mut self) -> bool {
+        let len = match self.reader.read(&mut self.buffer[self.filled..]) {
+            Ok(0) => return false,
+            Ok(len) => len,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => return true,
+            Err(e) => {
+                self.error = Some(e);
+                return false;
+            }
+        };
+        self.filled += len;
+
+        let width = self.format.bytes_per_sample();
+        let block_align = width * usize::from(self.channels);
+        let usable = self.filled - self.filled % block_align;
+        let offset = width * usize::from(self.channel);
+        let samples = self.buffer[..usable]
+            .chunks_exact(block_align)
+            .map(|block| &block[offset..offset + width]);
+        let buffers = &mut self.samples;
+        match self.format {
+            Format::U8 => {
+                buffers.u8.clear();
+                buffers.u8.extend(samples.map(|s| s[0]));
+                self.decoder.write(&buffers.u8);
+            }
+            Format::I16 => {
+                buffers.i16.clear();
+                buffers
+                    .i16
+                    .extend(samples.map(|s| i16::from_le_bytes([s[0], s[1]])));
+                self.decoder.write_i16(&buffers.i16);
+            }
+            Format::I24 => {
+                buffers.f32.clear();
+                buffers.f32.extend(
+                    samples.map(|s| {
+                        i32::from_le_bytes([0, s[0], s[1], s[2]]) as f32 / 2_147_483_648.0
+                    }),
+                );
+                self.decoder.write_f32(&buffers.f32);
+            }
+            Format::I32 => {
+                buffers.f32.clear();
+                buffers.f32.extend(
+                    samples.map(|s| {
+                        i32::from_le_bytes(s.try_into().unwrap()) as f32 / 2_147_483_648.0
+                    }),
+                );
+                self.decoder.write_f32(&buffers.f32);
+            }
+            Format::F32 => {
+                buffers.f32.clear();
+                buffers
+                    .f32
+                    .extend(samples.map(|s| f32::from_le_bytes(s.try_into().unwrap())));
+                self.decoder.write_f32(&buffers.f32);
+            }
+            Format::F64 => {
+                buffers.f64.clear();
+                buffers
+                    .f64
+                    .extend(samples.map(|s| f64::from_le_bytes(s.try_into().unwrap())));
+                self.decoder.write_f64(&buffers.f64);
+            }
+        }
+
+        self.buffer.copy_within(usable..self.filled, 0);
+        self.filled -= usable;
+        true
+    }
+}
+
+impl<R: Read> Iterator for WavFrames<R> {
+    type Item = DecodedFrame;
+
+    fn next(&mut self) -> Option<DecodedFrame> {
+        loop {
+            if let Some(frame) = self.decoder.read() {
+                return Some(frame);
+            }
+            if !self.read_chunk() {
+                return None;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::{Encoder, SampleFormat, Timecode, WavConfig, WavWriter};
+
+    fn encode_wav(config: WavConfig, frame_rate: FrameRate, frames: usize) -> Vec<u8> {
+        let mut encoder = Encoder::new(config.sample_rate, frame_rate).unwrap();
+        encoder.set_timecode("10:00:00:00".parse::<Timecode>().unwrap());
+        let mut wav = WavWriter::new(Cursor::new(Vec::new()), config).unwrap();
+        for _ in 0..frames {
+            wav.write_frame(&mut encoder).unwrap();
+            encoder.increase_timecode();
+        }
+        wav.finish().unwrap().into_inner()
+    }
+
+    #[test]
+    fn decode_wav_reads_all_writer_formats() {
+        for format in [
+            SampleFormat::U8,
+            SampleFormat::I16,
+            SampleFormat::I24,
+            SampleFormat::F32,
+        ] {
+            let config = WavConfig::new(48_000, format).channels(3, 2);
+            let file = encode_wav(config, FrameRate::Fps25, 20);
+            let mut wav = decode_wav(Cursor::new(file), 2).unwrap();
+            assert_eq!(wav.sample_rate(), 48_000);
+            assert_eq!(wav.channels(), 3);
+
+            let frames: Vec<_> = wav.by_ref().collect();
+            assert!(wav.error().is_none());
+            assert_eq!(frames.len(), 19, "{:?}", format);
+            for (i, frame) in frames.iter().enumerate() {
+                assert_eq!(frame.timecode().frame(), i as u8);
+                assert!((frame.off_start() - 1920 * i as i64).abs() < 40);
+            }
+        }
+    }
+
+    #[test]
+    fn decode_wav_reads_extensible_32_bit_and_64_bit_float() {
+        // Convert an 8 bit mono file to the given format by hand.
+        let file = encode_wav(
+            WavConfig::new(44_100, SampleFormat::U8),
+            FrameRate::Fps30,
+            10,
+        );
+        let data = file.len() - 10 * 1470;
+        let wav = |format_tag: u16, width: usize, convert: &dyn Fn(u8) -> Vec<u8>| {
+            let mut fmt = Vec::new();
+            fmt.extend_from_slice(&WAVE_FORMAT_EXTENSIBLE.to_le_bytes());
+            fmt.extend_from_slice(&1u16.to_le_bytes());
+            fmt.extend_from_slice(&44_100u32.to_le_bytes());
+            fmt.extend_from_slice(&(44_100 * width as u32).to_le_bytes());
+            fmt.extend_from_slice(&(width as u16).to_le_bytes());
+            fmt.extend_from_slice(&(8 * width as u16).to_le_bytes());
+            fmt.extend_from_slice(&22u16.to_le_bytes());
+            fmt.extend_from_slice(&(8 * width as u16).to_le_bytes());
+            fmt.extend_from_slice(&0u32.to_le_bytes());
+            fmt.extend_from_slice(&format_tag.to_le_bytes());
+            fmt.extend_from_slice(&[0; 14]);
+            let samples: Vec<u8> = file[data..].iter().flat_map(|&s| convert(s)).collect();
+
+            let mut wav = Vec::new();
+            wav.extend_from_slice(b"RIFF\0\0\0\0WAVEfmt ");
+            wav.extend_from_slice(&(fmt.len() as u32).to_le_bytes());
+            wav.extend_from_slice(&fmt);
+            wav.extend_from_slice(b"data");
+            wav.extend_from_slice(&(samples.len() as u32).to_le_bytes());
+            wav.extend_from_slice(&samples);
+            wav
+        };
+
+        let i32_file = wav(WAVE_FORMAT_PCM, 4, &|s| {
+            ((i32::from(s) - 128) << 24).to_le_bytes().to_vec()
+        });
+        let f64_file = wav(WAVE_FORMAT_IEEE_FLOAT, 8, &|s| {
+            ((f64::from(s) - 128.0) / 128.0).to_le_bytes().to_vec()
+        });
+        for file in [i32_file, f64_file] {
+            let frames: Vec<_> = decode_wav_with_frame_rate(Cursor::new(file), 0, FrameRate::Fps30)
+                .unwrap()
+                .collect();
+            assert_eq!(frames.len(), 9);
+            assert_eq!(frames[8].timecode().to_string(), "10:00:00:08");
+        }
+    }
+
+    #[test]
+    fn decode_wav_rejects_invalid_files() {
+        let file = encode_wav(
+            WavConfig::new(48_000, SampleFormat::I16),
+            FrameRate::Fps25,
+            1,
+        );
+        let error = |file: &[u8], channel| decode_wav(file, channel).err().unwrap().kind();
+        assert_eq!(error(&file, 1), io::ErrorKind::InvalidData);
+        assert_eq!(error(&file[..20], 0), io::ErrorKind::UnexpectedEof);
+        assert_eq!(error(b"RIFX\0\0\0\0WAVE", 0), io::ErrorKind::InvalidData);
+
+        let mut float_16 = file.clone();
+        float_16[20..22].copy_from_slice(&WAVE_FORMAT_IEEE_FLOAT.to_le_bytes());
+        assert_eq!(error(&float_16, 0), io::ErrorKind::InvalidData);
+
+        // A huge fmt chunk length is skipped over, not allocated.
+        let mut huge_fmt = file.clone();
+        huge_fmt[16..20].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert_eq!(error(&huge_fmt, 0), io::ErrorKind::UnexpectedEof);
+    }
+
+    #[test]
+    fn decode_wav_skips_fmt_chunk_extensions() {
+        let file = encode_wav(
+            WavConfig::new(48_000, SampleFormat::I16),
+            FrameRate::Fps25,
+            10,
+        );
+        // Pad the 16 byte fmt chunk with an odd-sized extension of 35 bytes.
+        let mut padded = file[..16].to_vec();
+        padded.extend_from_slice(&51u32.to_le_bytes());
+        padded.extend_from_slice(&file[20..36]);
+        padded.extend_from_slice(&[0xFF; 36]);
+        padded.extend_from_slice(&file[36..]);
+
+        let frames: Vec<_> = decode_wav(Cursor::new(padded), 0).unwrap().collect();
+        assert_eq!(frames.len(), 9);
+        assert_eq!(frames[8].timecode().to_string(), "10:00:00:08");
+    }
+}
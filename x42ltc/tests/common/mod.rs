@@ -0,0 +1,53 @@
+// x42ltc: tests/common/mod.rs
+//
+// Copyright 2019-2020 Johannes Maibaum <jmaibaum@gmail.com>
+//
+// This file is free software; you can redistribute it and/or modify it
+// under the terms of the GNU Lesser General Public License as
+// published by the Free Software Foundation; either version 3 of the
+// License, or (at your option) any later version.
+//
+// This file is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public
+// License along with this program.  If not, see <http://www.gnu.org/licenses/>.
+//
+// SPDX-License-Identifier: LGPL-3.0-or-later
+use x42ltc::{DecodedFrame, Decoder};
+
+/// Format a frame like vendor/tests/ltcdecode.c does.
+fn format_frame(frame: &DecodedFrame) -> String {
+    let tc = frame.timecode();
+    format!(
+        "{} {} {:02}:{:02}:{:02}{}{:02} | {:8} {:8}{}\r\n",
+        tc.date()
+            .map(|date| date.to_string())
+            .unwrap_or_else(|| "2000-00-00".to_string()),
+        tc.timezone()
+            .map(|timezone| timezone.to_string())
+            .unwrap_or_else(|| "+0000".to_string()),
+        tc.hours(),
+        tc.mins(),
+        tc.secs(),
+        if tc.is_drop_frame() { '.' } else { ':' },
+        tc.frame(),
+        frame.off_start(),
+        frame.off_end(),
+        if frame.is_reverse() { "  R" } else { "" },
+    )
+}
+
+/// Adapted C test code from vendor/tests/ltcdecode.c
+pub fn decode_raw(data: &[u8], apv: i32) -> String {
+    let mut decoder = Decoder::new(apv, 32).unwrap();
+    let mut output = String::new();
+    for chunk in data.chunks(1024) {
+        for frame in decoder.decode(chunk) {
+            output.push_str(&format_frame(&frame));
+        }
+    }
+    output
+}
@@ -0,0 +1,32 @@
+// x42ltc: tests/decoder_tests.rs
+//
+// Copyright 2019-2020 Johannes Maibaum <jmaibaum@gmail.com>
+//
+// This file is free software; you can redistribute it and/or modify it
+// under the terms of the GNU Lesser General Public License as
+// published by the Free Software Foundation; either version 3 of the
+// License, or (at your option) any later version.
+//
+// This file is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public
+// License along with this program.  If not, see <http://www.gnu.org/licenses/>.
+//
+// SPDX-License-Identifier: LGPL-3.0-or-later
+use std::fs;
+
+mod common;
+use common::decode_raw;
+
+#[test]
+/// Decode raw test file provided in vendor/tests/timecode.raw
+fn decode_timecode_dot_raw_in_chunks() {
+    let raw_contents = fs::read("../x42ltc-sys/vendor/tests/timecode.raw").unwrap();
+    let expected_output = fs::read_to_string("../x42ltc-sys/vendor/tests/timecode.txt").unwrap();
+
+    let decoded_output = decode_raw(&raw_contents, 882);
+    assert_eq!(decoded_output, expected_output);
+}
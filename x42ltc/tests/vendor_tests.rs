@@ -0,0 +1,64 @@
+// x42ltc: tests/vendor_tests.rs
+//
+// Copyright 2019-2020 Johannes Maibaum <jmaibaum@gmail.com>
+//
+// This file is free software; you can redistribute it and/or modify it
+// under the terms of the GNU Lesser General Public License as
+// published by the Free Software Foundation; either version 3 of the
+// License, or (at your option) any later version.
+//
+// This file is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// Lesser General Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public
+// License along with this program.  If not, see <http://www.gnu.org/licenses/>.
+//
+// SPDX-License-Identifier: LGPL-3.0-or-later
+use std::fs;
+
+use x42ltc::{BgFlags, Date, Encoder, EncoderConfig, FrameRate, Timecode, TimezoneOffset};
+
+mod common;
+use common::decode_raw;
+
+/// Adapted C test code from vendor/tests/ltcencode.c
+fn encode_raw(sample_rate: u32) -> Vec<u8> {
+    let config = EncoderConfig::new(sample_rate, FrameRate::Fps25).flags(BgFlags::USE_DATE);
+    let mut encoder = Encoder::with_config(config).unwrap();
+    encoder.set_filter(25.0);
+    encoder.set_volume(-18.0).unwrap();
+    encoder.set_timecode(
+        Timecode::new(23, 59, 59, 0)
+            .unwrap()
+            .with_date(Date::new(2008, 12, 31).unwrap())
+            .with_timezone("+0100".parse::<TimezoneOffset>().unwrap()),
+    );
+
+    let mut output = Vec::new();
+    for _ in 0..50 {
+        encoder.encode_frame();
+        encoder.extend_into(&mut output);
+        encoder.increase_timecode();
+    }
+    output
+}
+
+#[test]
+/// Adapted C test code from vendor/tests/ltc{de,en}code.c
+fn ltc_encode_then_decode_48000() {
+    let decoded_output = decode_raw(&encode_raw(48_000), 1920);
+    let expected_output =
+        fs::read_to_string("../x42ltc-sys/vendor/tests/expect_48k_2sec.txt").unwrap();
+    assert_eq!(decoded_output, expected_output);
+}
+
+#[test]
+/// Adapted C test code from vendor/tests/ltc{de,en}code.c
+fn ltc_encode_then_decode_192000() {
+    let decoded_output = decode_raw(&encode_raw(192_000), 7680);
+    let expected_output =
+        fs::read_to_string("../x42ltc-sys/vendor/tests/expect_96k_2sec.txt").unwrap();
+    assert_eq!(decoded_output, expected_output);
+}